@@ -1,3 +1,31 @@
+use std::process::Command;
+
 fn main() {
+  // Build metadata for `get_build_info`
+  let git_hash = Command::new("git")
+    .args(["rev-parse", "--short", "HEAD"])
+    .output()
+    .ok()
+    .filter(|out| out.status.success())
+    .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+    .unwrap_or_default();
+  println!("cargo:rustc-env=MAESTRO_GIT_HASH={}", git_hash);
+
+  let target = std::env::var("TARGET").unwrap_or_default();
+  println!("cargo:rustc-env=MAESTRO_TARGET={}", target);
+
+  // Cargo sets CARGO_FEATURE_<NAME> for every enabled feature of this crate
+  let mut features: Vec<String> = std::env::vars()
+    .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(|f| f.to_lowercase().replace('_', "-")))
+    .collect();
+  features.sort();
+  println!("cargo:rustc-env=MAESTRO_FEATURES={}", features.join(","));
+
+  for head in ["../../.git/HEAD", "../../.git/refs"] {
+    if std::path::Path::new(head).exists() {
+      println!("cargo:rerun-if-changed={}", head);
+    }
+  }
+
   tauri_build::build()
 }
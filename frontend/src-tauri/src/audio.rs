@@ -1,29 +1,550 @@
 use base64::Engine;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{SampleFormat, StreamConfig};
-use serde::Serialize;
-use std::sync::atomic::{AtomicBool, Ordering};
+use cpal::{BufferSize, SampleFormat, StreamConfig, SupportedBufferSize};
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 // ── Target format for all audio sent to backend ──
-const TARGET_SAMPLE_RATE: u32 = 16000;
+const TARGET_SAMPLE_RATE: u32 = 16000; // default per-source rate; the mix always uses it
+const MIN_TARGET_RATE: u32 = 8000;
+const MAX_TARGET_RATE: u32 = 96000;
+const MAX_BUFFER_FRAMES: u32 = 16384; // ~340ms at 48 kHz
 
 // ── VAD parameters ──
 const VAD_ENERGY_THRESHOLD: f32 = 0.005; // RMS energy threshold for speech
-const VAD_SILENCE_FRAMES: usize = 24; // ~1.5s of silence at 16kHz/1024-sample frames
+const VAD_SILENCE_MS: u32 = 1500; // trailing silence that ends an utterance
 const VAD_MIN_SPEECH_FRAMES: usize = 5; // minimum ~320ms of speech to emit a chunk
+const VAD_NOISE_FLOOR_ALPHA: f32 = 0.02; // EMA weight per silent frame (~3s time constant)
+const VAD_WARMUP_MS: u32 = 200; // startup transient ignored after each start
+const VAD_COOLDOWN_MS: u32 = 150; // after a flush, new speech must be sustained
+const VAD_COOLDOWN_ONSET_FRAMES: usize = 3; // speech frames that reopen during cooldown
+const MAX_RELEASE_DECREMENT_FRAMES: usize = 100;
+const MIN_DOWNMIX_GAIN: f32 = 1.0;
+const MAX_DOWNMIX_GAIN: f32 = 8.0;
+
+/// Size of the header `Container::Wav` prepends
+const WAV_HEADER_LEN: usize = 44;
+
+// ── Live speaking indicator ──
+const DEFAULT_SPEAKING_HOLD_MS: u32 = 300; // bridges the gaps between words
+const MAX_SPEAKING_HOLD_MS: u32 = 10_000;
+
+// ── Wake trigger ──
+const DEFAULT_WAKE_SPEECH_MS: u32 = 600;
+const WAKE_SPEECH_MS_RANGE: std::ops::RangeInclusive<u32> = 100..=10_000;
+const WAKE_MIN_SILENCE_MS: u64 = 2000; // mic quiet this long before speech re-arms it
+
+// ── Peak normalization ──
+const DEFAULT_PEAK_TARGET_DBFS: f32 = -1.0;
+const MAX_PEAK_BOOST_DB: f32 = 24.0; // so a quiet utterance's noise isn't raised without bound
+
+// ── Idle stop ──
+const MAX_IDLE_MINS: u32 = 24 * 60;
+
+// ── Loopback fallback ──
+const DEFAULT_LOOPBACK_PROBE_MS: u32 = 300;
+const MAX_LOOPBACK_PROBE_MS: u32 = 5000;
+
+// ── Utterance hard cap ──
+const DEFAULT_MAX_UTTERANCE_SECS: u32 = 60;
+const MAX_UTTERANCE_SECS: u32 = 3600;
+
+// ── Raw diagnostic capture ──
+const RAW_AUDIO_CHUNK_MS: u32 = 100;
+const MAX_RAW_CAPTURE_MS: u64 = 30_000;
+
+// ── Continuous recording ──
+const DEFAULT_CONTINUOUS_WINDOW_SECS: u32 = 10;
+const MAX_CONTINUOUS_WINDOW_SECS: u32 = 300;
+
+// ── UI level metering ──
+const ENERGY_FRAME_INTERVAL_MS: u32 = 50;
+const ENERGY_TRACE_MS: u32 = 50; // window of each AudioChunk.energy_trace value
+const DBFS_FLOOR: f32 = -120.0; // reported for digital silence instead of -inf
+const DEFAULT_METER_FLOOR_DBFS: f32 = -90.0;
+
+// ── Soft limiter ──
+const LIMITER_KNEE: f32 = 0.8; // linear below this level, saturating above
+
+// ── Suspend-on-silence ──
+// Raw RMS that wakes a suspended stream; below the lowest adaptive threshold
+// so nothing the VAD could call speech is skipped
+const SUSPEND_WAKE_RMS: f32 = 0.001;
+
+// ── Chunk size limit (base64 bytes) ──
+const DEFAULT_MAX_CHUNK_B64_BYTES: usize = 16 * 1024 * 1024; // ~6.5 min of 16 kHz i16
+const MIN_CHUNK_B64_BYTES: usize = 64 * 1024;
+const MAX_CHUNK_B64_BYTES: usize = 256 * 1024 * 1024;
+
+// ── File source ──
+const FILE_SOURCE_MAX_SPEED: f32 = 16.0; // playback speed multiplier cap
+
+// ── Stream liveness ──
+const STREAM_STALE_MS: u64 = 2000; // a held stream silent for this long is dead
+
+// ── Stereo phase check ──
+const PHASE_ANTI_CORRELATION: f32 = -0.8; // L/R correlation that counts as inverted
+const PHASE_CHECK_CALLBACKS: usize = 20; // consecutive inverted callbacks before switching
+
+// ── Echo cancellation (NLMS, loopback as reference) ──
+const AEC_DEFAULT_TAIL_MS: u32 = 128; // echo path length the filter covers
+const AEC_MAX_TAIL_MS: u32 = 512;
+const AEC_STEP: f32 = 0.5; // NLMS step size (0..2; lower adapts slower but steadier)
+const AEC_EPSILON: f32 = 1e-6; // regularizes the step while the reference is silent
+const AEC_MAX_QUEUED_MS: u32 = 1000; // reference audio kept waiting for mic samples
+
+// ── Fake-stereo detection ──
+const CHANNEL_CHECK_INTERVAL: usize = 50; // callbacks between duplicate-channel checks
+const CHANNEL_MATCH_TOLERANCE: f32 = 1e-4; // max sample difference for "identical"
+
+// ── VAD analysis filters ──
+const BANDPASS_LOW_HZ: f32 = 300.0; // telephony band
+const BANDPASS_HIGH_HZ: f32 = 3400.0;
+const HIGHPASS_HZ: f32 = 150.0; // below the voice fundamental of most speakers
+
+/// How many recent chunks keep downstream annotations (see `annotate_chunk`)
+const MAX_ANNOTATIONS: usize = 256;
+/// Queue length at which the backpressure policy kicks in (see
+/// `set_backpressure_policy`); the "buffer" policy ignores it
+const MAX_QUEUED_CHUNKS: usize = 256;
+/// Recent chunks kept addressable by seq for `compute_spectrogram`; smaller
+/// than `MAX_ANNOTATIONS` since each holds its full PCM
+const MAX_RECENT_CHUNKS: usize = 32;
+/// Spectrogram FFT size bounds
+const MIN_FFT_SIZE: usize = 16;
+const MAX_FFT_SIZE: usize = 8192;
+/// Evenly split speech seeded into the balance score (see `get_balance_score`)
+const BALANCE_PRIOR_SECS: f32 = 10.0;
+
+/// Per-utterance talk records kept in memory (oldest dropped first); roughly
+/// several hours of conversation
+const MAX_TALK_HISTORY: usize = 10_000;
+/// Most buckets `get_talk_ratio_buckets` returns; longer spans keep the
+/// most recent ones
+const MAX_TALK_BUCKETS: usize = 1_000;
+const MAX_TALK_BUCKET_SECS: u32 = 86_400;
+
+/// VAD transitions kept per accumulator while `vad_log` is on
+const MAX_VAD_LOG: usize = 200;
 
 // ── Serializable metadata sent alongside audio chunks ──
 #[derive(Debug, Clone, Serialize)]
 pub struct AudioChunk {
-    /// base64-encoded 16-bit PCM, 16 kHz mono
+    /// base64-encoded little-endian PCM (see `sample_type`), mono at
+    /// `sample_rate`
     pub audio_b64: String,
     /// "mic" or "loopback"
     pub source: String,
     /// duration of this chunk in seconds
     pub duration_secs: f32,
-    /// number of samples (mono, 16 kHz)
+    /// number of samples (mono)
     pub sample_count: usize,
+    /// rate of the PCM: the source's target rate (16 kHz unless changed
+    /// with `set_source_target_rate`)
+    pub sample_rate: u32,
+    /// "i16" (16-bit integer PCM) or "f32" (32-bit float, unclamped)
+    pub sample_type: String,
+    /// "raw" (bare PCM) or "wav" (PCM behind a 44-byte WAV header, playable
+    /// as-is from a data: URL)
+    pub container: String,
+    pub bits_per_sample: u16,
+    /// monotonic nanoseconds since capture state creation, stamped when the
+    /// chunk is queued; orders chunks that land within the same millisecond
+    pub emit_nanos: u64,
+    /// session-wide emission order, assigned when the chunk is queued
+    pub seq: u64,
+    /// wall-clock ms since the Unix epoch at which the chunk's audio began,
+    /// estimated back from the flush; orders utterances that flush together
+    /// (see `flush_order`)
+    pub onset_ms: u64,
+    /// UUID of the capture session that produced the chunk, regenerated on
+    /// every start
+    pub session_id: String,
+    /// free-form downstream annotations (language, confidence, speaker...)
+    /// attached via `annotate_chunk`
+    pub meta: Option<serde_json::Value>,
+    /// how to interpret the PCM: "mono" (one source) or "mixed" (mic +
+    /// loopback summed into one channel)
+    pub layout: String,
+    /// name of the capture device the audio came from ("<mic> + <loopback>"
+    /// for mixed chunks)
+    pub device_name: String,
+    /// gap between the end of this source's previous chunk (or the stream
+    /// start) and the start of this one, for pacing metrics
+    pub preceding_silence_secs: f32,
+    /// RMS per 50ms window across the chunk, for re-endpointing without
+    /// decoding the PCM; empty unless enabled with `set_energy_trace`
+    pub energy_trace: Vec<f32>,
+    /// shared by every part of an utterance that was split for exceeding
+    /// `max_chunk_b64_bytes`; unique otherwise
+    pub utterance_id: String,
+    /// index of this part within the utterance, and the number of parts
+    /// (1 unless split)
+    pub part: u32,
+    pub parts: u32,
+    /// gain peak normalization applied to the utterance, in dB (0 when off)
+    pub normalize_gain_db: f32,
+    /// capture mode that produced the chunk: "per-source" or "mixed-mono".
+    /// A chunk flushed just after `set_capture_mode` keeps its old mode
+    pub mode: String,
+    /// false only for a `continuous_record` window the VAD heard no speech
+    /// in, and for markers
+    pub contains_speech: bool,
+    /// label from `set_source_speaker`, else the source name; `None` for
+    /// mixed chunks, which can't be attributed
+    pub speaker: Option<String>,
+    /// Set on zero-length marker chunks that carry no audio: "turn-end"
+    /// after an utterance (see `turn_end_markers`); `None` for audio
+    pub marker: Option<String>,
+}
+
+/// Runtime-tunable VAD and encoding parameters, shared by every accumulator
+/// and read on each frame. Serialized as part of a `SettingsProfile`; fields
+/// missing from an imported profile keep their defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    /// Derive the threshold from a running noise-floor estimate instead of
+    /// the fixed `VAD_ENERGY_THRESHOLD`
+    pub adaptive: bool,
+    /// Adaptive threshold = noise floor × margin
+    pub adaptive_margin: f32,
+    /// Bounds for the adaptive threshold so it can't collapse to zero in a
+    /// dead-quiet room or climb into speech levels
+    pub adaptive_min_threshold: f32,
+    pub adaptive_max_threshold: f32,
+    /// Loopback-only rejection of app audio (notification blips, background
+    /// music): a multiplier on the threshold and a separate minimum length
+    pub loopback_threshold_scale: f32,
+    pub loopback_min_speech_frames: usize,
+    /// Soft-knee saturation before i16 conversion instead of a hard clamp
+    pub limiter: bool,
+    /// Trailing silence that ends an utterance
+    pub endpoint_silence_ms: u32,
+    /// Shorter endpoint used when energy was rising into the pause (a
+    /// question-like contour, where the other side tends to answer quickly).
+    /// Equal to `endpoint_silence_ms` disables the distinction.
+    pub endpoint_short_silence_ms: u32,
+    /// Chunks shorter than this are discarded in `flush`, on top of the
+    /// speech-frame minimum (0 = no limit)
+    pub min_chunk_secs: f32,
+    /// Keep the trailing silence that ended the utterance in the chunk; off
+    /// cuts it at the last speech frame
+    pub include_tail_silence: bool,
+    /// Largest `audio_b64` a single chunk may carry; longer utterances are
+    /// split in `flush` so the webview bridge doesn't drop the message
+    pub max_chunk_b64_bytes: usize,
+    /// Audio after each start that only feeds the noise estimate and can
+    /// never open an utterance (device pops, driver garbage)
+    pub warmup_ms: u32,
+    /// Window after a flush in which a new utterance only opens on sustained
+    /// speech, so an echo or breath doesn't split a sentence boundary
+    pub cooldown_ms: u32,
+    /// Silence frames a speech frame takes back off the endpoint count, so
+    /// noise blips in a pause only delay the end instead of restarting it
+    /// (0 = any speech frame resets the count)
+    pub release_decrement_frames: usize,
+    /// Filter the copy of each frame the VAD decides on, so HVAC rumble and
+    /// fan hiss don't read as speech
+    pub analysis_filter: AnalysisFilter,
+    /// Also emit the filtered audio instead of the full-band signal
+    pub filter_output: bool,
+    /// Push-to-talk: the mic (or the mix, in mixed mode) ignores energy and
+    /// records exactly while `set_ptt` holds it, flushing on release.
+    /// Loopback keeps its VAD.
+    pub ptt_mode: bool,
+    /// Record VAD transitions for `get_vad_log` (debugging aid, off by
+    /// default)
+    pub vad_log: bool,
+    /// Cancel loopback echo from the mic before the VAD (see `EchoCanceller`)
+    pub echo_cancellation: bool,
+    /// Echo path length the canceller covers
+    pub echo_tail_ms: u32,
+    /// After this much continuous near-silence on a source, its callback
+    /// only measures RMS and skips resampling/VAD until energy returns
+    /// (0 = never suspend)
+    pub suspend_after_ms: u32,
+    /// Resample targets per source. Fixed for a session: changes apply at
+    /// the next start.
+    pub mic_target_rate: u32,
+    pub loopback_target_rate: u32,
+    /// Coarse echo suppression: while loopback is speaking, the mic
+    /// threshold is raised by `barge_in_raise_db` so the mic doesn't fire on
+    /// the speakers' own output
+    pub barge_in_suppression: bool,
+    pub barge_in_raise_db: f32,
+    /// Fixed device buffer size in frames for the next start; `None` lets
+    /// the driver choose
+    pub buffer_frames: Option<u32>,
+    /// Mic stream format to request on the next start instead of the
+    /// device default; `None` uses the default
+    pub preferred_input_config: Option<PreferredInputConfig>,
+    /// Raise each capture callback thread to maximum priority on its first
+    /// callback
+    pub realtime_priority: bool,
+    /// PCM encoding of emitted chunks
+    pub sample_type: SampleType,
+    /// Whether `audio_b64` is bare PCM or a playable WAV file
+    pub container: Container,
+    /// Brickwall ceiling: a chunk whose peak exceeds it is scaled down as a
+    /// whole so the peak lands on it (0 dBFS = no change)
+    pub output_ceiling_dbfs: f32,
+    /// Scale each utterance so its peak lands on `peak_target_dbfs`; only
+    /// ever down unless `peak_boost` is set (boost capped at
+    /// `MAX_PEAK_BOOST_DB`). Runs before the limiter and ceiling.
+    pub peak_normalize: bool,
+    pub peak_target_dbfs: f32,
+    pub peak_boost: bool,
+    /// TPDF dither before the i16 cast instead of plain truncation. Off by
+    /// default so output stays bit-exact for the same input.
+    pub dither: bool,
+    /// Switch a stream's downmix to its first channel when its first two
+    /// channels are strongly anti-correlated (averaging would cancel them)
+    pub phase_autocorrect: bool,
+    /// Boost the averaged downmix so a device with only one active channel
+    /// isn't ~6 dB (stereo) below that channel's level. Off by default:
+    /// correlated content (the usual case) comes out louder by the same
+    /// factor. `downmix_gain` of `None` uses sqrt(channels), +3 dB for stereo.
+    pub downmix_compensation: bool,
+    pub downmix_gain: Option<f32>,
+    /// Attach `AudioChunk.energy_trace` (opt-in, it grows every chunk)
+    pub energy_trace: bool,
+    /// Emit `"energy-frame"` events for UI meters (opt-in)
+    pub energy_events: bool,
+    pub energy_interval_ms: u32,
+    /// Meter levels below this are reported as this value, flagged as
+    /// silence, so idle meters sit still instead of jittering near -120
+    pub meter_floor_dbfs: f32,
+    /// How long `anyone_speaking` stays true after both sources go quiet
+    pub speaking_hold_ms: u32,
+    /// Whether each source's chunks add to the talk ratio and its history
+    /// (off for e.g. background music on loopback); chunks emit either way
+    pub mic_counts_in_ratio: bool,
+    pub loopback_counts_in_ratio: bool,
+    /// Emit `"wake"` when the mic speaks for `wake_speech_ms` after at least
+    /// `WAKE_MIN_SILENCE_MS` of silence
+    pub wake_trigger: bool,
+    pub wake_speech_ms: u32,
+    /// Pause capture while the overlay window is hidden or minimized
+    pub capture_when_visible_only: bool,
+    /// Stop capture after this many minutes without an emitted chunk; 0
+    /// (the default) never stops
+    pub idle_stop_mins: u32,
+    /// After an idle stop, reopen the devices this many minutes later; 0
+    /// leaves capture stopped
+    pub idle_rearm_mins: u32,
+    /// Follow each utterance's last chunk with a zero-length "turn-end"
+    /// marker chunk, for backends that finalize a segment on an explicit
+    /// signal. Off by default so the stream stays pure audio
+    pub turn_end_markers: bool,
+    /// Emission order for utterances that end in the same flush (a stop,
+    /// a mode change), and so which is dropped first from a full queue
+    pub flush_order: FlushOrder,
+    /// Emit every `continuous_window_secs` of audio, speech or not, with
+    /// the VAD only marking `contains_speech`, instead of speech-only
+    /// utterances (for recordings that must be complete)
+    pub continuous_record: bool,
+    pub continuous_window_secs: u32,
+    /// When no loopback device is pinned, try each loopback strategy in
+    /// turn with a `loopback_probe_ms` probe and keep the first that
+    /// delivers signal, instead of taking the platform default blindly
+    pub loopback_fallback: bool,
+    pub loopback_probe_ms: u32,
+    /// Hard cap on audio an accumulator buffers: past it the buffer is
+    /// flushed whatever the VAD, push-to-talk or continuous window say, so
+    /// a stuck-open VAD can't grow it without bound
+    pub max_utterance_secs: u32,
+}
+
+impl AudioSettings {
+    fn target_rate(&self, source: &str) -> u32 {
+        match source {
+            "mic" => self.mic_target_rate,
+            "loopback" => self.loopback_target_rate,
+            _ => TARGET_SAMPLE_RATE,
+        }
+    }
+
+    /// Factor applied after averaging `channels` channels to mono.
+    fn downmix_gain(&self, channels: u16) -> f32 {
+        if !self.downmix_compensation || channels < 2 {
+            return 1.0;
+        }
+        self.downmix_gain.unwrap_or((channels as f32).sqrt())
+    }
+
+    fn counts_in_ratio(&self, source: &str) -> bool {
+        match source {
+            "mic" => self.mic_counts_in_ratio,
+            "loopback" => self.loopback_counts_in_ratio,
+            _ => false,
+        }
+    }
+
+    /// The range checks the individual setters apply, for settings that
+    /// arrive as a whole (`import_settings`).
+    fn validate(&self) -> Result<(), String> {
+        if !self.adaptive_margin.is_finite() || self.adaptive_margin < 1.0 {
+            return Err("Adaptive VAD margin must be at least 1.0".into());
+        }
+        if !(self.adaptive_min_threshold >= 0.0 && self.adaptive_min_threshold <= self.adaptive_max_threshold) {
+            return Err("Adaptive threshold bounds must satisfy 0 <= min <= max".into());
+        }
+        if !self.loopback_threshold_scale.is_finite() || self.loopback_threshold_scale < 1.0 {
+            return Err("Loopback threshold scale must be at least 1.0".into());
+        }
+        if self.endpoint_silence_ms == 0 || self.endpoint_short_silence_ms == 0 {
+            return Err("Endpoint silence must be greater than 0 ms".into());
+        }
+        if self.endpoint_short_silence_ms > self.endpoint_silence_ms {
+            return Err("Short endpoint silence can't exceed the regular endpoint silence".into());
+        }
+        if !(0.0..=30.0).contains(&self.min_chunk_secs) {
+            return Err("Minimum chunk length must be between 0 and 30 seconds".into());
+        }
+        if !(MIN_CHUNK_B64_BYTES..=MAX_CHUNK_B64_BYTES).contains(&self.max_chunk_b64_bytes) {
+            return Err(format!(
+                "Chunk size limit must be between {} and {} bytes",
+                MIN_CHUNK_B64_BYTES, MAX_CHUNK_B64_BYTES
+            ));
+        }
+        if self.release_decrement_frames > MAX_RELEASE_DECREMENT_FRAMES {
+            return Err(format!(
+                "Release decrement must be at most {} frames",
+                MAX_RELEASE_DECREMENT_FRAMES
+            ));
+        }
+        if self.echo_tail_ms == 0 || self.echo_tail_ms > AEC_MAX_TAIL_MS {
+            return Err(format!("Echo tail must be between 1 and {} ms", AEC_MAX_TAIL_MS));
+        }
+        for hz in [self.mic_target_rate, self.loopback_target_rate] {
+            if !(MIN_TARGET_RATE..=MAX_TARGET_RATE).contains(&hz) {
+                return Err(format!(
+                    "Target rate must be between {} and {} Hz",
+                    MIN_TARGET_RATE, MAX_TARGET_RATE
+                ));
+            }
+        }
+        if self.echo_cancellation && self.mic_target_rate != self.loopback_target_rate {
+            return Err("Echo cancellation needs mic and loopback at the same target rate".into());
+        }
+        if !(0.0..=40.0).contains(&self.barge_in_raise_db) {
+            return Err("Barge-in raise must be between 0 and 40 dB".into());
+        }
+        if self.buffer_frames.is_some_and(|frames| frames == 0 || frames > MAX_BUFFER_FRAMES) {
+            return Err(format!("Buffer size must be between 1 and {} frames", MAX_BUFFER_FRAMES));
+        }
+        if !(-60.0..=0.0).contains(&self.output_ceiling_dbfs) {
+            return Err("Output ceiling must be between -60 and 0 dBFS".into());
+        }
+        if !(-60.0..=0.0).contains(&self.peak_target_dbfs) {
+            return Err("Peak target must be between -60 and 0 dBFS".into());
+        }
+        if !WAKE_SPEECH_MS_RANGE.contains(&self.wake_speech_ms) {
+            return Err(format!(
+                "Wake speech duration must be between {} and {} ms",
+                WAKE_SPEECH_MS_RANGE.start(),
+                WAKE_SPEECH_MS_RANGE.end()
+            ));
+        }
+        if self.downmix_gain.is_some_and(|g| !(MIN_DOWNMIX_GAIN..=MAX_DOWNMIX_GAIN).contains(&g)) {
+            return Err(format!(
+                "Downmix gain must be between {} and {}",
+                MIN_DOWNMIX_GAIN, MAX_DOWNMIX_GAIN
+            ));
+        }
+        if self.idle_stop_mins > MAX_IDLE_MINS || self.idle_rearm_mins > MAX_IDLE_MINS {
+            return Err(format!("Idle stop and re-arm must be at most {} minutes", MAX_IDLE_MINS));
+        }
+        if !(DBFS_FLOOR..=0.0).contains(&self.meter_floor_dbfs) {
+            return Err(format!("Meter floor must be between {} and 0 dBFS", DBFS_FLOOR));
+        }
+        if self.energy_interval_ms == 0 {
+            return Err("Energy frame interval must be greater than 0 ms".into());
+        }
+        if !(1..=MAX_CONTINUOUS_WINDOW_SECS).contains(&self.continuous_window_secs) {
+            return Err(format!(
+                "Continuous window must be between 1 and {} seconds",
+                MAX_CONTINUOUS_WINDOW_SECS
+            ));
+        }
+        if !(1..=MAX_LOOPBACK_PROBE_MS).contains(&self.loopback_probe_ms) {
+            return Err(format!("Loopback probe must be between 1 and {} ms", MAX_LOOPBACK_PROBE_MS));
+        }
+        if !(1..=MAX_UTTERANCE_SECS).contains(&self.max_utterance_secs) {
+            return Err(format!("Utterance cap must be between 1 and {} seconds", MAX_UTTERANCE_SECS));
+        }
+        Ok(())
+    }
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            adaptive: false,
+            adaptive_margin: 3.0,
+            adaptive_min_threshold: 0.002,
+            adaptive_max_threshold: 0.05,
+            loopback_threshold_scale: 1.0,
+            loopback_min_speech_frames: VAD_MIN_SPEECH_FRAMES,
+            limiter: false,
+            endpoint_silence_ms: VAD_SILENCE_MS,
+            endpoint_short_silence_ms: VAD_SILENCE_MS,
+            min_chunk_secs: 0.0,
+            include_tail_silence: true,
+            max_chunk_b64_bytes: DEFAULT_MAX_CHUNK_B64_BYTES,
+            warmup_ms: VAD_WARMUP_MS,
+            cooldown_ms: VAD_COOLDOWN_MS,
+            release_decrement_frames: 0,
+            analysis_filter: AnalysisFilter::None,
+            vad_log: false,
+            ptt_mode: false,
+            filter_output: false,
+            echo_cancellation: false,
+            echo_tail_ms: AEC_DEFAULT_TAIL_MS,
+            suspend_after_ms: 0,
+            dither: false,
+            barge_in_suppression: false,
+            barge_in_raise_db: 12.0,
+            buffer_frames: None,
+            preferred_input_config: None,
+            realtime_priority: false,
+            sample_type: SampleType::I16,
+            output_ceiling_dbfs: 0.0,
+            peak_normalize: false,
+            peak_target_dbfs: DEFAULT_PEAK_TARGET_DBFS,
+            peak_boost: false,
+            mic_target_rate: TARGET_SAMPLE_RATE,
+            loopback_target_rate: TARGET_SAMPLE_RATE,
+            phase_autocorrect: true,
+            downmix_compensation: false,
+            downmix_gain: None,
+            energy_trace: false,
+            energy_events: false,
+            energy_interval_ms: ENERGY_FRAME_INTERVAL_MS,
+            meter_floor_dbfs: DEFAULT_METER_FLOOR_DBFS,
+            speaking_hold_ms: DEFAULT_SPEAKING_HOLD_MS,
+            container: Container::Raw,
+            mic_counts_in_ratio: true,
+            loopback_counts_in_ratio: true,
+            wake_trigger: false,
+            wake_speech_ms: DEFAULT_WAKE_SPEECH_MS,
+            capture_when_visible_only: false,
+            idle_stop_mins: 0,
+            idle_rearm_mins: 0,
+            turn_end_markers: false,
+            flush_order: FlushOrder::Onset,
+            continuous_record: false,
+            continuous_window_secs: DEFAULT_CONTINUOUS_WINDOW_SECS,
+            loopback_fallback: false,
+            loopback_probe_ms: DEFAULT_LOOPBACK_PROBE_MS,
+            max_utterance_secs: DEFAULT_MAX_UTTERANCE_SECS,
+        }
+    }
 }
 
 /// Holds the running state for one capture stream (mic or loopback).
@@ -31,419 +552,6495 @@ pub struct AudioChunk {
 /// the entire utterance as a single chunk when silence is detected.
 struct VadAccumulator {
     source_label: String,
-    buffer: Vec<f32>,          // accumulated f32 samples (mono 16 kHz)
+    device_name: String,       // AudioChunk.device_name, set when the stream opens
+    sample_rate: u32,          // rate of the frames fed in
+    buffer: Vec<f32>,          // accumulated f32 samples (mono, at sample_rate)
     silence_counter: usize,    // consecutive silent frames
     speech_counter: usize,     // consecutive speech frames in current utterance
     is_speaking: bool,
+    noise_floor: Option<f32>,  // running RMS estimate over non-speech frames
+    speech_rms_avg: f32,       // running RMS over the current utterance's speech frames
+    rising_tail: bool,         // last speech frame was louder than the utterance average
+    layout: &'static str,      // AudioChunk.layout for chunks from this accumulator
+    samples_seen: usize,       // total samples fed since creation, for warm-up
+    last_energy_emit: Option<Instant>, // throttles "energy-frame" events
+    cooldown_until: usize,     // samples_seen mark where the post-flush cooldown ends
+    onset_frames: usize,       // speech frames held back during the cooldown
+    filter: VadFilter,         // analysis filter state, carried across frames
+    band_scratch: Vec<f32>,    // band-passed copy of the current frame
+    last_end_sample: usize,    // samples_seen when the previous chunk ended
+    barge_in_active: bool,     // mic only: loopback is speaking, raise the threshold
+    speech_end: usize,         // buffer length after the latest speech frame
+    ptt: Option<bool>,         // Some(held) while push-to-talk governs this accumulator
+    last_rms: f32,             // RMS of the latest frame, for the VAD log
+    log: VecDeque<VadEvent>,   // recent transitions, when `vad_log` is on
+    window_speech_frames: usize, // speech frames in the current continuous window
+    last_encode_ms: Option<f32>, // wall time the latest chunk took to encode
 }
 
 impl VadAccumulator {
     fn new(source_label: &str) -> Self {
         Self {
             source_label: source_label.to_string(),
+            device_name: String::new(),
+            sample_rate: TARGET_SAMPLE_RATE,
             buffer: Vec::with_capacity(TARGET_SAMPLE_RATE as usize * 10), // pre-alloc ~10s
             silence_counter: 0,
             speech_counter: 0,
             is_speaking: false,
+            noise_floor: None,
+            speech_rms_avg: 0.0,
+            rising_tail: false,
+            layout: "mono",
+            samples_seen: 0,
+            last_energy_emit: None,
+            cooldown_until: 0,
+            onset_frames: 0,
+            filter: VadFilter::new(AnalysisFilter::None, TARGET_SAMPLE_RATE),
+            band_scratch: Vec::new(),
+            last_end_sample: 0,
+            barge_in_active: false,
+            speech_end: 0,
+            ptt: None,
+            last_rms: 0.0,
+            log: VecDeque::new(),
+            window_speech_frames: 0,
+            last_encode_ms: None,
+        }
+    }
+
+    fn with_layout(mut self, layout: &'static str) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    fn with_device(mut self, device_name: &str) -> Self {
+        self.device_name = device_name.to_string();
+        self
+    }
+
+    fn with_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = sample_rate;
+        self.filter = VadFilter::new(self.filter.kind, sample_rate);
+        self
+    }
+
+    /// Back to a fresh accumulator (warm-up included) on the same source,
+    /// device and rate.
+    fn reset(&mut self) {
+        let log = std::mem::take(&mut self.log);
+        *self = VadAccumulator::new(&self.source_label)
+            .with_layout(self.layout)
+            .with_device(&self.device_name)
+            .with_rate(self.sample_rate);
+        self.log = log;
+    }
+
+    /// Appends a transition to the VAD log if it's enabled.
+    fn log_event(&mut self, kind: &'static str, settings: &AudioSettings) {
+        if !settings.vad_log {
+            return;
+        }
+        if self.log.len() >= MAX_VAD_LOG {
+            self.log.pop_front();
+        }
+        let event = VadEvent {
+            kind,
+            ts_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            audio_ms: (self.samples_seen as u64 * 1000) / self.sample_rate.max(1) as u64,
+            rms: self.last_rms,
+            threshold: self.threshold(settings),
+            speech_frames: self.speech_counter,
+            buffered_secs: self.buffer.len() as f32 / self.sample_rate as f32,
+        };
+        self.log.push_back(event);
+    }
+
+    fn ms_to_samples(&self, ms: u32) -> usize {
+        ms as usize * self.sample_rate as usize / 1000
+    }
+
+    /// Silence frames (of `frame_len` samples) that end the utterance, using
+    /// the short endpoint when the speech tail was rising.
+    fn endpoint_frames(&self, settings: &AudioSettings, frame_len: usize) -> usize {
+        let ms = if self.rising_tail {
+            settings.endpoint_short_silence_ms
+        } else {
+            settings.endpoint_silence_ms
+        };
+        let samples = self.ms_to_samples(ms);
+        samples.div_ceil(frame_len.max(1)).max(1)
+    }
+
+    /// Energy threshold for the next frame: fixed, or tracking the noise
+    /// floor when adaptive mode is on.
+    fn threshold(&self, settings: &AudioSettings) -> f32 {
+        let base = match (settings.adaptive, self.noise_floor) {
+            (true, Some(floor)) => (floor * settings.adaptive_margin)
+                .clamp(settings.adaptive_min_threshold, settings.adaptive_max_threshold),
+            _ => VAD_ENERGY_THRESHOLD,
+        };
+        if self.source_label == "loopback" {
+            base * settings.loopback_threshold_scale
+        } else if self.barge_in_active {
+            base * 10f32.powf(settings.barge_in_raise_db / 20.0)
+        } else {
+            base
+        }
+    }
+
+    fn min_speech_frames(&self, settings: &AudioSettings) -> usize {
+        if self.source_label == "loopback" {
+            settings.loopback_min_speech_frames
+        } else {
+            VAD_MIN_SPEECH_FRAMES
+        }
+    }
+
+    fn update_noise_floor(&mut self, rms: f32) {
+        self.noise_floor = Some(match self.noise_floor {
+            Some(floor) => floor + (rms - floor) * VAD_NOISE_FLOOR_ALPHA,
+            None => rms,
+        });
+    }
+
+    /// Feed a frame of mono f32 samples at `sample_rate`. Returns the utterance's
+    /// chunk(s) when the speaker stops (silence detected after speech) or
+    /// the buffer reaches `max_utterance_secs`; more than one only when it
+    /// exceeds `max_chunk_b64_bytes`.
+    fn feed(&mut self, mono_16k: &[f32], settings: &AudioSettings) -> Vec<AudioChunk> {
+        let mut chunks = if settings.analysis_filter == AnalysisFilter::None {
+            self.feed_frame(mono_16k, mono_16k, settings)
+        } else {
+            if self.filter.kind != settings.analysis_filter {
+                self.filter = VadFilter::new(settings.analysis_filter, self.sample_rate);
+            }
+            let mut band = std::mem::take(&mut self.band_scratch);
+            self.filter.process_into(mono_16k, &mut band);
+            let keep = if settings.filter_output { &band[..] } else { mono_16k };
+            let chunks = self.feed_frame(&band, keep, settings);
+            self.band_scratch = band;
+            chunks
+        };
+        if self.buffer.len() >= self.ms_to_samples(settings.max_utterance_secs * 1000) {
+            chunks.extend(self.flush_at_cap(settings));
         }
+        chunks
     }
 
-    /// Feed a frame of mono 16 kHz f32 samples. Returns Some(AudioChunk) when
-    /// the speaker stops (silence detected after speech).
-    fn feed(&mut self, mono_16k: &[f32]) -> Option<AudioChunk> {
-        let rms = (mono_16k.iter().map(|s| s * s).sum::<f32>() / mono_16k.len() as f32).sqrt();
-        let is_speech = rms > VAD_ENERGY_THRESHOLD;
+    /// Safety net for `max_utterance_secs`: ends the buffered audio as if
+    /// it had been endpointed. Speech that carries on starts a new
+    /// utterance on the next frame, without a cooldown.
+    fn flush_at_cap(&mut self, settings: &AudioSettings) -> Vec<AudioChunk> {
+        log::warn!(
+            "{} buffer hit the {}s cap; flushing (stuck-open VAD or constant noise?)",
+            self.source_label,
+            settings.max_utterance_secs
+        );
+        if settings.continuous_record {
+            let start_sample = self.samples_seen.saturating_sub(self.buffer.len());
+            return self.flush_window(start_sample, settings);
+        }
+        self.is_speaking = false;
+        self.onset_frames = 0;
+        let chunks = self.flush(settings);
+        self.silence_counter = 0;
+        self.speech_counter = 0;
+        self.speech_rms_avg = 0.0;
+        self.rising_tail = false;
+        chunks
+    }
+
+    /// Runs the VAD on `detect` and buffers `keep`, the audio that ends up in
+    /// the chunk. The two differ only when the speech band-pass is on.
+    fn feed_frame(&mut self, detect: &[f32], keep: &[f32], settings: &AudioSettings) -> Vec<AudioChunk> {
+        let rms = frame_rms(detect);
+        self.last_rms = rms;
+        if settings.continuous_record {
+            return self.feed_continuous(rms, keep, settings);
+        }
+        if let Some(held) = self.ptt {
+            self.samples_seen = self.samples_seen.saturating_add(keep.len());
+            return self.feed_ptt(keep, held, settings);
+        }
+
+        let warmup_samples = self.ms_to_samples(settings.warmup_ms);
+        let warming_up = self.samples_seen < warmup_samples;
+        self.samples_seen = self.samples_seen.saturating_add(keep.len());
+        if warming_up {
+            self.update_noise_floor(rms);
+            return Vec::new();
+        }
+
+        let is_speech = rms > self.threshold(settings);
+
+        // Only non-speech frames move the floor, so it can't drift up into speech
+        if !is_speech {
+            self.update_noise_floor(rms);
+        }
+
+        if !self.is_speaking && self.samples_seen <= self.cooldown_until {
+            if !is_speech {
+                // onset didn't hold up -- drop the held frames
+                self.onset_frames = 0;
+                self.buffer.clear();
+                self.silence_counter += 1;
+                return Vec::new();
+            }
+            // hold speech back until it's sustained
+            self.onset_frames += 1;
+            self.speech_rms_avg += (rms - self.speech_rms_avg) / self.onset_frames as f32;
+            self.buffer.extend_from_slice(keep);
+            self.speech_end = self.buffer.len();
+            if self.onset_frames >= VAD_COOLDOWN_ONSET_FRAMES {
+                self.speech_counter = self.onset_frames;
+                self.onset_frames = 0;
+                self.silence_counter = 0;
+                self.is_speaking = true;
+                self.log_event("speech_start", settings);
+            }
+            return Vec::new();
+        }
 
         if is_speech {
-            self.silence_counter = 0;
+            if !self.is_speaking {
+                self.log_event("speech_start", settings);
+            }
+            self.rising_tail = self.speech_counter > 0 && rms > self.speech_rms_avg;
+            self.speech_rms_avg += (rms - self.speech_rms_avg) / (self.speech_counter + 1) as f32;
+            self.silence_counter = match settings.release_decrement_frames {
+                0 => 0,
+                n => self.silence_counter.saturating_sub(n),
+            };
             self.speech_counter += 1;
             self.is_speaking = true;
-            self.buffer.extend_from_slice(mono_16k);
-            None
+            self.buffer.extend_from_slice(keep);
+            self.speech_end = self.buffer.len();
+            Vec::new()
         } else if self.is_speaking {
             // still accumulate a little silence so we don't clip the tail
-            self.buffer.extend_from_slice(mono_16k);
+            self.buffer.extend_from_slice(keep);
             self.silence_counter += 1;
 
-            if self.silence_counter >= VAD_SILENCE_FRAMES {
+            if self.silence_counter >= self.endpoint_frames(settings, keep.len()) {
                 // end of utterance -- flush
                 self.is_speaking = false;
-                let chunk = self.flush();
+                let chunk = self.flush(settings);
                 self.silence_counter = 0;
                 self.speech_counter = 0;
+                self.speech_rms_avg = 0.0;
+                self.rising_tail = false;
+                let cooldown = self.ms_to_samples(settings.cooldown_ms);
+                self.cooldown_until = self.samples_seen + cooldown;
                 chunk
             } else {
-                None
+                Vec::new()
             }
         } else {
             // pure silence, not speaking -- discard
             self.silence_counter += 1;
-            None
+            Vec::new()
         }
     }
 
-    /// Convert accumulated f32 buffer to a base64-encoded 16-bit PCM chunk.
-    fn flush(&mut self) -> Option<AudioChunk> {
-        if self.speech_counter < VAD_MIN_SPEECH_FRAMES {
-            self.buffer.clear();
-            return None;
+    /// Push-to-talk in place of the VAD: every frame while held is speech,
+    /// and the release ends the utterance. No warm-up or cooldown applies.
+    fn feed_ptt(&mut self, keep: &[f32], held: bool, settings: &AudioSettings) -> Vec<AudioChunk> {
+        if held {
+            if !self.is_speaking {
+                self.is_speaking = true;
+                self.log_event("speech_start", settings);
+            }
+            self.speech_counter += 1;
+            self.buffer.extend_from_slice(keep);
+            self.speech_end = self.buffer.len();
+            Vec::new()
+        } else {
+            self.force_flush(settings)
         }
+    }
 
-        let sample_count = self.buffer.len();
-        let duration_secs = sample_count as f32 / TARGET_SAMPLE_RATE as f32;
+    /// Continuous recording in place of utterances: every frame is kept and
+    /// emitted in fixed `continuous_window_secs` windows; the VAD only
+    /// decides each window's `contains_speech`. Warm-up audio is kept too,
+    /// as silence.
+    fn feed_continuous(&mut self, rms: f32, keep: &[f32], settings: &AudioSettings) -> Vec<AudioChunk> {
+        let warming_up = self.samples_seen < self.ms_to_samples(settings.warmup_ms);
+        self.samples_seen = self.samples_seen.saturating_add(keep.len());
+        let is_speech = !warming_up && rms > self.threshold(settings);
+        if is_speech {
+            self.window_speech_frames += 1;
+        } else {
+            self.update_noise_floor(rms);
+        }
+        self.is_speaking = is_speech;
+        self.buffer.extend_from_slice(keep);
 
-        // Convert f32 [-1.0, 1.0] to i16 PCM bytes (little-endian)
-        let mut pcm_bytes: Vec<u8> = Vec::with_capacity(sample_count * 2);
-        for &sample in &self.buffer {
-            let clamped = sample.clamp(-1.0, 1.0);
-            let as_i16 = (clamped * i16::MAX as f32) as i16;
-            pcm_bytes.extend_from_slice(&as_i16.to_le_bytes());
+        let window = self.ms_to_samples(settings.continuous_window_secs * 1000).max(1);
+        if self.buffer.len() < window {
+            return Vec::new();
         }
+        // Cut at the exact window length; the overshoot starts the next one
+        let rest = self.buffer.split_off(window);
+        let start_sample = self.samples_seen.saturating_sub(window + rest.len());
+        let chunks = self.flush_window(start_sample, settings);
+        self.buffer = rest;
+        chunks
+    }
+
+    /// Emits the buffered window, starting at `start_sample`, whatever it
+    /// holds.
+    fn flush_window(&mut self, start_sample: usize, settings: &AudioSettings) -> Vec<AudioChunk> {
+        if self.buffer.is_empty() {
+            return Vec::new();
+        }
+        let contains_speech = self.window_speech_frames >= self.min_speech_frames(settings);
+        self.window_speech_frames = 0;
+        let mut chunks = self.encode_buffer(start_sample, 0.0, settings);
+        for chunk in &mut chunks {
+            chunk.contains_speech = contains_speech;
+        }
+        self.buffer.clear();
+        chunks
+    }
 
-        let audio_b64 = base64::engine::general_purpose::STANDARD.encode(&pcm_bytes);
+    /// Ends the current utterance immediately (e.g. on shutdown), emitting it
+    /// if it is long enough. In continuous recording, emits the partial
+    /// window instead.
+    fn force_flush(&mut self, settings: &AudioSettings) -> Vec<AudioChunk> {
+        if settings.continuous_record {
+            self.is_speaking = false;
+            let start_sample = self.samples_seen.saturating_sub(self.buffer.len());
+            return self.flush_window(start_sample, settings);
+        }
+        if !self.is_speaking {
+            self.buffer.clear();
+            return Vec::new();
+        }
+        self.is_speaking = false;
+        let chunk = self.flush(settings);
+        self.silence_counter = 0;
+        self.speech_counter = 0;
+        self.speech_rms_avg = 0.0;
+        self.rising_tail = false;
+        chunk
+    }
 
+    /// Ends the utterance in the buffer: discarded if too short, otherwise
+    /// normalized and encoded (see `encode_buffer`).
+    fn flush(&mut self, settings: &AudioSettings) -> Vec<AudioChunk> {
+        if self.speech_counter < self.min_speech_frames(settings) {
+            self.log_event("discard_short", settings);
+            self.buffer.clear();
+            return Vec::new();
+        }
+        // The buffer runs from the utterance onset to now; blips too short
+        // to emit count toward the gap
+        let start_sample = self.samples_seen.saturating_sub(self.buffer.len());
+        if !settings.include_tail_silence {
+            self.buffer.truncate(self.speech_end);
+        }
+        let duration_secs = self.buffer.len() as f32 / self.sample_rate as f32;
+        if duration_secs < settings.min_chunk_secs {
+            self.log_event("discard_short", settings);
+            self.buffer.clear();
+            return Vec::new();
+        }
+        self.log_event("flush", settings);
+        let normalize_gain_db = if settings.peak_normalize {
+            peak_normalize(&mut self.buffer, settings)
+        } else {
+            0.0
+        };
+        let chunks = self.encode_buffer(start_sample, normalize_gain_db, settings);
         self.buffer.clear();
+        chunks
+    }
 
-        Some(AudioChunk {
-            audio_b64,
-            source: self.source_label.clone(),
-            duration_secs,
-            sample_count,
-        })
+    /// Encodes the whole buffer, which began at `start_sample`, as one
+    /// utterance: a single chunk unless its base64 would exceed
+    /// `max_chunk_b64_bytes`, in which case the PCM is split into parts
+    /// sharing an `utterance_id`. Leaves the buffer in place.
+    fn encode_buffer(&mut self, start_sample: usize, normalize_gain_db: f32, settings: &AudioSettings) -> Vec<AudioChunk> {
+        let started = Instant::now();
+        let duration_secs = self.buffer.len() as f32 / self.sample_rate as f32;
+        // 4 base64 bytes per 3 PCM bytes, less the container header
+        let max_bytes = (settings.max_chunk_b64_bytes / 4 * 3).saturating_sub(settings.container.header_len());
+        let max_samples = (max_bytes / settings.sample_type.bytes()).max(1);
+        let parts = self.buffer.len().div_ceil(max_samples);
+        if parts > 1 {
+            self.log_event("size_split", settings);
+            log::info!(
+                "{} utterance of {:.1}s exceeds the chunk size limit, splitting into {} parts",
+                self.source_label,
+                duration_secs,
+                parts
+            );
+        }
+        let utterance_id = uuid::Uuid::new_v4().to_string();
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        // Audio between a part's first sample and the newest one fed in
+        let age_ms = |part: usize| {
+            let behind = self.samples_seen.saturating_sub(start_sample + part * max_samples);
+            (behind as u64 * 1000) / self.sample_rate.max(1) as u64
+        };
+        let preceding_silence_secs =
+            start_sample.saturating_sub(self.last_end_sample) as f32 / self.sample_rate as f32;
+        self.last_end_sample = start_sample + self.buffer.len();
+
+        let chunks = self
+            .buffer
+            .chunks(max_samples)
+            .enumerate()
+            .map(|(part, samples)| {
+                let mut chunk = encode_chunk(
+                    samples,
+                    self.sample_rate,
+                    &self.source_label,
+                    &self.device_name,
+                    self.layout,
+                    settings,
+                );
+                chunk.utterance_id = utterance_id.clone();
+                chunk.part = part as u32;
+                chunk.parts = parts as u32;
+                chunk.normalize_gain_db = normalize_gain_db;
+                chunk.onset_ms = now_ms.saturating_sub(age_ms(part));
+                if part == 0 {
+                    chunk.preceding_silence_secs = preceding_silence_secs;
+                }
+                if settings.energy_trace {
+                    let window = self.ms_to_samples(ENERGY_TRACE_MS).max(1);
+                    chunk.energy_trace = samples.chunks(window).map(frame_rms).collect();
+                }
+                chunk
+            })
+            .collect();
+        self.last_encode_ms = Some(started.elapsed().as_secs_f32() * 1000.0);
+        chunks
     }
 }
 
-/// Converts interleaved multi-channel audio at an arbitrary sample rate
-/// to mono at TARGET_SAMPLE_RATE using simple linear interpolation.
-fn to_mono_16k(input: &[f32], channels: u16, input_rate: u32) -> Vec<f32> {
-    // Step 1: downmix to mono by averaging channels
-    let mono: Vec<f32> = input
-        .chunks_exact(channels as usize)
-        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
-        .collect();
+/// Scales `samples` so the peak lands on `peak_target_dbfs` and returns the
+/// gain applied, in dB. Digital silence is left alone.
+fn peak_normalize(samples: &mut [f32], settings: &AudioSettings) -> f32 {
+    let peak = samples.iter().fold(0.0f32, |peak, &s| peak.max(s.abs()));
+    if peak <= 0.0 {
+        return 0.0;
+    }
+    let mut gain_db = settings.peak_target_dbfs - 20.0 * peak.log10();
+    gain_db = if settings.peak_boost {
+        gain_db.min(MAX_PEAK_BOOST_DB)
+    } else {
+        gain_db.min(0.0)
+    };
+    let gain = 10f32.powf(gain_db / 20.0);
+    samples.iter_mut().for_each(|s| *s *= gain);
+    gain_db
+}
 
-    // Step 2: resample if needed
-    if input_rate == TARGET_SAMPLE_RATE {
-        return mono;
+/// Encodes mono samples at `sample_rate` as a base64 PCM `AudioChunk`. The
+/// emit stamp and seq are filled in when the chunk is queued.
+///
+/// Runs on the flushing stream's own callback thread, so mic and loopback
+/// chunks already encode in parallel, each in order. There is no separate
+/// encoding stage to pool until a codec heavier than PCM is added.
+fn encode_chunk(
+    samples: &[f32],
+    sample_rate: u32,
+    source: &str,
+    device_name: &str,
+    layout: &str,
+    settings: &AudioSettings,
+) -> AudioChunk {
+    let sample_count = samples.len();
+    let duration_secs = sample_count as f32 / sample_rate as f32;
+
+    let data_len = sample_count * settings.sample_type.bytes();
+    let mut pcm_bytes: Vec<u8> = Vec::with_capacity(WAV_HEADER_LEN + data_len);
+    if settings.container == Container::Wav {
+        pcm_bytes.extend_from_slice(&wav_header(sample_rate, settings.sample_type, data_len));
+    }
+    let limit = |sample: f32| if settings.limiter { soft_limit(sample) } else { sample };
+    // Per-chunk static gain that puts the peak at the ceiling
+    let ceiling = 10f32.powf(settings.output_ceiling_dbfs / 20.0);
+    let peak = samples.iter().fold(0.0f32, |peak, &s| peak.max(limit(s).abs()));
+    let gain = if peak > ceiling { ceiling / peak } else { 1.0 };
+    let limited = samples.iter().map(|&sample| limit(sample) * gain);
+    match settings.sample_type {
+        // Raw little-endian f32 keeps the full range, unclamped
+        SampleType::F32 => {
+            for sample in limited {
+                pcm_bytes.extend_from_slice(&sample.to_le_bytes());
+            }
+        }
+        // Convert f32 [-1.0, 1.0] to i16 PCM bytes (little-endian)
+        SampleType::I16 => {
+            let mut dither = settings.dither.then(TpdfDither::new);
+            for sample in limited {
+                let clamped = sample.clamp(-1.0, 1.0);
+                let as_i16 = match dither.as_mut() {
+                    Some(d) => (clamped * i16::MAX as f32 + d.next_lsb())
+                        .round()
+                        .clamp(i16::MIN as f32, i16::MAX as f32) as i16,
+                    None => (clamped * i16::MAX as f32) as i16,
+                };
+                pcm_bytes.extend_from_slice(&as_i16.to_le_bytes());
+            }
+        }
     }
 
-    let ratio = TARGET_SAMPLE_RATE as f64 / input_rate as f64;
-    let output_len = (mono.len() as f64 * ratio) as usize;
-    let mut resampled = Vec::with_capacity(output_len);
+    let audio_b64 = base64::engine::general_purpose::STANDARD.encode(&pcm_bytes);
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
 
-    for i in 0..output_len {
-        let src_idx = i as f64 / ratio;
-        let idx0 = src_idx.floor() as usize;
-        let idx1 = (idx0 + 1).min(mono.len().saturating_sub(1));
-        let frac = (src_idx - idx0 as f64) as f32;
-        resampled.push(mono[idx0] * (1.0 - frac) + mono[idx1] * frac);
+    AudioChunk {
+        audio_b64,
+        source: source.to_string(),
+        duration_secs,
+        sample_count,
+        sample_rate,
+        sample_type: settings.sample_type.as_str().to_string(),
+        container: settings.container.as_str().to_string(),
+        bits_per_sample: settings.sample_type.bytes() as u16 * 8,
+        emit_nanos: 0,
+        seq: 0,
+        // Assumes the samples just arrived; `encode_buffer` knows better
+        onset_ms: now_ms.saturating_sub((duration_secs * 1000.0) as u64),
+        session_id: String::new(),
+        meta: None,
+        layout: layout.to_string(),
+        device_name: device_name.to_string(),
+        preceding_silence_secs: 0.0,
+        energy_trace: Vec::new(),
+        utterance_id: uuid::Uuid::new_v4().to_string(),
+        part: 0,
+        parts: 1,
+        normalize_gain_db: 0.0,
+        mode: CaptureMode::of_layout(layout).as_str().to_string(),
+        contains_speech: true,
+        speaker: None,
+        marker: None,
     }
+}
 
-    resampled
+/// Canonical 44-byte header for a mono WAV holding `data_len` bytes of
+/// `sample_type` PCM (format 1, or 3 for IEEE float).
+fn wav_header(sample_rate: u32, sample_type: SampleType, data_len: usize) -> [u8; WAV_HEADER_LEN] {
+    let bytes = sample_type.bytes() as u32;
+    let format: u16 = match sample_type {
+        SampleType::I16 => 1,
+        SampleType::F32 => 3,
+    };
+    let mut header = [0u8; WAV_HEADER_LEN];
+    header[0..4].copy_from_slice(b"RIFF");
+    header[4..8].copy_from_slice(&(36 + data_len as u32).to_le_bytes());
+    header[8..12].copy_from_slice(b"WAVE");
+    header[12..16].copy_from_slice(b"fmt ");
+    header[16..20].copy_from_slice(&16u32.to_le_bytes());
+    header[20..22].copy_from_slice(&format.to_le_bytes());
+    header[22..24].copy_from_slice(&1u16.to_le_bytes()); // mono
+    header[24..28].copy_from_slice(&sample_rate.to_le_bytes());
+    header[28..32].copy_from_slice(&(sample_rate * bytes).to_le_bytes());
+    header[32..34].copy_from_slice(&(bytes as u16).to_le_bytes());
+    header[34..36].copy_from_slice(&(bytes as u16 * 8).to_le_bytes());
+    header[36..40].copy_from_slice(b"data");
+    header[40..44].copy_from_slice(&(data_len as u32).to_le_bytes());
+    header
 }
 
-/// Shared state that both capture threads push chunks into,
-/// and the Tauri command polls from.
-pub struct AudioCaptureState {
-    pub running: AtomicBool,
-    pub chunks: Mutex<Vec<AudioChunk>>,
-    /// Cumulative seconds of speech detected per source, for talk-ratio
-    pub mic_speech_secs: Mutex<f32>,
-    pub loopback_speech_secs: Mutex<f32>,
+/// Triangular-PDF dither noise in LSBs (sum of two uniform ±0.5 LSB draws),
+/// from a xorshift generator seeded per chunk.
+struct TpdfDither(u32);
+
+impl TpdfDither {
+    fn new() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        // xorshift must not start at zero
+        Self(seed | 1)
+    }
+
+    fn uniform(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32 - 0.5
+    }
+
+    fn next_lsb(&mut self) -> f32 {
+        self.uniform() + self.uniform()
+    }
 }
 
-impl Default for AudioCaptureState {
-    fn default() -> Self {
-        Self {
-            running: AtomicBool::new(false),
-            chunks: Mutex::new(Vec::new()),
-            mic_speech_secs: Mutex::new(0.0),
-            loopback_speech_secs: Mutex::new(0.0),
-        }
+fn frame_rms(samples: &[f32]) -> f32 {
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+fn to_dbfs(level: f32) -> f32 {
+    if level > 0.0 {
+        (20.0 * level.log10()).max(DBFS_FLOOR)
+    } else {
+        DBFS_FLOOR
     }
 }
 
-/// Builds a cpal input stream for a given device.
-/// `source_label` is "mic" or "loopback".
-/// Captured audio is VAD-sliced and pushed into `state.chunks`.
-fn build_capture_stream(
-    device: &cpal::Device,
-    source_label: &str,
-    state: Arc<AudioCaptureState>,
-) -> Result<(cpal::Stream, StreamConfig), String> {
-    let supported = device
-        .default_input_config()
-        .map_err(|e| format!("No default config for {}: {}", source_label, e))?;
+/// Soft-knee limiter: linear up to `LIMITER_KNEE`, then tanh saturation that
+/// approaches but never reaches full scale. The curve is continuous with
+/// unit slope at the knee, so quiet audio is untouched.
+fn soft_limit(sample: f32) -> f32 {
+    let magnitude = sample.abs();
+    if magnitude <= LIMITER_KNEE {
+        return sample;
+    }
+    let headroom = 1.0 - LIMITER_KNEE;
+    let limited = LIMITER_KNEE + headroom * ((magnitude - LIMITER_KNEE) / headroom).tanh();
+    limited.copysign(sample)
+}
 
-    let sample_format = supported.sample_format();
-    let config: StreamConfig = supported.into();
+/// One RBJ-cookbook biquad section (transposed direct form II).
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
 
-    let channels = config.channels;
-    let sample_rate = config.sample_rate.0;
-    let label = source_label.to_string();
+impl Biquad {
+    /// Butterworth (Q = 1/√2) low- or high-pass at `cutoff_hz`.
+    fn butterworth(cutoff_hz: f32, high_pass: bool, sample_rate: u32) -> Self {
+        let w0 = 2.0 * std::f32::consts::PI * cutoff_hz / sample_rate as f32;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / std::f32::consts::SQRT_2;
+        let a0 = 1.0 + alpha;
+        let (b0, b1) = if high_pass {
+            ((1.0 + cos) / 2.0, -(1.0 + cos))
+        } else {
+            ((1.0 - cos) / 2.0, 1.0 - cos)
+        };
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b0 / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha) / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
 
-    let vad = Arc::new(Mutex::new(VadAccumulator::new(&label)));
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+}
 
-    let speech_secs = state.clone();
-    let state_for_stream = state.clone();
-    let label_for_err = label.clone();
+/// Filter applied to the VAD's analysis copy of each frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnalysisFilter {
+    /// The VAD sees the signal as captured (default)
+    #[default]
+    None,
+    /// 150 Hz high-pass: drops rumble, keeps all of the voice band
+    Highpass,
+    /// 300–3400 Hz band-pass: also drops hiss above the telephony band
+    Bandpass,
+}
 
-    let stream = match sample_format {
-        SampleFormat::F32 => device.build_input_stream(
-            &config,
-            move |data: &[f32], _info| {
-                if !state_for_stream.running.load(Ordering::Relaxed) {
-                    return;
-                }
-                let mono_16k = to_mono_16k(data, channels, sample_rate);
-                if let Ok(mut vad_lock) = vad.lock() {
-                    if let Some(chunk) = vad_lock.feed(&mono_16k) {
-                        // Track cumulative speech time
-                        let secs_mutex = if label == "mic" {
-                            &speech_secs.mic_speech_secs
-                        } else {
-                            &speech_secs.loopback_speech_secs
-                        };
-                        if let Ok(mut secs) = secs_mutex.lock() {
-                            *secs += chunk.duration_secs;
-                        }
-                        if let Ok(mut chunks) = state_for_stream.chunks.lock() {
-                            chunks.push(chunk);
-                        }
-                    }
-                }
-            },
-            move |err| {
-                log::error!("Audio stream error ({}): {}", label_for_err, err);
-            },
-            None,
-        ),
-        SampleFormat::I16 => {
-            let vad_i16 = Arc::new(Mutex::new(VadAccumulator::new(&label)));
-            let state_i16 = state.clone();
-            let speech_secs_i16 = state.clone();
-            let label_i16 = label.clone();
-            let label_err_i16 = label.clone();
-            device.build_input_stream(
-                &config,
-                move |data: &[i16], _info| {
-                    if !state_i16.running.load(Ordering::Relaxed) {
-                        return;
-                    }
-                    let f32_data: Vec<f32> =
-                        data.iter().map(|&s| s as f32 / i16::MAX as f32).collect();
-                    let mono_16k = to_mono_16k(&f32_data, channels, sample_rate);
-                    if let Ok(mut vad_lock) = vad_i16.lock() {
-                        if let Some(chunk) = vad_lock.feed(&mono_16k) {
-                            let secs_mutex = if label_i16 == "mic" {
-                                &speech_secs_i16.mic_speech_secs
-                            } else {
-                                &speech_secs_i16.loopback_speech_secs
-                            };
-                            if let Ok(mut secs) = secs_mutex.lock() {
-                                *secs += chunk.duration_secs;
-                            }
-                            if let Ok(mut chunks) = state_i16.chunks.lock() {
-                                chunks.push(chunk);
-                            }
-                        }
-                    }
-                },
-                move |err| {
-                    log::error!("Audio stream error ({}): {}", label_err_i16, err);
-                },
-                None,
-            )
-        }
-        _ => {
-            return Err(format!(
-                "Unsupported sample format {:?} for {}",
-                sample_format, source_label
-            ));
+impl AnalysisFilter {
+    pub fn parse(kind: &str) -> Result<Self, String> {
+        match kind {
+            "none" => Ok(Self::None),
+            "highpass" => Ok(Self::Highpass),
+            "bandpass" => Ok(Self::Bandpass),
+            other => Err(format!(
+                "Unknown analysis filter '{}' (expected \"none\", \"highpass\" or \"bandpass\")",
+                other
+            )),
         }
     }
-    .map_err(|e| format!("Failed to build {} stream: {}", source_label, e))?;
+}
 
-    Ok((stream, config))
+/// Cascaded Butterworth sections for an `AnalysisFilter`: two per edge, so
+/// 4th order on each side.
+#[derive(Debug, Clone)]
+struct VadFilter {
+    kind: AnalysisFilter,
+    sections: Vec<Biquad>,
 }
 
-/// Lists all available audio input devices with their names.
-pub fn list_input_devices() -> Vec<String> {
-    let host = cpal::default_host();
-    let mut names = Vec::new();
-    if let Ok(devices) = host.input_devices() {
-        for device in devices {
-            if let Ok(name) = device.name() {
-                names.push(name);
+impl VadFilter {
+    fn new(kind: AnalysisFilter, sample_rate: u32) -> Self {
+        let high = |hz| Biquad::butterworth(hz, true, sample_rate);
+        let low = |hz| Biquad::butterworth(hz, false, sample_rate);
+        let sections = match kind {
+            AnalysisFilter::None => Vec::new(),
+            AnalysisFilter::Highpass => vec![high(HIGHPASS_HZ), high(HIGHPASS_HZ)],
+            AnalysisFilter::Bandpass => vec![
+                high(BANDPASS_LOW_HZ),
+                high(BANDPASS_LOW_HZ),
+                low(BANDPASS_HIGH_HZ),
+                low(BANDPASS_HIGH_HZ),
+            ],
+        };
+        Self { kind, sections }
+    }
+
+    fn process_into(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        out.clear();
+        out.extend(input.iter().map(|&x| self.sections.iter_mut().fold(x, |s, bq| bq.process(s))));
+    }
+}
+
+/// Normalized-LMS acoustic echo canceller: learns the speaker-to-mic path
+/// from the loopback reference and subtracts its estimate from the mic.
+/// Loopback frames queue up in `reference`; each mic sample consumes one,
+/// so both sources must run at the same rate. Adaptation doesn't pause for
+/// double-talk, so the estimate wobbles while both sides speak.
+struct EchoCanceller {
+    weights: Vec<f32>,
+    /// Reference history written twice (at `pos` and `pos + taps`) so the
+    /// latest `taps` samples are always one contiguous slice
+    history: Vec<f32>,
+    pos: usize,
+    /// Sum of squares over the window, kept incrementally
+    energy: f32,
+    reference: VecDeque<f32>,
+    max_queued: usize,
+}
+
+impl EchoCanceller {
+    fn new() -> Self {
+        Self {
+            weights: Vec::new(),
+            history: Vec::new(),
+            pos: 0,
+            energy: 0.0,
+            reference: VecDeque::new(),
+            max_queued: 0,
+        }
+    }
+
+    /// Sizes the filter for `tail_ms` at `sample_rate`, starting over if
+    /// either changed.
+    fn configure(&mut self, tail_ms: u32, sample_rate: u32) {
+        let taps = (tail_ms as usize * sample_rate as usize / 1000).max(1);
+        if self.weights.len() == taps {
+            return;
+        }
+        *self = Self::new();
+        self.weights = vec![0.0; taps];
+        self.history = vec![0.0; taps * 2];
+        self.max_queued = AEC_MAX_QUEUED_MS as usize * sample_rate as usize / 1000;
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    fn push_reference(&mut self, loopback: &[f32]) {
+        self.reference.extend(loopback.iter().copied());
+        let excess = self.reference.len().saturating_sub(self.max_queued);
+        self.reference.drain(..excess);
+    }
+
+    /// Replaces the `mic` frame with its echo-cancelled version.
+    fn cancel(&mut self, mic: &mut [f32]) {
+        let taps = self.weights.len();
+        for m in mic.iter_mut() {
+            // No reference (loopback idle or behind) cancels nothing
+            let r = self.reference.pop_front().unwrap_or(0.0);
+            let oldest = self.history[self.pos];
+            self.energy = (self.energy + r * r - oldest * oldest).max(0.0);
+            self.history[self.pos] = r;
+            self.history[self.pos + taps] = r;
+            self.pos = (self.pos + 1) % taps;
+
+            let window = &self.history[self.pos..self.pos + taps];
+            let estimate: f32 = self.weights.iter().zip(window).map(|(w, x)| w * x).sum();
+            let error = *m - estimate;
+            let step = AEC_STEP * error / (self.energy + AEC_EPSILON);
+            for (w, x) in self.weights.iter_mut().zip(window) {
+                *w += step * x;
             }
+            *m = error;
         }
     }
-    names
 }
 
-/// Lists all available audio output devices.
-pub fn list_output_devices() -> Vec<String> {
-    let host = cpal::default_host();
-    let mut names = Vec::new();
-    if let Ok(devices) = host.output_devices() {
-        for device in devices {
-            if let Ok(name) = device.name() {
-                names.push(name);
-            }
+/// How the two capture streams are turned into chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureMode {
+    /// Independent VAD and chunks per source ("mic" / "loopback")
+    PerSource,
+    /// Mic and loopback summed into one mono signal with a single VAD,
+    /// emitted as source "mixed"
+    MixedMono,
+}
+
+impl CaptureMode {
+    pub fn parse(mode: &str) -> Result<Self, String> {
+        match mode {
+            "per-source" => Ok(Self::PerSource),
+            "mixed-mono" => Ok(Self::MixedMono),
+            other => Err(format!(
+                "Unknown capture mode '{}' (expected \"per-source\" or \"mixed-mono\")",
+                other
+            )),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::PerSource => "per-source",
+            Self::MixedMono => "mixed-mono",
+        }
+    }
+
+    /// Mode whose accumulators emit chunks of this `AudioChunk.layout`.
+    fn of_layout(layout: &str) -> Self {
+        if layout == "mixed" {
+            Self::MixedMono
+        } else {
+            Self::PerSource
         }
     }
-    names
 }
 
-/// Starts dual capture (mic + loopback). Returns the two cpal::Stream handles
-/// which must be kept alive for the duration of capture.
-///
-/// On Windows (WASAPI), loopback capture is done by opening the default
-/// output device as an input stream -- WASAPI exposes this automatically.
-/// On macOS, CoreAudio aggregate devices or ScreenCaptureKit are needed;
-/// this implementation captures the default output device which works
-/// with loopback-capable drivers.
-/// On Linux, PipeWire/PulseAudio monitor sources appear as input devices.
-pub fn start_capture(
-    state: Arc<AudioCaptureState>,
-) -> Result<(cpal::Stream, cpal::Stream), String> {
-    let host = cpal::default_host();
+/// How `emit_flushed` orders utterances that end together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlushOrder {
+    /// Earliest `onset_ms` first, so seq follows speech order (default);
+    /// equal onsets fall back to `Source` order
+    Onset,
+    /// Mic, then loopback, then mixed, whatever their timing
+    Source,
+}
 
-    // ── Microphone (default input device) ──
-    let mic_device = host
-        .default_input_device()
-        .ok_or("No default input (microphone) device found")?;
-    log::info!(
-        "Mic device: {}",
-        mic_device.name().unwrap_or_default()
-    );
+impl FlushOrder {
+    pub fn parse(order: &str) -> Result<Self, String> {
+        match order {
+            "onset" => Ok(Self::Onset),
+            "source" => Ok(Self::Source),
+            other => Err(format!(
+                "Unknown flush order '{}' (expected \"onset\" or \"source\")",
+                other
+            )),
+        }
+    }
+}
 
-    let (mic_stream, mic_config) = build_capture_stream(&mic_device, "mic", state.clone())?;
-    log::info!(
-        "Mic stream: {}ch @ {}Hz",
-        mic_config.channels,
-        mic_config.sample_rate.0
-    );
+/// What wraps the PCM in `AudioChunk.audio_b64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Container {
+    /// Bare little-endian PCM (default)
+    Raw,
+    /// 44-byte WAV header + PCM
+    Wav,
+}
 
-    // ── Loopback (default output device captured as input) ──
-    //
-    // Platform behavior:
-    //   Windows (WASAPI): default_output_device supports build_input_stream
-    //     for loopback capture natively.
-    //   Linux: PipeWire/PulseAudio monitor sources show up as input devices.
-    //     We try to find a "Monitor" device first, fall back to default output.
-    //   macOS: Requires a loopback driver (e.g., BlackHole) or ScreenCaptureKit.
-    //     We try default output; this works if a loopback driver is installed.
-    let loopback_device = find_loopback_device(&host)
-        .ok_or("No loopback/monitor audio device found. On Linux, ensure PipeWire or PulseAudio is running. On Windows, WASAPI loopback is used automatically. On macOS, a loopback audio driver is required.")?;
+impl Container {
+    pub fn parse(container: &str) -> Result<Self, String> {
+        match container {
+            "raw" => Ok(Self::Raw),
+            "wav" => Ok(Self::Wav),
+            other => Err(format!(
+                "Unknown container '{}' (expected \"raw\" or \"wav\")",
+                other
+            )),
+        }
+    }
 
-    log::info!(
-        "Loopback device: {}",
-        loopback_device.name().unwrap_or_default()
-    );
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Raw => "raw",
+            Self::Wav => "wav",
+        }
+    }
 
-    let (loopback_stream, loopback_config) =
-        build_capture_stream(&loopback_device, "loopback", state.clone())?;
-    log::info!(
-        "Loopback stream: {}ch @ {}Hz",
-        loopback_config.channels,
-        loopback_config.sample_rate.0
-    );
+    fn header_len(self) -> usize {
+        match self {
+            Self::Raw => 0,
+            Self::Wav => WAV_HEADER_LEN,
+        }
+    }
+}
 
-    // Mark running and start both streams
-    state.running.store(true, Ordering::SeqCst);
-    mic_stream.play().map_err(|e| format!("Mic play failed: {}", e))?;
-    loopback_stream
-        .play()
-        .map_err(|e| format!("Loopback play failed: {}", e))?;
+/// PCM encoding of emitted chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SampleType {
+    /// 16-bit integer PCM (default; half the size)
+    I16,
+    /// 32-bit float, no quantization or clamping
+    F32,
+}
 
-    Ok((mic_stream, loopback_stream))
+impl SampleType {
+    pub fn parse(sample_type: &str) -> Result<Self, String> {
+        match sample_type {
+            "i16" => Ok(Self::I16),
+            "f32" => Ok(Self::F32),
+            other => Err(format!(
+                "Unknown sample type '{}' (expected \"i16\" or \"f32\")",
+                other
+            )),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::I16 => "i16",
+            Self::F32 => "f32",
+        }
+    }
+
+    fn bytes(self) -> usize {
+        match self {
+            Self::I16 => 2,
+            Self::F32 => 4,
+        }
+    }
 }
 
-/// Platform-aware loopback device finder.
-/// On Linux, searches input devices for monitor sources using prioritized
-/// keywords. Logs all discovered devices for debugging.
-fn find_loopback_device(host: &cpal::Host) -> Option<cpal::Device> {
-    #[cfg(target_os = "linux")]
-    {
-        if let Ok(devices) = host.input_devices() {
-            let mut all_names = Vec::new();
-            let mut best: Option<(usize, cpal::Device)> = None;
-
-            // Priority keywords (lower index = higher priority)
-            let keywords = [
-                "maestro",      // Our virtual sink from setup_audio.sh
-                "monitor of",   // PipeWire naming convention
-                ".monitor",     // PulseAudio naming convention
-                "monitor",      // Generic monitor source
-            ];
-
-            for device in devices {
-                if let Ok(name) = device.name() {
-                    all_names.push(name.clone());
-                    let lower = name.to_lowercase();
-                    for (priority, kw) in keywords.iter().enumerate() {
-                        if lower.contains(kw) {
-                            if best.as_ref().map_or(true, |(p, _)| priority < *p) {
-                                best = Some((priority, device));
-                            }
-                            break;
-                        }
-                    }
-                }
-            }
+/// What happens when a chunk arrives and the poll queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackpressurePolicy {
+    /// Keep queueing without bound (original behavior)
+    Buffer,
+    /// Discard the oldest queued chunk to make room
+    DropOldest,
+    /// Append the new chunk's audio to the latest queued chunk from the same
+    /// source; falls back to dropping the oldest if there is none
+    Coalesce,
+}
 
-            log::info!("Available input devices: {:?}", all_names);
+impl BackpressurePolicy {
+    pub fn parse(policy: &str) -> Result<Self, String> {
+        match policy {
+            "buffer" => Ok(Self::Buffer),
+            "drop-oldest" => Ok(Self::DropOldest),
+            "coalesce" => Ok(Self::Coalesce),
+            other => Err(format!(
+                "Unknown backpressure policy '{}' (expected \"buffer\", \"drop-oldest\" or \"coalesce\")",
+                other
+            )),
+        }
+    }
 
-            if let Some((_, device)) = best {
-                log::info!("Selected loopback: {}", device.name().unwrap_or_default());
-                return Some(device);
-            }
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Buffer => "buffer",
+            Self::DropOldest => "drop-oldest",
+            Self::Coalesce => "coalesce",
         }
     }
+}
 
-    // On Windows, WASAPI exposes loopback via the default output device
-    // opened as an input stream. On macOS, this works if a loopback
-    // driver is installed.
-    #[cfg(any(target_os = "windows", target_os = "macos"))]
-    {
-        return host.default_output_device();
+/// How far one side of the mixer may run ahead (~250ms) before the lagging
+/// side is zero-padded. WASAPI loopback, for instance, delivers no callbacks
+/// at all while nothing is playing.
+const MIX_MAX_LAG_SAMPLES: usize = TARGET_SAMPLE_RATE as usize / 4;
+
+/// Sums mic and loopback (mono 16 kHz) sample-by-sample into one signal and
+/// runs a single VAD over the mix. Both streams are aligned by arrival order
+/// from the moment capture starts.
+pub struct MonoMixer {
+    mic: VecDeque<f32>,
+    loopback: VecDeque<f32>,
+    mic_gain: f32,
+    loopback_gain: f32,
+    vad: VadAccumulator,
+    mixed: Vec<f32>,
+}
+
+impl MonoMixer {
+    fn new() -> Self {
+        Self {
+            mic: VecDeque::new(),
+            loopback: VecDeque::new(),
+            mic_gain: 1.0,
+            loopback_gain: 1.0,
+            vad: VadAccumulator::new("mixed").with_layout("mixed"),
+            mixed: Vec::new(),
+        }
     }
 
-    // Fallback for Linux: try default output device
-    #[cfg(target_os = "linux")]
-    {
-        log::warn!("No monitor source found, falling back to default output device");
-        host.default_output_device()
+    /// Drops queued audio and any half-finished utterance, keeping the gains.
+    fn reset(&mut self) {
+        self.mic.clear();
+        self.loopback.clear();
+        self.vad.reset();
+    }
+
+    /// Queues one source's samples, mixes whatever both sides have in common,
+    /// and returns the chunk(s) if the mixed VAD detected the end of an utterance.
+    fn push(&mut self, source: &str, mono_16k: &[f32], settings: &AudioSettings) -> Vec<AudioChunk> {
+        let (own, other) = if source == "mic" {
+            (&mut self.mic, &mut self.loopback)
+        } else {
+            (&mut self.loopback, &mut self.mic)
+        };
+        own.extend(mono_16k.iter().copied());
+        if own.len() > MIX_MAX_LAG_SAMPLES && other.len() < own.len() {
+            other.resize(own.len(), 0.0);
+        }
+
+        let ready = self.mic.len().min(self.loopback.len());
+        if ready == 0 {
+            return Vec::new();
+        }
+
+        self.mixed.clear();
+        for (m, l) in self.mic.drain(..ready).zip(self.loopback.drain(..ready)) {
+            // Clamp the sum so two loud sources can't overshoot full scale
+            self.mixed
+                .push((m * self.mic_gain + l * self.loopback_gain).clamp(-1.0, 1.0));
+        }
+        self.vad.feed(&self.mixed, settings)
     }
 }
 
-/// Stops capture by setting the running flag to false.
-/// The Stream handles should be dropped by the caller to fully release devices.
-pub fn stop_capture(state: &AudioCaptureState) {
-    state.running.store(false, Ordering::SeqCst);
+/// How interleaved channels are folded to mono.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Downmix {
+    /// Average all channels
+    #[default]
+    Average,
+    /// Use channel 0 only; for phase-inverted pairs that average to silence
+    FirstChannel,
 }
 
-/// Drains all pending audio chunks from the shared state.
-pub fn drain_chunks(state: &AudioCaptureState) -> Vec<AudioChunk> {
-    if let Ok(mut chunks) = state.chunks.lock() {
-        chunks.drain(..).collect()
-    } else {
-        Vec::new()
+/// Converts interleaved multi-channel audio at an arbitrary sample rate
+/// to mono at `output_rate` using simple linear interpolation.
+/// Writes into caller-owned buffers (`mono` is scratch) so the realtime
+/// path reuses its allocations instead of creating new ones per callback.
+fn to_mono_into(
+    input: &[f32],
+    channels: u16,
+    input_rate: u32,
+    output_rate: u32,
+    downmix: Downmix,
+    mono: &mut Vec<f32>,
+    out: &mut Vec<f32>,
+) {
+    // Step 1: downmix to mono
+    mono.clear();
+    out.clear();
+    if channels == 0 || input_rate == 0 {
+        // Rejected when streams open; never divide by it here
+        return;
+    }
+    let frames = input.chunks_exact(channels as usize);
+    match downmix {
+        Downmix::Average => mono.extend(frames.map(|frame| frame.iter().sum::<f32>() / channels as f32)),
+        Downmix::FirstChannel => mono.extend(frames.map(|frame| frame[0])),
+    }
+
+    // Step 2: resample if needed
+    if input_rate == output_rate {
+        out.extend_from_slice(mono);
+        return;
+    }
+
+    let ratio = output_rate as f64 / input_rate as f64;
+    let output_len = (mono.len() as f64 * ratio) as usize;
+    out.reserve(output_len);
+
+    for i in 0..output_len {
+        let src_idx = i as f64 / ratio;
+        let idx0 = src_idx.floor() as usize;
+        let idx1 = (idx0 + 1).min(mono.len().saturating_sub(1));
+        let frac = (src_idx - idx0 as f64) as f32;
+        out.push(mono[idx0] * (1.0 - frac) + mono[idx1] * frac);
     }
 }
 
-/// Returns (mic_secs, loopback_secs) for talk-ratio calculation.
-pub fn get_talk_ratio(state: &AudioCaptureState) -> (f32, f32) {
-    let mic = state
-        .mic_speech_secs
-        .lock()
-        .map(|s| *s)
-        .unwrap_or(0.0);
-    let loopback = state
-        .loopback_speech_secs
-        .lock()
-        .map(|s| *s)
-        .unwrap_or(0.0);
-    (mic, loopback)
+/// Per-stream conversion buffers, owned by the stream's data callback.
+/// They grow to the device's callback size once and are then reused.
+#[derive(Default)]
+struct ConversionScratch {
+    /// i16 input converted to f32
+    samples: Vec<f32>,
+    /// downmixed audio at the device rate
+    mono: Vec<f32>,
+    /// mono result at the target rate, handed to the VAD
+    out: Vec<f32>,
+    /// switched to `FirstChannel` by the phase check
+    downmix: Downmix,
+}
+
+impl ConversionScratch {
+    fn convert_f32(&mut self, data: &[f32], channels: u16, sample_rate: u32, target_rate: u32, gain: f32) -> &mut [f32] {
+        to_mono_into(data, channels, sample_rate, target_rate, self.downmix, &mut self.mono, &mut self.out);
+        self.compensate(gain);
+        &mut self.out
+    }
+
+    fn convert_i16(&mut self, data: &[i16], channels: u16, sample_rate: u32, target_rate: u32, gain: f32) -> &mut [f32] {
+        self.samples.clear();
+        self.samples
+            .extend(data.iter().map(|&s| s as f32 / i16::MAX as f32));
+        to_mono_into(&self.samples, channels, sample_rate, target_rate, self.downmix, &mut self.mono, &mut self.out);
+        self.compensate(gain);
+        &mut self.out
+    }
+
+    /// Applies the downmix gain compensation. Only averaging attenuates, so
+    /// a `FirstChannel` downmix is left alone.
+    fn compensate(&mut self, gain: f32) {
+        if self.downmix == Downmix::Average && gain != 1.0 {
+            self.out.iter_mut().for_each(|s| *s *= gain);
+        }
+    }
+}
+
+/// Normalized correlation of the first two channels, or `None` for mono
+/// input or a buffer too quiet to judge.
+fn channel_correlation<T: Copy>(data: &[T], channels: u16, to_f32: impl Fn(T) -> f32) -> Option<f32> {
+    if channels < 2 {
+        return None;
+    }
+    let (mut lr, mut ll, mut rr) = (0.0f32, 0.0f32, 0.0f32);
+    for frame in data.chunks_exact(channels as usize) {
+        let (l, r) = (to_f32(frame[0]), to_f32(frame[1]));
+        lr += l * r;
+        ll += l * l;
+        rr += r * r;
+    }
+    let frames = (data.len() / channels as usize).max(1) as f32;
+    if (ll / frames).sqrt() < SUSPEND_WAKE_RMS || (rr / frames).sqrt() < SUSPEND_WAKE_RMS {
+        return None;
+    }
+    Some(lr / (ll * rr).sqrt())
+}
+
+/// Number of distinct channels in the buffer: a channel that matches an
+/// earlier one sample-for-sample (within `CHANNEL_MATCH_TOLERANCE`) is a
+/// copy. `None` for mono input or a buffer too quiet to tell.
+fn distinct_channels<T: Copy>(data: &[T], channels: u16, to_f32: impl Fn(T) -> f32) -> Option<u16> {
+    if channels < 2 {
+        return None;
+    }
+    let frame_len = channels as usize;
+    let sum_sq: f32 = data.iter().map(|&s| to_f32(s).powi(2)).sum();
+    if (sum_sq / data.len().max(1) as f32).sqrt() < SUSPEND_WAKE_RMS {
+        return None;
+    }
+    let distinct = (0..frame_len)
+        .filter(|&c| {
+            !(0..c).any(|earlier| {
+                data.chunks_exact(frame_len)
+                    .all(|frame| (to_f32(frame[c]) - to_f32(frame[earlier])).abs() <= CHANNEL_MATCH_TOLERANCE)
+            })
+        })
+        .count();
+    Some(distinct as u16)
+}
+
+/// Device channel count versus the channels that actually differ.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChannelStats {
+    pub channels: u16,
+    /// Distinct channels seen at the last check; `None` until a non-silent
+    /// multi-channel buffer has been checked
+    pub effective_channels: Option<u16>,
+}
+
+/// Returned by `get_channel_stats`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelReport {
+    pub mic: ChannelStats,
+    pub loopback: ChannelStats,
+}
+
+/// Payload of the one-time `"phase-inversion-detected"` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseInversion {
+    pub source: String,
+    pub correlation: f32,
+    /// Downmix the stream switched to
+    pub downmix: String,
+}
+
+/// Device names opened by the most recent capture session.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceSelection {
+    pub mic: String,
+    pub loopback: String,
+}
+
+/// Negotiated setup of one capture stream.
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamInfo {
+    pub device: String,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/// Payload of the `"capture-started"` event, emitted once both streams play.
+/// A start that can't build or play either stream fails instead, so the
+/// event always means mic and loopback are both running.
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureStarted {
+    pub mic: StreamInfo,
+    pub loopback: StreamInfo,
+}
+
+/// Result of a restart: the devices actually opened, plus a note for
+/// every remembered device that had to be replaced by a default.
+#[derive(Debug, Clone, Serialize)]
+pub struct RestartReport {
+    pub devices: DeviceSelection,
+    pub substitutions: Vec<String>,
+}
+
+/// Liveness of one capture stream, as seen by `stream_health`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceHealth {
+    /// Whether a stream handle for this source is held
+    pub held: bool,
+    pub device: Option<String>,
+    /// Milliseconds since the stream's last callback; `None` if it never
+    /// called back
+    pub last_callback_ms_ago: Option<u64>,
+    /// Held, but no callback for `STREAM_STALE_MS`
+    pub stale: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamHealth {
+    pub running: bool,
+    pub mic: SourceHealth,
+    pub loopback: SourceHealth,
+}
+
+/// `AudioSettings` behind a lock, shared with the audio callbacks as a
+/// snapshot (`snapshot`) rather than a per-frame deep copy. Writes through
+/// `lock` copy the settings only while a snapshot is still held.
+#[derive(Default)]
+struct SettingsCell(Mutex<Arc<AudioSettings>>);
+
+impl SettingsCell {
+    fn lock(&self) -> Result<SettingsGuard<'_>, std::sync::PoisonError<std::sync::MutexGuard<'_, Arc<AudioSettings>>>> {
+        self.0.lock().map(SettingsGuard)
+    }
+
+    /// The current settings, without copying them.
+    fn snapshot(&self) -> Option<Arc<AudioSettings>> {
+        self.0.lock().ok().map(|settings| Arc::clone(&settings))
+    }
+}
+
+struct SettingsGuard<'a>(std::sync::MutexGuard<'a, Arc<AudioSettings>>);
+
+impl std::ops::Deref for SettingsGuard<'_> {
+    type Target = AudioSettings;
+
+    fn deref(&self) -> &AudioSettings {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for SettingsGuard<'_> {
+    fn deref_mut(&mut self) -> &mut AudioSettings {
+        Arc::make_mut(&mut self.0)
+    }
+}
+
+/// Shared state that both capture threads push chunks into,
+/// and the Tauri command polls from.
+pub struct AudioCaptureState {
+    pub running: AtomicBool,
+    pub chunks: Mutex<Vec<AudioChunk>>,
+    /// Cumulative seconds of speech detected per source, for talk-ratio
+    pub mic_speech_secs: Mutex<f32>,
+    pub loopback_speech_secs: Mutex<f32>,
+    /// Devices used by the last successful start, kept across stop so a
+    /// restart reopens the same hardware
+    pub last_devices: Mutex<Option<DeviceSelection>>,
+    /// Reference point for `AudioChunk.emit_nanos`
+    epoch: Instant,
+    pub capture_mode: Mutex<CaptureMode>,
+    /// Applied in `emit_chunk` once the queue holds `MAX_QUEUED_CHUNKS`
+    pub backpressure: Mutex<BackpressurePolicy>,
+    /// Chunks discarded / merged by the backpressure policy, for
+    /// `get_buffer_stats`
+    pub dropped_chunks: AtomicU64,
+    pub coalesced_chunks: AtomicU64,
+    /// Combiner used while `capture_mode` is `MixedMono`
+    pub mixer: Mutex<MonoMixer>,
+    /// Mic echo canceller, fed the loopback as its reference
+    echo_canceller: Mutex<EchoCanceller>,
+    /// What currently owns capture; one at a time (see `claim_session`)
+    active_session: Mutex<Option<SessionKind>>,
+    /// Armed by `diagnostic_raw_capture` to copy a running stream's audio,
+    /// before the VAD, into `"raw-audio"` events
+    raw_tap: Mutex<Option<RawTap>>,
+    /// Set while `capture_when_visible_only` has paused capture; callbacks
+    /// drop audio but the devices stay open
+    pub privacy_paused: AtomicBool,
+    /// Push-to-talk key state, read every callback while `ptt_mode` is on
+    pub ptt_active: AtomicBool,
+    /// Tracks mic speech/silence runs for the `"wake"` event
+    wake: Mutex<WakeTrigger>,
+    /// Next `AudioChunk.seq` to hand out
+    next_seq: AtomicU64,
+    /// Bumped by `reset_capture_state`; a chunk flushed before a reset is
+    /// dropped instead of queued
+    generation: AtomicU64,
+    /// Current `AudioChunk.session_id`; empty until the first start
+    pub session_id: Mutex<String>,
+    /// Annotations for the most recent chunks, keyed by seq and bounded to
+    /// `MAX_ANNOTATIONS` entries
+    pub annotations: Mutex<BTreeMap<u64, serde_json::Value>>,
+    /// Copies of the most recent chunks keyed by seq (same addressing as
+    /// `annotations`), bounded to `MAX_RECENT_CHUNKS`
+    pub recent_chunks: Mutex<BTreeMap<u64, AudioChunk>>,
+    /// Human labels per source ("mic" -> "Alice"), from `set_source_speaker`
+    speakers: Mutex<BTreeMap<String, String>>,
+    settings: SettingsCell,
+    /// Live mirror of each accumulator's `is_speaking`, updated every frame
+    pub mic_speaking: AtomicBool,
+    pub loopback_speaking: AtomicBool,
+    /// Nanoseconds after `epoch` when either flag was last seen set, for the
+    /// `anyone_speaking` hold; 0 if never
+    last_speaking_nanos: AtomicU64,
+    /// Nanoseconds after `epoch` of the last emitted chunk or session start,
+    /// for the idle stop
+    last_activity_nanos: AtomicU64,
+    /// When an idle-stopped session should reopen; cleared by any stop or
+    /// start
+    idle_rearm_at: Mutex<Option<Instant>>,
+    /// Nanoseconds after `epoch` of each stream's latest callback; 0 until
+    /// the first one
+    mic_last_callback: AtomicU64,
+    loopback_last_callback: AtomicU64,
+    /// Audio length of each stream's latest callback buffer, in
+    /// microseconds; 0 until the first one
+    mic_callback_us: AtomicU64,
+    loopback_callback_us: AtomicU64,
+    /// Mic streams carry the build generation they were created under and
+    /// only feed the accumulator while it matches the live one, so a
+    /// `switch_mic` replacement can run alongside the old stream silently
+    /// until the handover
+    mic_build_generation: AtomicU64,
+    mic_live_generation: AtomicU64,
+    /// Reported versus distinct channels per source, from the last stream
+    /// opened for it
+    mic_channel_stats: Mutex<ChannelStats>,
+    loopback_channel_stats: Mutex<ChannelStats>,
+    /// Overruns detected per source since the session started
+    mic_xruns: XrunCounters,
+    loopback_xruns: XrunCounters,
+    /// Per-utterance log behind the talk ratio, bounded to `MAX_TALK_HISTORY`
+    pub talk_history: Mutex<VecDeque<TalkRecord>>,
+    /// Utterance count and total length per source, behind
+    /// `get_avg_utterance_length`; counted like the talk ratio
+    mic_utterances: Mutex<UtteranceTally>,
+    loopback_utterances: Mutex<UtteranceTally>,
+    /// Per-source VAD state, fed by the stream callbacks. Kept here rather
+    /// than inside the callbacks so it can be flushed or reset from commands.
+    mic_vad: Mutex<VadAccumulator>,
+    loopback_vad: Mutex<VadAccumulator>,
+    /// Where `shutdown` writes chunks that were never polled
+    pub recording_dir: Mutex<Option<std::path::PathBuf>>,
+    /// User-chosen loopback device; `None` uses `find_loopback_device`
+    pub loopback_device: Mutex<Option<String>>,
+    /// Latest `probe_loopback_strategies` choice, for
+    /// `describe_loopback_strategy`
+    probed_loopback: Mutex<Option<LoopbackStrategyChosen>>,
+    /// Used to emit events from the audio callbacks; attached in `setup`
+    pub app: Mutex<Option<tauri::AppHandle>>,
+    /// WebSocket link chunks are forwarded to (see `backend::connect`)
+    pub backend: Mutex<Option<crate::backend::BackendLink>>,
+    /// Whether queued chunks also reach `poll_audio_chunks`; cleared when
+    /// the backend link is the only consumer
+    pub relay_to_frontend: AtomicBool,
+}
+
+impl Default for AudioCaptureState {
+    fn default() -> Self {
+        Self {
+            running: AtomicBool::new(false),
+            chunks: Mutex::new(Vec::new()),
+            mic_speech_secs: Mutex::new(0.0),
+            loopback_speech_secs: Mutex::new(0.0),
+            last_devices: Mutex::new(None),
+            epoch: Instant::now(),
+            capture_mode: Mutex::new(CaptureMode::PerSource),
+            backpressure: Mutex::new(BackpressurePolicy::Buffer),
+            dropped_chunks: AtomicU64::new(0),
+            coalesced_chunks: AtomicU64::new(0),
+            mixer: Mutex::new(MonoMixer::new()),
+            echo_canceller: Mutex::new(EchoCanceller::new()),
+            wake: Mutex::new(WakeTrigger::default()),
+            ptt_active: AtomicBool::new(false),
+            privacy_paused: AtomicBool::new(false),
+            active_session: Mutex::new(None),
+            raw_tap: Mutex::new(None),
+            next_seq: AtomicU64::new(0),
+            generation: AtomicU64::new(0),
+            session_id: Mutex::new(String::new()),
+            annotations: Mutex::new(BTreeMap::new()),
+            recent_chunks: Mutex::new(BTreeMap::new()),
+            speakers: Mutex::new(BTreeMap::new()),
+            settings: SettingsCell::default(),
+            mic_speaking: AtomicBool::new(false),
+            loopback_speaking: AtomicBool::new(false),
+            last_speaking_nanos: AtomicU64::new(0),
+            last_activity_nanos: AtomicU64::new(0),
+            idle_rearm_at: Mutex::new(None),
+            mic_last_callback: AtomicU64::new(0),
+            mic_callback_us: AtomicU64::new(0),
+            mic_build_generation: AtomicU64::new(0),
+            mic_live_generation: AtomicU64::new(0),
+            loopback_callback_us: AtomicU64::new(0),
+            loopback_last_callback: AtomicU64::new(0),
+            mic_channel_stats: Mutex::new(ChannelStats::default()),
+            loopback_channel_stats: Mutex::new(ChannelStats::default()),
+            mic_xruns: XrunCounters::default(),
+            loopback_xruns: XrunCounters::default(),
+            talk_history: Mutex::new(VecDeque::new()),
+            mic_utterances: Mutex::new(UtteranceTally::default()),
+            loopback_utterances: Mutex::new(UtteranceTally::default()),
+            mic_vad: Mutex::new(VadAccumulator::new("mic")),
+            loopback_vad: Mutex::new(VadAccumulator::new("loopback")),
+            recording_dir: Mutex::new(None),
+            loopback_device: Mutex::new(None),
+            probed_loopback: Mutex::new(None),
+            app: Mutex::new(None),
+            backend: Mutex::new(None),
+            relay_to_frontend: AtomicBool::new(true),
+        }
+    }
+}
+
+impl AudioCaptureState {
+    fn accumulator(&self, source: &str) -> Option<&Mutex<VadAccumulator>> {
+        match source {
+            "mic" => Some(&self.mic_vad),
+            "loopback" => Some(&self.loopback_vad),
+            _ => None,
+        }
+    }
+
+    fn utterances(&self, source: &str) -> Option<&Mutex<UtteranceTally>> {
+        match source {
+            "mic" => Some(&self.mic_utterances),
+            "loopback" => Some(&self.loopback_utterances),
+            _ => None,
+        }
+    }
+
+    fn speaking_flag(&self, source: &str) -> Option<&AtomicBool> {
+        match source {
+            "mic" => Some(&self.mic_speaking),
+            "loopback" => Some(&self.loopback_speaking),
+            _ => None,
+        }
+    }
+
+    fn channel_stats(&self, source: &str) -> Option<&Mutex<ChannelStats>> {
+        match source {
+            "mic" => Some(&self.mic_channel_stats),
+            "loopback" => Some(&self.loopback_channel_stats),
+            _ => None,
+        }
+    }
+
+    fn xrun_counters(&self, source: &str) -> Option<&XrunCounters> {
+        match source {
+            "mic" => Some(&self.mic_xruns),
+            "loopback" => Some(&self.loopback_xruns),
+            _ => None,
+        }
+    }
+
+    fn last_callback(&self, source: &str) -> Option<&AtomicU64> {
+        match source {
+            "mic" => Some(&self.mic_last_callback),
+            "loopback" => Some(&self.loopback_last_callback),
+            _ => None,
+        }
+    }
+
+    fn callback_us(&self, source: &str) -> Option<&AtomicU64> {
+        match source {
+            "mic" => Some(&self.mic_callback_us),
+            "loopback" => Some(&self.loopback_callback_us),
+            _ => None,
+        }
+    }
+}
+
+/// Which sources are mid-utterance right now.
+#[derive(Debug, Clone, Serialize)]
+pub struct SpeakingState {
+    pub mic: bool,
+    pub loopback: bool,
+}
+
+/// One VAD transition, as recorded for `get_vad_log`.
+#[derive(Debug, Clone, Serialize)]
+pub struct VadEvent {
+    /// "speech_start", "flush" (an utterance was emitted), "discard_short"
+    /// (too few speech frames or under `min_chunk_secs`) or "size_split"
+    /// (the utterance exceeded `max_chunk_b64_bytes` and was split)
+    pub kind: &'static str,
+    /// Wall clock, Unix ms
+    pub ts_ms: u64,
+    /// Position in the source's audio since the accumulator was created
+    pub audio_ms: u64,
+    /// RMS of the frame that triggered it and the threshold it was held to
+    pub rms: f32,
+    pub threshold: f32,
+    /// Speech frames counted in the utterance so far
+    pub speech_frames: usize,
+    /// Audio buffered for the utterance, trailing silence included
+    pub buffered_secs: f32,
+}
+
+/// Recent VAD transitions per accumulator, oldest first.
+#[derive(Debug, Clone, Serialize)]
+pub struct VadLog {
+    pub enabled: bool,
+    pub mic: Vec<VadEvent>,
+    pub loopback: Vec<VadEvent>,
+    /// The shared accumulator used in mixed mode
+    pub mixed: Vec<VadEvent>,
+}
+
+/// One emitted utterance, as recorded for talk-ratio history.
+#[derive(Debug, Clone, Serialize)]
+pub struct TalkRecord {
+    pub seq: u64,
+    pub source: String,
+    /// utterance start, milliseconds since the UNIX epoch
+    pub start_ts_ms: u64,
+    pub duration_secs: f32,
+}
+
+/// Payload of the `"energy-frame"` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct EnergyFrame {
+    pub source: String,
+    /// Clamped to `meter_floor_dbfs`
+    pub rms_dbfs: f32,
+    /// The level was at or below the floor
+    pub silence: bool,
+}
+
+/// Emits a Tauri event to the frontend, if the app handle is attached.
+fn emit_event<S: Serialize + Clone>(state: &AudioCaptureState, event: &str, payload: S) {
+    if let Ok(app) = state.app.lock() {
+        if let Some(app) = app.as_ref() {
+            if let Err(e) = app.emit(event, payload) {
+                log::warn!("Failed to emit {}: {}", event, e);
+            }
+        }
+    }
+}
+
+/// Sends the frame's level as an `"energy-frame"` event, at most once per
+/// `energy_interval_ms` per source.
+fn maybe_emit_energy(state: &AudioCaptureState, source: &str, mono_16k: &[f32], settings: &AudioSettings) {
+    let Some(Ok(mut vad)) = state.accumulator(source).map(|v| v.lock()) else {
+        return;
+    };
+    let interval = std::time::Duration::from_millis(settings.energy_interval_ms as u64);
+    if vad.last_energy_emit.is_some_and(|t| t.elapsed() < interval) {
+        return;
+    }
+    vad.last_energy_emit = Some(Instant::now());
+    drop(vad);
+
+    let dbfs = to_dbfs(frame_rms(mono_16k));
+    emit_event(
+        state,
+        "energy-frame",
+        EnergyFrame {
+            source: source.to_string(),
+            rms_dbfs: dbfs.max(settings.meter_floor_dbfs),
+            silence: dbfs <= settings.meter_floor_dbfs,
+        },
+    );
+}
+
+/// Records a flushed chunk's speech time for the talk ratio, stamps its
+/// emit time and sequence number, and queues it for polling.
+fn emit_chunk(state: &AudioCaptureState, mut chunk: AudioChunk) {
+    let settings = state.settings.lock().map(|s| s.clone()).unwrap_or_default();
+    // Mixed chunks can't be attributed to either side of the talk ratio,
+    // nor can continuous-recording windows without speech or markers
+    let counts = chunk.contains_speech && settings.counts_in_ratio(&chunk.source);
+    // Continuous windows aren't turns, so they get no marker
+    let turn_end = (settings.turn_end_markers
+        && !settings.continuous_record
+        && chunk.marker.is_none()
+        && chunk.part + 1 == chunk.parts)
+        .then(|| turn_end_marker(&chunk, &settings));
+    let secs_mutex = match chunk.source.as_str() {
+        "mic" => Some(&state.mic_speech_secs),
+        "loopback" => Some(&state.loopback_speech_secs),
+        _ => None,
+    };
+    if let Some(Ok(mut secs)) = secs_mutex.filter(|_| counts).map(|m| m.lock()) {
+        *secs += chunk.duration_secs;
+    }
+    // Continuous windows are cut by the clock, not by turns
+    if counts && !settings.continuous_record {
+        if let Some(Ok(mut tally)) = state.utterances(&chunk.source).map(|m| m.lock()) {
+            tally.add(&chunk);
+        }
+    }
+    let mut outgoing = None;
+    if let Ok(mut chunks) = state.chunks.lock() {
+        chunk.emit_nanos = state.epoch.elapsed().as_nanos() as u64;
+        state.last_activity_nanos.store(chunk.emit_nanos, Ordering::Relaxed);
+        chunk.seq = state.next_seq.fetch_add(1, Ordering::SeqCst);
+        chunk.session_id = get_session_id(state);
+        chunk.speaker = speaker_for(state, &chunk.source);
+        if counts {
+            record_talk(state, &chunk);
+        }
+        if let Ok(mut recent) = state.recent_chunks.lock() {
+            recent.insert(chunk.seq, chunk.clone());
+            while recent.len() > MAX_RECENT_CHUNKS {
+                recent.pop_first();
+            }
+        }
+        let linked = crate::backend::is_linked(state);
+        if state.relay_to_frontend.load(Ordering::SeqCst) {
+            outgoing = linked.then(|| chunk.clone());
+            push_with_backpressure(state, &mut chunks, chunk);
+        } else {
+            outgoing = linked.then_some(chunk);
+        }
+    }
+    // Outside the queue lock, so polls never wait on the backend hand-off
+    if let Some(chunk) = outgoing {
+        crate::backend::forward(state, chunk);
+    }
+    if let Some(marker) = turn_end {
+        emit_chunk(state, marker);
+    }
+}
+
+/// Zero-length "turn-end" marker for the utterance `last` ended. It goes
+/// through `emit_chunk` like audio, so it gets its own seq and timestamp.
+fn turn_end_marker(last: &AudioChunk, settings: &AudioSettings) -> AudioChunk {
+    let mut marker = encode_chunk(&[], last.sample_rate, &last.source, &last.device_name, &last.layout, settings);
+    marker.utterance_id = last.utterance_id.clone();
+    marker.contains_speech = false;
+    marker.marker = Some("turn-end".into());
+    marker
+}
+
+/// Ends `source`'s current turn on demand: flushes any utterance in
+/// progress ("mic", "loopback" or "mixed") and emits a "turn-end" marker
+/// after it, whether or not `turn_end_markers` is on (with it on, the
+/// flushed utterance already brings its own).
+pub fn mark_turn_end(state: &AudioCaptureState, source: &str) -> Result<(), String> {
+    let settings = state.settings.lock().map(|s| s.clone()).unwrap_or_default();
+    let flush = |vad: &mut VadAccumulator| {
+        let chunks = vad.force_flush(&settings);
+        let marker = encode_chunk(&[], vad.sample_rate, &vad.source_label, &vad.device_name, vad.layout, &settings);
+        (chunks, marker)
+    };
+    let (chunks, mut marker) = match source {
+        "mixed" => state
+            .mixer
+            .lock()
+            .map(|mut mixer| flush(&mut mixer.vad))
+            .map_err(|_| "Mixer is unavailable".to_string())?,
+        _ => state
+            .accumulator(source)
+            .ok_or_else(|| format!("Unknown source '{}' (expected \"mic\", \"loopback\" or \"mixed\")", source))?
+            .lock()
+            .map(|mut vad| flush(&mut vad))
+            .map_err(|_| format!("{} accumulator is unavailable", source))?,
+    };
+    let automatic = settings.turn_end_markers && !settings.continuous_record;
+    let flushed = !chunks.is_empty();
+    if let Some(last) = chunks.last() {
+        marker.utterance_id = last.utterance_id.clone();
+    }
+    for chunk in chunks {
+        emit_chunk(state, chunk);
+    }
+    if !(automatic && flushed) {
+        marker.contains_speech = false;
+        marker.marker = Some("turn-end".into());
+        emit_chunk(state, marker);
+    }
+    Ok(())
+}
+
+/// Queues a chunk, applying the backpressure policy if the queue is full.
+fn push_with_backpressure(state: &AudioCaptureState, chunks: &mut Vec<AudioChunk>, chunk: AudioChunk) {
+    let policy = state
+        .backpressure
+        .lock()
+        .map(|p| *p)
+        .unwrap_or(BackpressurePolicy::Buffer);
+    if chunks.len() < MAX_QUEUED_CHUNKS || policy == BackpressurePolicy::Buffer {
+        chunks.push(chunk);
+        return;
+    }
+
+    if policy == BackpressurePolicy::Coalesce {
+        // Never coalesce past the size limit that split the chunks
+        let max_b64 = state
+            .settings
+            .lock()
+            .map(|s| s.max_chunk_b64_bytes)
+            .unwrap_or(DEFAULT_MAX_CHUNK_B64_BYTES);
+        let target = chunks.iter_mut().rev().find(|c| {
+            c.source == chunk.source
+                && c.sample_rate == chunk.sample_rate
+                && c.sample_type == chunk.sample_type
+                && c.container == chunk.container
+                && c.marker.is_none()
+                && chunk.marker.is_none()
+                && c.audio_b64.len() + chunk.audio_b64.len() <= max_b64
+        });
+        if let Some(target) = target {
+            match append_chunk(target, &chunk) {
+                Ok(()) => {
+                    state.coalesced_chunks.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                Err(e) => log::warn!("Failed to coalesce chunk {}: {}", chunk.seq, e),
+            }
+        }
+    }
+
+    let dropped = chunks.remove(0);
+    log::warn!("Chunk queue full, dropped chunk {}", dropped.seq);
+    state.dropped_chunks.fetch_add(1, Ordering::Relaxed);
+    chunks.push(chunk);
+}
+
+/// Appends `next`'s audio (same source, rate, sample type and container)
+/// onto `target`, which keeps its own seq and timing.
+fn append_chunk(target: &mut AudioChunk, next: &AudioChunk) -> Result<(), String> {
+    let engine = base64::engine::general_purpose::STANDARD;
+    let container = Container::parse(&target.container)?;
+    let mut bytes = engine.decode(&target.audio_b64).map_err(|e| e.to_string())?;
+    let next_bytes = engine.decode(&next.audio_b64).map_err(|e| e.to_string())?;
+    bytes.extend_from_slice(next_bytes.get(container.header_len()..).unwrap_or_default());
+    if container == Container::Wav {
+        let sample_type = SampleType::parse(&target.sample_type)?;
+        let header = wav_header(target.sample_rate, sample_type, bytes.len().saturating_sub(WAV_HEADER_LEN));
+        bytes[..WAV_HEADER_LEN].copy_from_slice(&header);
+    }
+    target.audio_b64 = engine.encode(&bytes);
+    target.sample_count += next.sample_count;
+    target.duration_secs += next.duration_secs;
+    target.energy_trace.extend_from_slice(&next.energy_trace);
+    Ok(())
+}
+
+fn record_talk(state: &AudioCaptureState, chunk: &AudioChunk) {
+    let record = TalkRecord {
+        seq: chunk.seq,
+        source: chunk.source.clone(),
+        start_ts_ms: chunk.onset_ms,
+        duration_secs: chunk.duration_secs,
+    };
+    if let Ok(mut history) = state.talk_history.lock() {
+        if history.len() >= MAX_TALK_HISTORY {
+            history.pop_front();
+        }
+        history.push_back(record);
+    }
+}
+
+/// Ways of capturing that feed the shared accumulators or hold a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SessionKind {
+    Devices,
+    FileSource,
+    Clip,
+}
+
+impl SessionKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Devices => "device capture",
+            Self::FileSource => "file source",
+            Self::Clip => "clip recording",
+        }
+    }
+}
+
+/// Claim on the single capture slot, from `claim_session`. Released on
+/// drop unless `keep` hands it to the running session, which gives it back
+/// in `stop_capture`.
+struct SessionClaim<'a> {
+    state: &'a AudioCaptureState,
+    kind: SessionKind,
+    kept: bool,
+}
+
+impl SessionClaim<'_> {
+    fn keep(mut self) {
+        self.kept = true;
+    }
+}
+
+impl Drop for SessionClaim<'_> {
+    fn drop(&mut self) {
+        if !self.kept {
+            release_session(self.state, self.kind);
+        }
+    }
+}
+
+/// Takes the capture slot for `kind`, or fails with an "Already running"
+/// error naming what holds it. Every start path goes through this, so two
+/// starts can't race onto the same devices or accumulators.
+fn claim_session(state: &AudioCaptureState, kind: SessionKind) -> Result<SessionClaim<'_>, String> {
+    let mut active = state
+        .active_session
+        .lock()
+        .map_err(|_| "Capture state is unavailable".to_string())?;
+    if let Some(current) = *active {
+        return Err(format!("Already running: {} is active", current.as_str()));
+    }
+    *active = Some(kind);
+    Ok(SessionClaim {
+        state,
+        kind,
+        kept: false,
+    })
+}
+
+/// Takes over the slot a `PreparedCapture` kept for `kind`, as a claim
+/// that is released again if the start fails. Errors if the slot was
+/// given up in the meantime (a stop).
+fn adopt_session(state: &AudioCaptureState, kind: SessionKind) -> Result<SessionClaim<'_>, String> {
+    let active = state
+        .active_session
+        .lock()
+        .map_err(|_| "Capture state is unavailable".to_string())?;
+    if *active != Some(kind) {
+        return Err("Capture start was cancelled".into());
+    }
+    Ok(SessionClaim {
+        state,
+        kind,
+        kept: false,
+    })
+}
+
+fn release_session(state: &AudioCaptureState, kind: SessionKind) {
+    if let Ok(mut active) = state.active_session.lock() {
+        if *active == Some(kind) {
+            *active = None;
+        }
+    }
+}
+
+/// Routes one callback's worth of mono audio at the target rate either to the source's
+/// own accumulator or, in mixed mode, to the shared mixer.
+fn process_frame(state: &AudioCaptureState, source: &str, mono_16k: &mut [f32]) {
+    let mode = state
+        .capture_mode
+        .lock()
+        .map(|m| *m)
+        .unwrap_or(CaptureMode::PerSource);
+
+    let Some(settings) = state.settings.snapshot() else {
+        return;
+    };
+    let generation = state.generation.load(Ordering::SeqCst);
+
+    feed_raw_tap(state, source, mono_16k);
+
+    echo_cancel(state, source, mono_16k, &settings);
+    let mono_16k = &*mono_16k;
+
+    if settings.energy_events && !mono_16k.is_empty() {
+        maybe_emit_energy(state, source, mono_16k, &settings);
+    }
+
+    let chunks = match mode {
+        // The accumulator's label, not the stream's, decides the role: they
+        // differ after `swap_sources`
+        CaptureMode::PerSource => state.accumulator(source).and_then(|vad| vad.lock().ok()).map(|mut v| {
+            v.barge_in_active = v.source_label == "mic"
+                && settings.barge_in_suppression
+                && state.loopback_speaking.load(Ordering::Relaxed);
+            v.ptt = (settings.ptt_mode && v.source_label == "mic").then(|| state.ptt_active.load(Ordering::Relaxed));
+            let chunk = v.feed(mono_16k, &settings);
+            if let Some(flag) = state.speaking_flag(&v.source_label) {
+                flag.store(v.is_speaking, Ordering::Relaxed);
+            }
+            chunk
+        }),
+        // The mix can't tell who is talking, so both flags follow the mixed VAD
+        CaptureMode::MixedMono => state.mixer.lock().ok().map(|mut mixer| {
+            mixer.vad.ptt = settings.ptt_mode.then(|| state.ptt_active.load(Ordering::Relaxed));
+            let chunk = mixer.push(source, mono_16k, &settings);
+            state.mic_speaking.store(mixer.vad.is_speaking, Ordering::Relaxed);
+            state.loopback_speaking.store(mixer.vad.is_speaking, Ordering::Relaxed);
+            chunk
+        }),
+    };
+    let now = state.epoch.elapsed().as_nanos() as u64;
+    if state.mic_speaking.load(Ordering::Relaxed) || state.loopback_speaking.load(Ordering::Relaxed) {
+        state.last_speaking_nanos.store(now, Ordering::Relaxed);
+    }
+    if settings.wake_trigger && source == "mic" {
+        let speaking = state.mic_speaking.load(Ordering::Relaxed);
+        let wake = state
+            .wake
+            .lock()
+            .ok()
+            .and_then(|mut w| w.update(speaking, now, settings.wake_speech_ms));
+        if let Some(wake) = wake {
+            emit_event(state, "wake", wake);
+        }
+    }
+
+    if state.generation.load(Ordering::SeqCst) == generation {
+        for chunk in chunks.unwrap_or_default() {
+            emit_chunk(state, chunk);
+        }
+    }
+}
+
+/// Echo cancellation step of `process_frame`: loopback frames become the
+/// reference, mic frames are cleaned in place. Other frames are left as is
+/// (disabled, or the two sources at different rates).
+fn echo_cancel(state: &AudioCaptureState, source: &str, frame: &mut [f32], settings: &AudioSettings) {
+    if !settings.echo_cancellation || settings.mic_target_rate != settings.loopback_target_rate {
+        return;
+    }
+    let Ok(mut aec) = state.echo_canceller.lock() else {
+        return;
+    };
+    aec.configure(settings.echo_tail_ms, settings.mic_target_rate);
+    match source {
+        "loopback" => aec.push_reference(frame),
+        "mic" => aec.cancel(frame),
+        _ => {}
+    }
+}
+
+/// Payload of the `"wake"` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct Wake {
+    /// Mic speech so far in the utterance that fired it
+    pub speech_ms: u32,
+    /// Silence before that utterance; `None` if it's the first this session
+    pub silence_ms: Option<u64>,
+}
+
+/// Energy-based activation: fires once per utterance when the mic VAD has
+/// been speaking for the required time after a long enough silence. Works
+/// on the VAD's speaking flag, so onset and hangover are the VAD's.
+#[derive(Default)]
+struct WakeTrigger {
+    /// When the current mic utterance started, in nanos after `epoch`
+    speech_start: Option<u64>,
+    /// When the last utterance ended; `None` until one has
+    silence_start: Option<u64>,
+    /// Silence before the current utterance, if it was long enough to arm
+    /// the trigger; `Some(None)` for the first utterance of a session
+    armed: Option<Option<u64>>,
+}
+
+impl WakeTrigger {
+    fn update(&mut self, speaking: bool, now: u64, speech_ms: u32) -> Option<Wake> {
+        if !speaking {
+            if self.speech_start.take().is_some() {
+                self.silence_start = Some(now);
+            }
+            self.armed = None;
+            return None;
+        }
+        let start = *self.speech_start.get_or_insert_with(|| {
+            let silence_ms = self.silence_start.map(|s| now.saturating_sub(s) / 1_000_000);
+            if silence_ms.map_or(true, |ms| ms >= WAKE_MIN_SILENCE_MS) {
+                self.armed = Some(silence_ms);
+            }
+            now
+        });
+        let spoken_ms = now.saturating_sub(start) / 1_000_000;
+        if spoken_ms < speech_ms as u64 {
+            return None;
+        }
+        self.armed.take().map(|silence_ms| Wake {
+            speech_ms: spoken_ms as u32,
+            silence_ms,
+        })
+    }
+}
+
+/// Rates a renegotiated device is snapped to when its delivery rate drifts.
+const STANDARD_SAMPLE_RATES: [u32; 9] = [8000, 11025, 16000, 22050, 32000, 44100, 48000, 88200, 96000];
+/// How much audio to count before comparing delivered vs configured rate.
+const RATE_CHECK_WINDOW: std::time::Duration = std::time::Duration::from_secs(2);
+/// Relative deviation that counts as a rate change rather than clock jitter.
+const RATE_CHANGE_TOLERANCE: f64 = 0.05;
+/// A callback arriving later than this many buffer lengths after the last
+/// one means the stream stalled (loopback silence, a pause), not that the
+/// rate changed: the window restarts instead of counting the gap.
+const RATE_STALL_BUFFERS: f64 = 4.0;
+
+/// Payload of the `"sample-rate-changed"` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct SampleRateChange {
+    pub source: String,
+    pub previous_hz: u32,
+    pub detected_hz: u32,
+}
+
+/// Estimates the rate a stream actually delivers from callback timestamps,
+/// to catch devices that renegotiate their format mid-session (common on
+/// virtual loopback sources) while cpal keeps reporting the original config.
+#[derive(Default)]
+struct RateMonitor {
+    window_start: Option<cpal::StreamInstant>,
+    last_callback: Option<cpal::StreamInstant>,
+    frames: u64,
+    /// Rate the previous window pointed at; a switch needs two in a row
+    pending: Option<u32>,
+}
+
+impl RateMonitor {
+    /// Counts `frames` delivered at this callback. Once per window, returns
+    /// the new standard rate if delivery no longer matches `current` and the
+    /// previous window agreed.
+    fn observe(&mut self, info: &cpal::InputCallbackInfo, frames: usize, current: u32) -> Option<u32> {
+        let now = info.timestamp().callback;
+        let stall = std::time::Duration::from_secs_f64(RATE_STALL_BUFFERS * frames as f64 / current.max(1) as f64);
+        let since_last = self.last_callback.and_then(|last| now.duration_since(&last));
+        self.last_callback = Some(now);
+        let (Some(start), Some(since_last)) = (self.window_start, since_last) else {
+            self.restart_window(now);
+            return None;
+        };
+        if since_last > stall {
+            self.restart_window(now);
+            return None;
+        }
+        self.frames += frames as u64;
+
+        let Some(elapsed) = now.duration_since(&start) else {
+            self.restart_window(now);
+            return None;
+        };
+        if elapsed < RATE_CHECK_WINDOW {
+            return None;
+        }
+        let delivered = self.frames as f64 / elapsed.as_secs_f64();
+        self.restart_window(now);
+
+        if ((delivered - current as f64) / current as f64).abs() <= RATE_CHANGE_TOLERANCE {
+            self.pending = None;
+            return None;
+        }
+        let snapped = *STANDARD_SAMPLE_RATES
+            .iter()
+            .min_by_key(|&&rate| (rate as f64 - delivered).abs() as u64)?;
+        if snapped == current || self.pending.replace(snapped) != Some(snapped) {
+            return None;
+        }
+        self.pending = None;
+        Some(snapped)
+    }
+
+    fn restart_window(&mut self, now: cpal::StreamInstant) {
+        self.window_start = Some(now);
+        self.frames = 0;
+    }
+
+    /// Forgets the open window, for callbacks whose audio isn't processed:
+    /// the next one starts a fresh window rather than spanning the gap.
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Gap in capture timestamps beyond the audio the previous callback carried
+/// that counts as an overrun (frames the driver dropped), in buffer lengths.
+/// Anything shorter is timestamp jitter.
+const XRUN_MIN_GAP_BUFFERS: f64 = 0.5;
+/// Longer gaps are a stream that stopped delivering (loopback silence on
+/// WASAPI, a suspended device), not frames a driver dropped
+const XRUN_MAX_GAP: std::time::Duration = std::time::Duration::from_millis(500);
+/// Overruns within `XRUN_SPIKE_WINDOW` that trigger an `"xrun-spike"` event
+const XRUN_SPIKE_COUNT: u64 = 5;
+const XRUN_SPIKE_WINDOW: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Overrun counters for one source, updated from its data callback.
+#[derive(Default)]
+struct XrunCounters {
+    xruns: AtomicU64,
+    dropped_frames: AtomicU64,
+}
+
+/// Per-source overrun totals returned by `get_xrun_stats`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceXruns {
+    pub xruns: u64,
+    /// Estimated from the timestamp gaps, at the device rate
+    pub dropped_frames: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct XrunStats {
+    pub mic: SourceXruns,
+    pub loopback: SourceXruns,
+}
+
+/// Payload of the `"xrun-spike"` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct XrunSpike {
+    pub source: String,
+    /// Overruns in the last window
+    pub xruns: u64,
+    pub window_secs: f32,
+}
+
+/// Detects overruns from capture timestamps. cpal doesn't report them, but a
+/// buffer whose first frame was captured later than the end of the previous
+/// buffer means frames were lost in between.
+#[derive(Default)]
+struct XrunDetector {
+    /// Capture time the next buffer should start at
+    expected: Option<cpal::StreamInstant>,
+    window_start: Option<Instant>,
+    window_xruns: u64,
+}
+
+impl XrunDetector {
+    /// Returns the estimated frames dropped before this buffer, if any.
+    fn observe(&mut self, info: &cpal::InputCallbackInfo, frames: usize, sample_rate: u32) -> Option<u64> {
+        let capture = info.timestamp().capture;
+        let buffer = std::time::Duration::from_secs_f64(frames as f64 / sample_rate as f64);
+        let gap = self.expected.and_then(|expected| capture.duration_since(&expected));
+        self.expected = capture.add(buffer);
+        overrun_frames(gap?, buffer, sample_rate)
+    }
+
+    /// Forgets the expected start, for callbacks whose audio isn't
+    /// processed: the gap they leave isn't an overrun.
+    fn reset(&mut self) {
+        self.expected = None;
+    }
+
+    /// Counts an overrun toward the spike window; returns the window's count
+    /// when it crosses `XRUN_SPIKE_COUNT`.
+    fn spike(&mut self) -> Option<u64> {
+        let now = Instant::now();
+        if self.window_start.map_or(true, |start| now.duration_since(start) > XRUN_SPIKE_WINDOW) {
+            self.window_start = Some(now);
+            self.window_xruns = 0;
+        }
+        self.window_xruns += 1;
+        (self.window_xruns == XRUN_SPIKE_COUNT).then_some(self.window_xruns)
+    }
+}
+
+/// Frames dropped across a `gap` in capture timestamps after a `buffer`-long
+/// callback, or `None` if the gap is jitter or a stall rather than an
+/// overrun.
+fn overrun_frames(gap: std::time::Duration, buffer: std::time::Duration, sample_rate: u32) -> Option<u64> {
+    if gap <= buffer.mul_f64(XRUN_MIN_GAP_BUFFERS) || gap > XRUN_MAX_GAP {
+        return None;
+    }
+    Some((gap.as_secs_f64() * sample_rate as f64) as u64)
+}
+
+/// Per-stream state owned by the data callback: the device format, reusable
+/// conversion buffers, and rate-change detection.
+struct StreamProcessor {
+    state: Arc<AudioCaptureState>,
+    label: String,
+    channels: u16,
+    sample_rate: u32,
+    scratch: ConversionScratch,
+    rate_monitor: RateMonitor,
+    xrun_detector: XrunDetector,
+    silent_frames: usize, // consecutive near-silent device frames
+    suspended: bool,      // skipping conversion until energy returns
+    anti_phase_callbacks: usize, // consecutive callbacks with inverted L/R
+    channel_check_countdown: usize, // callbacks until the next fake-stereo check
+    priority_checked: bool, // realtime priority attempted on this callback thread
+    generation: u64,      // mic only: feeds while equal to `mic_live_generation`
+}
+
+impl StreamProcessor {
+    fn new(state: Arc<AudioCaptureState>, label: &str, config: &StreamConfig) -> Self {
+        if let Some(Ok(mut stats)) = state.channel_stats(label).map(|s| s.lock()) {
+            *stats = ChannelStats {
+                channels: config.channels,
+                effective_channels: (config.channels == 1).then_some(1),
+            };
+        }
+        let generation = match label {
+            "mic" => state.mic_build_generation.load(Ordering::SeqCst),
+            _ => 0,
+        };
+        Self {
+            state,
+            label: label.to_string(),
+            channels: config.channels,
+            sample_rate: config.sample_rate.0,
+            scratch: ConversionScratch::default(),
+            rate_monitor: RateMonitor::default(),
+            xrun_detector: XrunDetector::default(),
+            silent_frames: 0,
+            suspended: false,
+            anti_phase_callbacks: 0,
+            channel_check_countdown: 0,
+            priority_checked: false,
+            generation,
+        }
+    }
+
+    fn on_f32(&mut self, data: &[f32], info: &cpal::InputCallbackInfo) {
+        if !self.begin(data.len(), info) || data.is_empty() {
+            return;
+        }
+        if self.idle(frame_rms(data), data.len()) {
+            return;
+        }
+        if self.scratch.downmix == Downmix::Average {
+            self.check_phase(channel_correlation(data, self.channels, |s| s));
+        }
+        if self.channel_check_due() {
+            self.record_channels(distinct_channels(data, self.channels, |s| s));
+        }
+        let target_rate = self.target_rate();
+        let gain = self.downmix_gain();
+        let mono = self.scratch.convert_f32(data, self.channels, self.sample_rate, target_rate, gain);
+        process_frame(&self.state, &self.label, mono);
+    }
+
+    fn on_i16(&mut self, data: &[i16], info: &cpal::InputCallbackInfo) {
+        if !self.begin(data.len(), info) || data.is_empty() {
+            return;
+        }
+        let sum_sq: f32 = data.iter().map(|&s| (s as f32 / i16::MAX as f32).powi(2)).sum();
+        if self.idle((sum_sq / data.len() as f32).sqrt(), data.len()) {
+            return;
+        }
+        if self.scratch.downmix == Downmix::Average {
+            self.check_phase(channel_correlation(data, self.channels, |s| s as f32 / i16::MAX as f32));
+        }
+        if self.channel_check_due() {
+            self.record_channels(distinct_channels(data, self.channels, |s| s as f32 / i16::MAX as f32));
+        }
+        let target_rate = self.target_rate();
+        let gain = self.downmix_gain();
+        let mono = self.scratch.convert_i16(data, self.channels, self.sample_rate, target_rate, gain);
+        process_frame(&self.state, &self.label, mono);
+    }
+
+    /// Asks the OS to run this callback thread at maximum priority, if
+    /// enabled. cpal owns the thread, so this can only happen from inside
+    /// the callback, once. A denial is logged and capture carries on.
+    fn raise_priority(&self) {
+        if !self.state.settings.lock().is_ok_and(|s| s.realtime_priority) {
+            return;
+        }
+        match thread_priority::set_current_thread_priority(thread_priority::ThreadPriority::Max) {
+            Ok(()) => log::info!("{} callback thread raised to maximum priority", self.label),
+            Err(e) => log::warn!(
+                "{} callback thread priority not raised ({:?}); continuing at normal priority",
+                self.label,
+                e
+            ),
+        }
+    }
+
+    /// Low duty cycle for the fake-stereo check: true once every
+    /// `CHANNEL_CHECK_INTERVAL` callbacks on multi-channel streams.
+    fn channel_check_due(&mut self) -> bool {
+        if self.channels < 2 {
+            return false;
+        }
+        if self.channel_check_countdown > 0 {
+            self.channel_check_countdown -= 1;
+            return false;
+        }
+        self.channel_check_countdown = CHANNEL_CHECK_INTERVAL;
+        true
+    }
+
+    fn record_channels(&mut self, effective: Option<u16>) {
+        let Some(effective) = effective else { return };
+        let Some(Ok(mut stats)) = self.state.channel_stats(&self.label).map(|s| s.lock()) else {
+            return;
+        };
+        if stats.effective_channels != Some(effective) && effective < self.channels {
+            log::info!(
+                "{} device reports {} channels but only {} differ (duplicated mono?)",
+                self.label,
+                self.channels,
+                effective
+            );
+        }
+        stats.channels = self.channels;
+        stats.effective_channels = Some(effective);
+    }
+
+    /// Switches the downmix to the first channel once the channels have been
+    /// anti-correlated for `PHASE_CHECK_CALLBACKS` callbacks in a row, and
+    /// reports it once. A stream never switches back.
+    fn check_phase(&mut self, correlation: Option<f32>) {
+        let Some(correlation) = correlation else { return };
+        if correlation > PHASE_ANTI_CORRELATION {
+            self.anti_phase_callbacks = 0;
+            return;
+        }
+        self.anti_phase_callbacks += 1;
+        if self.anti_phase_callbacks < PHASE_CHECK_CALLBACKS {
+            return;
+        }
+        if !self.state.settings.lock().is_ok_and(|s| s.phase_autocorrect) {
+            return;
+        }
+
+        self.scratch.downmix = Downmix::FirstChannel;
+        log::warn!(
+            "{} channels are phase-inverted (correlation {:.2}); downmixing from the first channel",
+            self.label,
+            correlation
+        );
+        emit_event(
+            &self.state,
+            "phase-inversion-detected",
+            PhaseInversion {
+                source: self.label.clone(),
+                correlation,
+                downmix: "first-channel".into(),
+            },
+        );
+    }
+
+    /// Rate to resample to: the source's own target, except in mixed mode
+    /// where both sides must line up at `TARGET_SAMPLE_RATE`.
+    fn target_rate(&self) -> u32 {
+        let mixed = self
+            .state
+            .capture_mode
+            .lock()
+            .is_ok_and(|m| *m == CaptureMode::MixedMono);
+        if mixed {
+            return TARGET_SAMPLE_RATE;
+        }
+        self.state
+            .settings
+            .lock()
+            .map(|s| s.target_rate(&self.label))
+            .unwrap_or(TARGET_SAMPLE_RATE)
+    }
+
+    fn downmix_gain(&self) -> f32 {
+        self.state
+            .settings
+            .lock()
+            .map(|s| s.downmix_gain(self.channels))
+            .unwrap_or(1.0)
+    }
+
+    /// Per-callback bookkeeping shared by every sample format. Returns false
+    /// if the audio should be ignored.
+    fn begin(&mut self, samples: usize, info: &cpal::InputCallbackInfo) -> bool {
+        // Stamped before the running check: liveness is about the device
+        if let Some(last) = self.state.last_callback(&self.label) {
+            last.store(self.state.epoch.elapsed().as_nanos().max(1) as u64, Ordering::Relaxed);
+        }
+        let skipped = !self.state.running.load(Ordering::Relaxed)
+            || self.state.privacy_paused.load(Ordering::Relaxed)
+            || (self.label == "mic" && self.generation != self.state.mic_live_generation.load(Ordering::SeqCst));
+        if skipped {
+            self.rate_monitor.reset();
+            self.xrun_detector.reset();
+            return false;
+        }
+        if !self.priority_checked {
+            self.priority_checked = true;
+            self.raise_priority();
+        }
+
+        let frames = samples / self.channels.max(1) as usize;
+        if let Some(callback_us) = self.state.callback_us(&self.label) {
+            callback_us.store(frames as u64 * 1_000_000 / self.sample_rate.max(1) as u64, Ordering::Relaxed);
+        }
+        if let Some(detected) = self.rate_monitor.observe(info, frames, self.sample_rate) {
+            log::warn!(
+                "{} stream rate changed: configured {}Hz, delivering ~{}Hz; resampling from the new rate",
+                self.label,
+                self.sample_rate,
+                detected
+            );
+            emit_event(
+                &self.state,
+                "sample-rate-changed",
+                SampleRateChange {
+                    source: self.label.clone(),
+                    previous_hz: self.sample_rate,
+                    detected_hz: detected,
+                },
+            );
+            self.sample_rate = detected;
+        }
+
+        if let Some(dropped) = self.xrun_detector.observe(info, frames, self.sample_rate) {
+            if let Some(counters) = self.state.xrun_counters(&self.label) {
+                counters.xruns.fetch_add(1, Ordering::Relaxed);
+                counters.dropped_frames.fetch_add(dropped, Ordering::Relaxed);
+            }
+            if let Some(xruns) = self.xrun_detector.spike() {
+                log::warn!(
+                    "{} stream overran {} times in {}s; the callback can't keep up",
+                    self.label,
+                    xruns,
+                    XRUN_SPIKE_WINDOW.as_secs()
+                );
+                emit_event(
+                    &self.state,
+                    "xrun-spike",
+                    XrunSpike {
+                        source: self.label.clone(),
+                        xruns,
+                        window_secs: XRUN_SPIKE_WINDOW.as_secs_f32(),
+                    },
+                );
+            }
+        }
+        true
+    }
+
+    /// Suspend-on-silence: returns true while the stream is suspended and
+    /// this callback should skip conversion. The check is one pass over the
+    /// raw buffer, versus downmix + resample + VAD for a processed frame.
+    /// Energy events pause with it.
+    fn idle(&mut self, raw_rms: f32, samples: usize) -> bool {
+        let (suspend_after_ms, ptt_mode, continuous) = self
+            .state
+            .settings
+            .lock()
+            .map(|s| (s.suspend_after_ms, s.ptt_mode, s.continuous_record))
+            .unwrap_or((0, false, false));
+        // Push-to-talk must hear the key press however quiet the room is,
+        // and continuous recording keeps the silence too
+        let ptt_governed = ptt_mode && self.label == "mic";
+        let tapped = self
+            .state
+            .raw_tap
+            .lock()
+            .is_ok_and(|tap| tap.as_ref().is_some_and(|t| t.source == self.label));
+        if suspend_after_ms == 0 || raw_rms > SUSPEND_WAKE_RMS || ptt_governed || continuous || tapped {
+            if self.suspended {
+                log::debug!("{} stream resumed processing", self.label);
+            }
+            self.silent_frames = 0;
+            self.suspended = false;
+            return false;
+        }
+        if self.suspended {
+            // Keep the accumulator's clock moving so gaps stay accurate
+            if let Some(Ok(mut vad)) = self.state.accumulator(&self.label).map(|v| v.lock()) {
+                let skipped = samples / self.channels.max(1) as usize * vad.sample_rate as usize
+                    / self.sample_rate.max(1) as usize;
+                vad.samples_seen = vad.samples_seen.saturating_add(skipped);
+            }
+            return true;
+        }
+
+        self.silent_frames += samples / self.channels.max(1) as usize;
+        // Never suspend mid-utterance: the VAD still needs silence to end it
+        let speaking = self
+            .state
+            .speaking_flag(&self.label)
+            .is_some_and(|flag| flag.load(Ordering::Relaxed));
+        let silent_ms = self.silent_frames as u64 * 1000 / self.sample_rate.max(1) as u64;
+        if !speaking && silent_ms >= suspend_after_ms as u64 {
+            log::debug!("{} stream suspended after {}ms of silence", self.label, silent_ms);
+            self.suspended = true;
+        }
+        false
+    }
+}
+
+/// A device stream format requested by the user, e.g. 16 kHz mono on a mic
+/// that supports it natively so no resampling is needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreferredInputConfig {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/// Finds an advertised config of `device` matching `preferred`, preferring
+/// f32 over i16 samples. Other sample formats can't be captured.
+fn find_supported_config(
+    device: &cpal::Device,
+    preferred: PreferredInputConfig,
+) -> Result<Option<cpal::SupportedStreamConfig>, String> {
+    let ranges = device
+        .supported_input_configs()
+        .map_err(|e| format!("Couldn't query supported configs: {}", e))?;
+    let rate = cpal::SampleRate(preferred.sample_rate);
+    let mut matches: Vec<_> = ranges
+        .filter(|r| r.channels() == preferred.channels)
+        .filter(|r| (r.min_sample_rate()..=r.max_sample_rate()).contains(&rate))
+        .filter(|r| matches!(r.sample_format(), SampleFormat::F32 | SampleFormat::I16))
+        .collect();
+    matches.sort_by_key(|r| r.sample_format() != SampleFormat::F32);
+    Ok(matches.into_iter().next().map(|r| r.with_sample_rate(rate)))
+}
+
+/// Rejects configs some broken virtual drivers report (zero channels or a
+/// zero sample rate) before a stream is built on them.
+fn check_stream_config(config: &StreamConfig, source_label: &str) -> Result<(), String> {
+    if config.channels == 0 || config.sample_rate.0 == 0 {
+        return Err(format!(
+            "{} device reports an unusable config ({}ch @ {}Hz)",
+            source_label, config.channels, config.sample_rate.0
+        ));
+    }
+    Ok(())
+}
+
+/// Builds a cpal input stream for a given device.
+/// `source_label` is "mic" or "loopback".
+/// Captured audio is VAD-sliced and pushed into `state.chunks`.
+fn build_capture_stream(
+    device: &cpal::Device,
+    source_label: &str,
+    state: Arc<AudioCaptureState>,
+) -> Result<(cpal::Stream, StreamConfig), String> {
+    let preferred = match source_label {
+        "mic" => state.settings.lock().ok().and_then(|s| s.preferred_input_config),
+        _ => None,
+    };
+    let matched = preferred.and_then(|p| match find_supported_config(device, p) {
+        Ok(Some(config)) => Some(config),
+        Ok(None) => {
+            log::warn!(
+                "{} device doesn't support {}ch @ {}Hz; using the default config",
+                source_label,
+                p.channels,
+                p.sample_rate
+            );
+            None
+        }
+        Err(e) => {
+            log::warn!("{} device: {}; using the default config", source_label, e);
+            None
+        }
+    });
+    let supported = match matched {
+        Some(config) => config,
+        None => device
+            .default_input_config()
+            .map_err(|e| format!("No default config for {}: {}", source_label, e))?,
+    };
+
+    let sample_format = supported.sample_format();
+    let buffer_range = *supported.buffer_size();
+    let mut config: StreamConfig = supported.into();
+    check_stream_config(&config, source_label)?;
+
+    let requested = state.settings.lock().ok().and_then(|s| s.buffer_frames);
+    if let Some(frames) = requested {
+        match buffer_range {
+            SupportedBufferSize::Range { min, max } if !(min..=max).contains(&frames) => log::warn!(
+                "{} device supports {}..={} frame buffers, not {}; using the default",
+                source_label,
+                min,
+                max,
+                frames
+            ),
+            _ => config.buffer_size = BufferSize::Fixed(frames),
+        }
+    }
+
+    let stream = match open_input_stream(device, &config, sample_format, source_label, state.clone()) {
+        Err(e) if config.buffer_size != BufferSize::Default => {
+            log::warn!(
+                "{} device rejected a fixed buffer ({}); retrying with the default",
+                source_label,
+                e
+            );
+            config.buffer_size = BufferSize::Default;
+            open_input_stream(device, &config, sample_format, source_label, state)?
+        }
+        result => result?,
+    };
+
+    Ok((stream, config))
+}
+
+fn open_input_stream(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    sample_format: SampleFormat,
+    source_label: &str,
+    state: Arc<AudioCaptureState>,
+) -> Result<cpal::Stream, String> {
+    let mut processor = StreamProcessor::new(state, source_label, config);
+    let label_for_err = source_label.to_string();
+    let on_error = move |err| {
+        log::error!("Audio stream error ({}): {}", label_for_err, err);
+    };
+
+    match sample_format {
+        SampleFormat::F32 => device.build_input_stream(
+            config,
+            move |data: &[f32], info| processor.on_f32(data, info),
+            on_error,
+            None,
+        ),
+        SampleFormat::I16 => device.build_input_stream(
+            config,
+            move |data: &[i16], info| processor.on_i16(data, info),
+            on_error,
+            None,
+        ),
+        _ => {
+            return Err(format!(
+                "Unsupported sample format {:?} for {}",
+                sample_format, source_label
+            ));
+        }
+    }
+    .map_err(|e| format!("Failed to build {} stream: {}", source_label, e))
+}
+
+/// Payload of the `"mic-device-changed"` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct MicDeviceChanged {
+    pub previous: String,
+    pub device: String,
+}
+
+#[cfg(target_os = "macos")]
+pub fn default_input_name() -> Option<String> {
+    cpal::default_host()
+        .default_input_device()
+        .and_then(|d| d.name().ok())
+}
+
+/// First half of a gapless mic switch: builds a stream on the input named
+/// `name` under the next mic generation, so once played it runs without
+/// feeding the accumulator. Follow with `commit_mic_switch` after it plays,
+/// or `abort_mic_switch` if it doesn't.
+pub fn prepare_mic_switch(state: Arc<AudioCaptureState>, name: &str) -> Result<cpal::Stream, String> {
+    if !state.running.load(Ordering::SeqCst) {
+        return Err("Audio capture is not running".into());
+    }
+    let host = cpal::default_host();
+    let device = host
+        .input_devices()
+        .ok()
+        .and_then(|mut devices| devices.find(|d| d.name().is_ok_and(|n| n == name)))
+        .ok_or_else(|| format!("Input device '{}' not found", name))?;
+
+    let live = state.mic_live_generation.load(Ordering::SeqCst);
+    state.mic_build_generation.store(live + 1, Ordering::SeqCst);
+    match build_capture_stream(&device, "mic", state.clone()) {
+        Ok((stream, config)) => {
+            log::info!(
+                "Mic switching to {}: {}ch @ {}Hz",
+                name,
+                config.channels,
+                config.sample_rate.0
+            );
+            Ok(stream)
+        }
+        Err(e) => {
+            abort_mic_switch(&state);
+            Err(e)
+        }
+    }
+}
+
+/// Hands the mic accumulator to the stream from `prepare_mic_switch`. The
+/// old device's utterance is flushed and the generation flipped under the
+/// accumulator lock, so no callback from either stream lands in between
+/// and nothing from the old device mixes into the new one's audio. The old
+/// stream is silent from here on and can be dropped.
+pub fn commit_mic_switch(state: &AudioCaptureState, name: &str) -> MicDeviceChanged {
+    let settings = state.settings.lock().map(|s| s.clone()).unwrap_or_default();
+    let mut flushed = Vec::new();
+    if let Ok(mut vad) = state.mic_vad.lock() {
+        flushed = vad.force_flush(&settings);
+        state
+            .mic_live_generation
+            .store(state.mic_build_generation.load(Ordering::SeqCst), Ordering::SeqCst);
+        vad.device_name = name.to_string();
+    }
+    for chunk in flushed {
+        emit_chunk(state, chunk);
+    }
+
+    let mut previous = String::new();
+    if let Ok(mut last) = state.last_devices.lock() {
+        if let Some(last) = last.as_mut() {
+            previous = std::mem::replace(&mut last.mic, name.to_string());
+        }
+    }
+    MicDeviceChanged {
+        previous,
+        device: name.to_string(),
+    }
+}
+
+/// Abandons a prepared switch; the new stream (if any) is simply dropped.
+pub fn abort_mic_switch(state: &AudioCaptureState) {
+    state
+        .mic_build_generation
+        .store(state.mic_live_generation.load(Ordering::SeqCst), Ordering::SeqCst);
+}
+
+/// Lists all available audio input devices with their names.
+pub fn list_input_devices() -> Vec<String> {
+    let host = cpal::default_host();
+    let mut names = Vec::new();
+    if let Ok(devices) = host.input_devices() {
+        for device in devices {
+            if let Ok(name) = device.name() {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Lists all available audio output devices.
+pub fn list_output_devices() -> Vec<String> {
+    let host = cpal::default_host();
+    let mut names = Vec::new();
+    if let Ok(devices) = host.output_devices() {
+        for device in devices {
+            if let Ok(name) = device.name() {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Devices resolved for a device-capture start, holding the capture slot
+/// until `open` builds the streams on them. Dropped unopened, it gives the
+/// slot back.
+///
+/// On Windows (WASAPI), loopback capture is done by opening the default
+/// output device as an input stream -- WASAPI exposes this automatically.
+/// On macOS, CoreAudio aggregate devices or ScreenCaptureKit are needed;
+/// this implementation captures the default output device which works
+/// with loopback-capable drivers.
+/// On Linux, PipeWire/PulseAudio monitor sources appear as input devices.
+pub struct PreparedCapture {
+    state: Arc<AudioCaptureState>,
+    mic: cpal::Device,
+    loopback: cpal::Device,
+    opened: bool,
+}
+
+impl PreparedCapture {
+    /// Starts dual capture (mic + loopback). Returns the two cpal::Stream
+    /// handles, which must be kept alive for the duration of capture. Fails
+    /// if capture was stopped since `prepare_capture`.
+    pub fn open(mut self) -> Result<(cpal::Stream, cpal::Stream), String> {
+        self.opened = true;
+        let claim = adopt_session(&self.state, SessionKind::Devices)?;
+        open_capture_devices(&self.mic, &self.loopback, self.state.clone(), claim)
+    }
+}
+
+impl Drop for PreparedCapture {
+    fn drop(&mut self) {
+        if !self.opened {
+            release_session(&self.state, SessionKind::Devices);
+        }
+    }
+}
+
+/// First half of a device-capture start: claims the capture slot, then
+/// resolves the mic and loopback devices. With `loopback_fallback` on this
+/// probes the loopback strategies, which blocks for up to
+/// `loopback_probe_ms` per strategy, so callers on the main thread should
+/// run it on the blocking pool and only `open` the result there. No stream
+/// is kept.
+pub fn prepare_capture(state: Arc<AudioCaptureState>) -> Result<PreparedCapture, String> {
+    // Claimed before resolving devices, so a second start can't probe them
+    // concurrently
+    let claim = claim_session(&state, SessionKind::Devices)?;
+    let host = cpal::default_host();
+
+    // ── Microphone (default input device) ──
+    let mic_device = host
+        .default_input_device()
+        .ok_or("No default input (microphone) device found")?;
+
+    // ── Loopback (default output device captured as input) ──
+    //
+    // Platform behavior:
+    //   Windows (WASAPI): default_output_device supports build_input_stream
+    //     for loopback capture natively.
+    //   Linux: PipeWire/PulseAudio monitor sources show up as input devices.
+    //     We try to find a "Monitor" device first, fall back to default output.
+    //   macOS: Requires a loopback driver (e.g., BlackHole) or ScreenCaptureKit.
+    //     We try default output; this works if a loopback driver is installed.
+    let loopback_device = resolve_capture_loopback(&host, &state)?;
+
+    claim.keep();
+    Ok(PreparedCapture {
+        state,
+        mic: mic_device,
+        loopback: loopback_device,
+        opened: false,
+    })
+}
+
+/// The loopback device a start uses: the pinned `loopback_device`, else
+/// the `loopback_fallback` probe's pick, else `find_loopback_device`. The
+/// probe blocks; see `prepare_capture`.
+fn resolve_capture_loopback(host: &cpal::Host, state: &AudioCaptureState) -> Result<cpal::Device, String> {
+    let selected = state.loopback_device.lock().ok().and_then(|s| s.clone());
+    let (fallback, probe_ms) = state
+        .settings
+        .lock()
+        .map(|s| (s.loopback_fallback, s.loopback_probe_ms))
+        .unwrap_or((false, DEFAULT_LOOPBACK_PROBE_MS));
+    match selected {
+        Some(name) => find_device_by_name(host, &name)
+            .ok_or_else(|| format!("Selected loopback device '{}' is no longer available", name)),
+        None if fallback => probe_loopback_strategies(host, state, probe_ms),
+        None => find_loopback_device(host).ok_or_else(|| LOOPBACK_NOT_FOUND.to_string()),
+    }
+}
+
+/// A `PreparedCapture` on the previous session's devices, from
+/// `prepare_restart`, with the substitutions it had to make.
+pub struct PreparedRestart {
+    capture: PreparedCapture,
+    substitutions: Vec<String>,
+}
+
+impl PreparedRestart {
+    /// Opens the streams and reports the devices actually in use.
+    pub fn open(self) -> Result<(cpal::Stream, cpal::Stream, RestartReport), String> {
+        let state = self.capture.state.clone();
+        let (mic_stream, loopback_stream) = self.capture.open()?;
+        let devices = state
+            .last_devices
+            .lock()
+            .ok()
+            .and_then(|last| last.clone())
+            .ok_or("Restarted capture recorded no devices")?;
+        Ok((
+            mic_stream,
+            loopback_stream,
+            RestartReport {
+                devices,
+                substitutions: self.substitutions,
+            },
+        ))
+    }
+}
+
+/// Restarts capture on the devices remembered from the previous session
+/// instead of re-resolving the defaults. A remembered device that is no
+/// longer present falls back to what a plain start would pick, and the
+/// substitution is reported so the caller can tell the user. Like
+/// `prepare_capture` this may probe and block; `open` the result on the
+/// main thread.
+pub fn prepare_restart(state: Arc<AudioCaptureState>) -> Result<PreparedRestart, String> {
+    let previous = state
+        .last_devices
+        .lock()
+        .ok()
+        .and_then(|last| last.clone())
+        .ok_or("No previous capture session to restart")?;
+
+    let claim = claim_session(&state, SessionKind::Devices)?;
+    let host = cpal::default_host();
+    let mut substitutions = Vec::new();
+
+    let mic_device = match find_device_by_name(&host, &previous.mic) {
+        Some(device) => device,
+        None => {
+            let device = host
+                .default_input_device()
+                .ok_or("No default input (microphone) device found")?;
+            substitutions.push(format!(
+                "mic: '{}' not found, using default '{}'",
+                previous.mic,
+                device.name().unwrap_or_default()
+            ));
+            device
+        }
+    };
+
+    let loopback_device = match find_device_by_name(&host, &previous.loopback) {
+        Some(device) => device,
+        None => {
+            let device = resolve_capture_loopback(&host, &state)?;
+            substitutions.push(format!(
+                "loopback: '{}' not found, using default '{}'",
+                previous.loopback,
+                device.name().unwrap_or_default()
+            ));
+            device
+        }
+    };
+
+    for note in &substitutions {
+        log::warn!("Restart device substitution -- {}", note);
+    }
+
+    claim.keep();
+    Ok(PreparedRestart {
+        capture: PreparedCapture {
+            state,
+            mic: mic_device,
+            loopback: loopback_device,
+            opened: false,
+        },
+        substitutions,
+    })
+}
+
+const LOOPBACK_NOT_FOUND: &str = "No loopback/monitor audio device found. On Linux, ensure PipeWire or PulseAudio is running. On Windows, WASAPI loopback is used automatically. On macOS, a loopback audio driver is required.";
+
+/// Builds and starts the mic and loopback streams on already-resolved
+/// devices under the caller's `claim`, remembering their names for a later
+/// `restart_capture`.
+fn open_capture_devices(
+    mic_device: &cpal::Device,
+    loopback_device: &cpal::Device,
+    state: Arc<AudioCaptureState>,
+    claim: SessionClaim<'_>,
+) -> Result<(cpal::Stream, cpal::Stream), String> {
+    let mic_name = mic_device.name().unwrap_or_default();
+    let loopback_name = loopback_device.name().unwrap_or_default();
+    let builder = state.clone();
+    start_streams(&state, claim, &mic_name, &loopback_name, |source| {
+        let device = if source == "mic" { mic_device } else { loopback_device };
+        build_capture_stream(device, source, builder.clone())
+    })
+}
+
+/// The body of `open_capture_devices`, with stream building behind `build`
+/// (called with "mic", then "loopback") so a failing device can be
+/// simulated. Capture is only marked running once both streams play.
+fn start_streams<S: StreamTrait>(
+    state: &AudioCaptureState,
+    claim: SessionClaim<'_>,
+    mic_name: &str,
+    loopback_name: &str,
+    mut build: impl FnMut(&str) -> Result<(S, StreamConfig), String>,
+) -> Result<(S, S), String> {
+    log::info!("Mic device: {}", mic_name);
+
+    let (mic_stream, mic_config) = build("mic")?;
+    log::info!(
+        "Mic stream: {}ch @ {}Hz",
+        mic_config.channels,
+        mic_config.sample_rate.0
+    );
+
+    log::info!("Loopback device: {}", loopback_name);
+
+    let (loopback_stream, loopback_config) = build("loopback")?;
+    log::info!(
+        "Loopback stream: {}ch @ {}Hz",
+        loopback_config.channels,
+        loopback_config.sample_rate.0
+    );
+
+    // Start both streams before touching any state: until `running` is set
+    // their callbacks drop audio, and on failure both streams are dropped
+    // on return with `running` still false
+    mic_stream.play().map_err(|e| format!("Mic play failed: {}", e))?;
+    loopback_stream
+        .play()
+        .map_err(|e| format!("Loopback play failed: {}", e))?;
+
+    begin_session(state, mic_name, loopback_name);
+    state.running.store(true, Ordering::SeqCst);
+    claim.keep();
+
+    emit_event(
+        state,
+        "capture-started",
+        CaptureStarted {
+            mic: StreamInfo {
+                device: mic_name.to_string(),
+                sample_rate: mic_config.sample_rate.0,
+                channels: mic_config.channels,
+            },
+            loopback: StreamInfo {
+                device: loopback_name.to_string(),
+                sample_rate: loopback_config.sample_rate.0,
+                channels: loopback_config.channels,
+            },
+        },
+    );
+
+    if let Ok(mut last) = state.last_devices.lock() {
+        *last = Some(DeviceSelection {
+            mic: mic_name.to_string(),
+            loopback: loopback_name.to_string(),
+        });
+    }
+
+    Ok((mic_stream, loopback_stream))
+}
+
+/// Fresh accumulators, mixer and session id for a new capture session fed
+/// from the named devices.
+fn begin_session(state: &AudioCaptureState, mic_name: &str, loopback_name: &str) {
+    let settings = state.settings.lock().map(|s| s.clone()).unwrap_or_default();
+    for (source, device_name) in [("mic", mic_name), ("loopback", loopback_name)] {
+        if let Some(Ok(mut vad)) = state.accumulator(source).map(|v| v.lock()) {
+            *vad = VadAccumulator::new(source)
+                .with_device(device_name)
+                .with_rate(settings.target_rate(source));
+        }
+    }
+    if let Ok(mut id) = state.session_id.lock() {
+        *id = uuid::Uuid::new_v4().to_string();
+    }
+    state.mic_last_callback.store(0, Ordering::Relaxed);
+    state.loopback_last_callback.store(0, Ordering::Relaxed);
+    state.mic_callback_us.store(0, Ordering::Relaxed);
+    state.loopback_callback_us.store(0, Ordering::Relaxed);
+    for counters in [&state.mic_xruns, &state.loopback_xruns] {
+        counters.xruns.store(0, Ordering::Relaxed);
+        counters.dropped_frames.store(0, Ordering::Relaxed);
+    }
+    if let Ok(mut mixer) = state.mixer.lock() {
+        mixer.reset();
+        mixer.vad.device_name = format!("{} + {}", mic_name, loopback_name);
+    }
+    if let Ok(mut aec) = state.echo_canceller.lock() {
+        aec.reset();
+    }
+    if let Ok(mut wake) = state.wake.lock() {
+        *wake = WakeTrigger::default();
+    }
+    state
+        .last_activity_nanos
+        .store(state.epoch.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    if let Ok(mut rearm) = state.idle_rearm_at.lock() {
+        *rearm = None;
+    }
+}
+
+/// Looks up a device by exact name among input devices, then output devices
+/// (loopback on Windows/macOS opens an output device as an input stream).
+fn find_device_by_name(host: &cpal::Host, name: &str) -> Option<cpal::Device> {
+    if let Ok(mut devices) = host.input_devices() {
+        if let Some(device) = devices.find(|d| d.name().is_ok_and(|n| n == name)) {
+            return Some(device);
+        }
+    }
+    if let Ok(mut devices) = host.output_devices() {
+        if let Some(device) = devices.find(|d| d.name().is_ok_and(|n| n == name)) {
+            return Some(device);
+        }
+    }
+    None
+}
+
+/// Peak above which a probe counts as "signal present" (~-80 dBFS).
+const PROBE_SIGNAL_FLOOR: f32 = 1e-4;
+
+/// Result of `probe_loopback`.
+#[derive(Debug, Clone, Serialize)]
+pub struct LoopbackProbe {
+    pub device_name: String,
+    pub peak: f32,
+    pub rms: f32,
+    /// Samples received during the window; 0 means the device never
+    /// delivered a callback (WASAPI loopback does this when nothing plays)
+    pub samples: usize,
+    pub signal_detected: bool,
+}
+
+/// Running peak / sum-of-squares for a level probe.
+#[derive(Default)]
+struct LevelMeter {
+    peak: f32,
+    sum_squares: f64,
+    samples: usize,
+}
+
+impl LevelMeter {
+    fn add(&mut self, samples: impl Iterator<Item = f32>) {
+        for sample in samples {
+            self.peak = self.peak.max(sample.abs());
+            self.sum_squares += (sample * sample) as f64;
+            self.samples += 1;
+        }
+    }
+}
+
+/// Opens `device` with its default config, independently of any running
+/// capture session, and hands every callback's interleaved samples (as f32)
+/// to `on_audio` for `duration`. Blocks the calling thread.
+fn capture_raw_for<F>(device: &cpal::Device, duration: std::time::Duration, mut on_audio: F) -> Result<StreamConfig, String>
+where
+    F: FnMut(&[f32], &StreamConfig) + Send + 'static,
+{
+    let supported = device
+        .default_input_config()
+        .map_err(|e| format!("No default config: {}", e))?;
+    let sample_format = supported.sample_format();
+    let config: StreamConfig = supported.into();
+    check_stream_config(&config, "Diagnostic")?;
+    let stream_config = config.clone();
+    let on_error = |err| log::error!("Diagnostic stream error: {}", err);
+
+    let stream = match sample_format {
+        SampleFormat::F32 => device.build_input_stream(
+            &config,
+            move |data: &[f32], _info| on_audio(data, &stream_config),
+            on_error,
+            None,
+        ),
+        SampleFormat::I16 => {
+            let mut converted = Vec::new();
+            device.build_input_stream(
+                &config,
+                move |data: &[i16], _info| {
+                    converted.clear();
+                    converted.extend(data.iter().map(|&s| s as f32 / i16::MAX as f32));
+                    on_audio(&converted, &stream_config);
+                },
+                on_error,
+                None,
+            )
+        }
+        _ => return Err(format!("Unsupported sample format {:?}", sample_format)),
+    }
+    .map_err(|e| format!("Failed to build diagnostic stream: {}", e))?;
+
+    stream.play().map_err(|e| format!("Diagnostic stream play failed: {}", e))?;
+    std::thread::sleep(duration);
+    drop(stream);
+
+    Ok(config)
+}
+
+/// Measures peak and RMS on `device` for `duration`.
+fn measure_device_levels(device: &cpal::Device, duration: std::time::Duration) -> Result<LevelMeter, String> {
+    let meter = Arc::new(Mutex::new(LevelMeter::default()));
+    let sink = meter.clone();
+    capture_raw_for(device, duration, move |data, _config| {
+        if let Ok(mut meter) = sink.lock() {
+            meter.add(data.iter().copied());
+        }
+    })?;
+
+    let result = meter.lock().map(|mut m| std::mem::take(&mut *m)).unwrap_or_default();
+    Ok(result)
+}
+
+/// Resolves the device a source would capture from: the default input for
+/// "mic", the selected or auto-detected loopback for "loopback".
+fn resolve_source_device(state: &AudioCaptureState, source: &str) -> Result<cpal::Device, String> {
+    let host = cpal::default_host();
+    match source {
+        "mic" => host
+            .default_input_device()
+            .ok_or_else(|| "No default input (microphone) device found".to_string()),
+        "loopback" => {
+            let selected = state.loopback_device.lock().ok().and_then(|s| s.clone());
+            match selected {
+                Some(name) => find_device_by_name(&host, &name)
+                    .ok_or_else(|| format!("Selected loopback device '{}' is no longer available", name)),
+                None => find_loopback_device(&host).ok_or_else(|| LOOPBACK_NOT_FOUND.to_string()),
+            }
+        }
+        other => Err(format!("Unknown source '{}' (expected \"mic\" or \"loopback\")", other)),
+    }
+}
+
+/// Payload of the `"raw-audio"` event: `RAW_AUDIO_CHUNK_MS` of a source's
+/// audio, downmixed and resampled but never seen by the VAD.
+#[derive(Debug, Clone, Serialize)]
+pub struct RawAudio {
+    pub source: String,
+    /// Order within the diagnostic window, from 0
+    pub index: u64,
+    pub sample_rate: u32,
+    /// base64 little-endian i16 mono PCM
+    pub audio_b64: String,
+    pub peak: f32,
+    pub rms_dbfs: f32,
+}
+
+/// Result of `diagnostic_raw_capture`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RawCaptureReport {
+    /// "session" (tapped the running stream) or "device" (opened its own)
+    pub path: String,
+    pub events: u64,
+    pub samples: usize,
+    pub sample_rate: u32,
+    pub peak: f32,
+    pub signal_detected: bool,
+}
+
+/// Cuts audio into fixed-size `"raw-audio"` events.
+struct RawTap {
+    source: String,
+    sample_rate: u32,
+    buffer: Vec<f32>,
+    index: u64,
+    meter: LevelMeter,
+}
+
+impl RawTap {
+    fn new(source: &str, sample_rate: u32) -> Self {
+        Self {
+            source: source.to_string(),
+            sample_rate,
+            buffer: Vec::new(),
+            index: 0,
+            meter: LevelMeter::default(),
+        }
+    }
+
+    /// Buffers `samples`, returning every event that is now complete.
+    fn push(&mut self, samples: &[f32]) -> Vec<RawAudio> {
+        self.meter.add(samples.iter().copied());
+        self.buffer.extend_from_slice(samples);
+        let size = (self.sample_rate as usize * RAW_AUDIO_CHUNK_MS as usize / 1000).max(1);
+        let mut events = Vec::new();
+        while self.buffer.len() >= size {
+            let rest = self.buffer.split_off(size);
+            let full = std::mem::replace(&mut self.buffer, rest);
+            events.push(self.event(&full));
+        }
+        events
+    }
+
+    /// The final, possibly short, event.
+    fn finish(&mut self) -> Option<RawAudio> {
+        if self.buffer.is_empty() {
+            return None;
+        }
+        let rest = std::mem::take(&mut self.buffer);
+        Some(self.event(&rest))
+    }
+
+    fn event(&mut self, samples: &[f32]) -> RawAudio {
+        let mut bytes = Vec::with_capacity(samples.len() * 2);
+        for &s in samples {
+            bytes.extend_from_slice(&((s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes());
+        }
+        let event = RawAudio {
+            source: self.source.clone(),
+            index: self.index,
+            sample_rate: self.sample_rate,
+            audio_b64: base64::engine::general_purpose::STANDARD.encode(&bytes),
+            peak: samples.iter().fold(0.0f32, |peak, &s| peak.max(s.abs())),
+            rms_dbfs: to_dbfs(frame_rms(samples)),
+        };
+        self.index += 1;
+        event
+    }
+
+    fn report(&self, path: &str) -> RawCaptureReport {
+        RawCaptureReport {
+            path: path.to_string(),
+            events: self.index,
+            samples: self.meter.samples,
+            sample_rate: self.sample_rate,
+            peak: self.meter.peak,
+            signal_detected: self.meter.peak > PROBE_SIGNAL_FLOOR,
+        }
+    }
+}
+
+/// Hands a stream's pre-VAD audio to an armed `raw_tap`.
+fn feed_raw_tap(state: &AudioCaptureState, source: &str, mono: &[f32]) {
+    let events = match state.raw_tap.lock() {
+        Ok(mut tap) => match tap.as_mut().filter(|t| t.source == source) {
+            Some(tap) => tap.push(mono),
+            None => return,
+        },
+        Err(_) => return,
+    };
+    for event in events {
+        emit_event(state, "raw-audio", event);
+    }
+}
+
+/// Streams `duration_ms` of a source's audio as `"raw-audio"` events of
+/// `RAW_AUDIO_CHUNK_MS` each, bypassing the VAD, to tell a silent device
+/// from a VAD that's eating speech. During device capture it taps the
+/// running stream after the downmix and resample, without touching the
+/// accumulators (and keeps the source from suspending meanwhile);
+/// otherwise it opens its own stream on the device capture would use.
+/// Blocks for the duration; call it off the main thread.
+pub fn diagnostic_raw_capture(state: Arc<AudioCaptureState>, source: &str, duration_ms: u64) -> Result<RawCaptureReport, String> {
+    if !(1..=MAX_RAW_CAPTURE_MS).contains(&duration_ms) {
+        return Err(format!(
+            "Raw capture duration must be between 1 and {} ms",
+            MAX_RAW_CAPTURE_MS
+        ));
+    }
+    let device = resolve_source_device(&state, source)?;
+    let settings = state.settings.lock().map(|s| s.clone()).unwrap_or_default();
+    let duration = std::time::Duration::from_millis(duration_ms);
+
+    let capturing_devices = state
+        .active_session
+        .lock()
+        .is_ok_and(|active| *active == Some(SessionKind::Devices));
+    if capturing_devices {
+        let mixed = state
+            .capture_mode
+            .lock()
+            .is_ok_and(|m| *m == CaptureMode::MixedMono);
+        let rate = if mixed {
+            TARGET_SAMPLE_RATE
+        } else {
+            settings.target_rate(source)
+        };
+        {
+            let mut tap = state
+                .raw_tap
+                .lock()
+                .map_err(|_| "Raw capture is unavailable".to_string())?;
+            if let Some(active) = tap.as_ref() {
+                return Err(format!("A raw capture of {} is already running", active.source));
+            }
+            *tap = Some(RawTap::new(source, rate));
+        }
+        std::thread::sleep(duration);
+        let mut tap = state
+            .raw_tap
+            .lock()
+            .ok()
+            .and_then(|mut tap| tap.take())
+            .ok_or("Raw capture tap was lost")?;
+        if let Some(event) = tap.finish() {
+            emit_event(&state, "raw-audio", event);
+        }
+        return Ok(tap.report("session"));
+    }
+
+    let target_rate = settings.target_rate(source);
+    let tap = Arc::new(Mutex::new(RawTap::new(source, target_rate)));
+    let sink = tap.clone();
+    let emitter = state.clone();
+    let (mut mono, mut resampled) = (Vec::new(), Vec::new());
+    capture_raw_for(&device, duration, move |data, config| {
+        to_mono_into(
+            data,
+            config.channels,
+            config.sample_rate.0,
+            target_rate,
+            Downmix::Average,
+            &mut mono,
+            &mut resampled,
+        );
+        let events = sink.lock().map(|mut tap| tap.push(&resampled)).unwrap_or_default();
+        for event in events {
+            emit_event(&emitter, "raw-audio", event);
+        }
+    })?;
+    let mut tap = tap.lock().map_err(|_| "Raw capture tap was lost".to_string())?;
+    if let Some(event) = tap.finish() {
+        emit_event(&state, "raw-audio", event);
+    }
+    Ok(tap.report("device"))
+}
+
+/// Longest clip `record_clip` will capture.
+const MAX_CLIP_SECS: f32 = 60.0;
+
+/// Records exactly `duration_secs` of a source with no VAD gating and
+/// returns it as one chunk. Uses its own stream, and fails with "Already
+/// running" while any other session is active. The clip isn't queued, so
+/// its `seq` is 0. Blocks for the duration; call it off the main thread.
+pub fn record_clip(state: &AudioCaptureState, source: &str, duration_secs: f32) -> Result<AudioChunk, String> {
+    if !(duration_secs > 0.0 && duration_secs <= MAX_CLIP_SECS) {
+        return Err(format!("Clip duration must be between 0 and {} seconds", MAX_CLIP_SECS));
+    }
+    let _claim = claim_session(state, SessionKind::Clip)?;
+    let device = resolve_source_device(state, source)?;
+    let settings = state.settings.lock().map(|s| s.clone()).unwrap_or_default();
+    let target_rate = settings.target_rate(source);
+
+    let clip = Arc::new(Mutex::new(Vec::<f32>::new()));
+    let sink = clip.clone();
+    let mut mono = Vec::new();
+    let mut resampled = Vec::new();
+    capture_raw_for(
+        &device,
+        std::time::Duration::from_secs_f32(duration_secs),
+        move |data, config| {
+            to_mono_into(
+                data,
+                config.channels,
+                config.sample_rate.0,
+                target_rate,
+                Downmix::Average,
+                &mut mono,
+                &mut resampled,
+            );
+            if let Ok(mut clip) = sink.lock() {
+                clip.extend_from_slice(&resampled);
+            }
+        },
+    )?;
+
+    let samples = clip.lock().map(|mut c| std::mem::take(&mut *c)).unwrap_or_default();
+    if samples.is_empty() {
+        return Err(format!("No audio arrived from the {} device", source));
+    }
+    // Trim the callback overshoot so the clip is exactly the requested length
+    let wanted = (duration_secs * target_rate as f32) as usize;
+    let samples = &samples[..samples.len().min(wanted)];
+
+    let device_name = device.name().unwrap_or_default();
+    let mut chunk = encode_chunk(samples, target_rate, source, &device_name, "mono", &settings);
+    chunk.emit_nanos = state.epoch.elapsed().as_nanos() as u64;
+    chunk.session_id = get_session_id(state);
+    chunk.speaker = speaker_for(state, source);
+    Ok(chunk)
+}
+
+/// Decoded file fed to the VAD by `start_file_source`.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileSourceInfo {
+    pub path: String,
+    pub source: String,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// `None` if the container doesn't state its length
+    pub duration_secs: Option<f32>,
+}
+
+/// Payload of the `"file-source-finished"` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileSourceFinished {
+    pub path: String,
+    pub source: String,
+    /// Audio fed before the file ended or capture was stopped
+    pub fed_secs: f32,
+    /// Stopped before the end of the file
+    pub stopped: bool,
+    pub error: Option<String>,
+}
+
+/// Plays a WAV/MP3 file into `source_label`'s accumulator in place of a
+/// device, so demos and integration tests run without audio hardware.
+/// Frames go through the same downmix/resample and `process_frame` path
+/// as a live stream. `speed` multiplies real time (default 1.0); 0 feeds as
+/// fast as the file decodes. Counts as a capture session: `stop_capture`
+/// ends it, and the end of the file flushes and stops capture.
+pub fn start_file_source(
+    state: Arc<AudioCaptureState>,
+    path: &str,
+    source_label: &str,
+    speed: Option<f32>,
+) -> Result<FileSourceInfo, String> {
+    use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+    use symphonia::core::formats::FormatOptions;
+    use symphonia::core::io::MediaSourceStream;
+    use symphonia::core::meta::MetadataOptions;
+    use symphonia::core::probe::Hint;
+
+    if source_label != "mic" && source_label != "loopback" {
+        return Err(format!("Unknown source '{}', expected mic or loopback", source_label));
+    }
+    let speed = speed.unwrap_or(1.0);
+    if !(0.0..=FILE_SOURCE_MAX_SPEED).contains(&speed) {
+        return Err(format!("Speed must be between 0 and {}", FILE_SOURCE_MAX_SPEED));
+    }
+    let claim = claim_session(&state, SessionKind::FileSource)?;
+
+    let file = std::fs::File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = std::path::Path::new(path).extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| format!("Unsupported audio file {}: {}", path, e))?;
+    let format = probed.format;
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| format!("No audio track in {}", path))?;
+    let track_id = track.id;
+    let sample_rate = track
+        .codec_params
+        .sample_rate
+        .ok_or_else(|| format!("Unknown sample rate in {}", path))?;
+    let channels = track.codec_params.channels.map_or(1, |c| c.count() as u16);
+    if channels == 0 || sample_rate == 0 {
+        return Err(format!("{} reports {}ch @ {}Hz", path, channels, sample_rate));
+    }
+    let duration_secs = track.codec_params.n_frames.map(|n| n as f32 / sample_rate as f32);
+    let decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| format!("No decoder for {}: {}", path, e))?;
+
+    let file_name = std::path::Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string());
+    let (mic_name, loopback_name) = if source_label == "mic" {
+        (file_name.as_str(), "")
+    } else {
+        ("", file_name.as_str())
+    };
+    begin_session(&state, mic_name, loopback_name);
+    state.running.store(true, Ordering::SeqCst);
+    claim.keep();
+    log::info!(
+        "File source {} as {}: {}ch @ {}Hz",
+        path,
+        source_label,
+        channels,
+        sample_rate
+    );
+
+    let info = FileSourceInfo {
+        path: path.to_string(),
+        source: source_label.to_string(),
+        sample_rate,
+        channels,
+        duration_secs,
+    };
+    let playback = FilePlayback {
+        session_id: get_session_id(&state),
+        state,
+        info: info.clone(),
+        track_id,
+        speed,
+    };
+    std::thread::spawn(move || playback.run(format, decoder));
+    Ok(info)
+}
+
+/// The feeding thread behind `start_file_source`.
+struct FilePlayback {
+    state: Arc<AudioCaptureState>,
+    /// Session this playback began; once another session replaces it the
+    /// thread exits without touching capture
+    session_id: String,
+    info: FileSourceInfo,
+    track_id: u32,
+    speed: f32,
+}
+
+impl FilePlayback {
+    /// Whether capture was stopped, or stopped and restarted, since this
+    /// playback began.
+    fn superseded(&self) -> bool {
+        !self.state.running.load(Ordering::Relaxed) || get_session_id(&self.state) != self.session_id
+    }
+
+    fn run(
+        self,
+        mut format: Box<dyn symphonia::core::formats::FormatReader>,
+        mut decoder: Box<dyn symphonia::core::codecs::Decoder>,
+    ) {
+        use symphonia::core::audio::SampleBuffer;
+        use symphonia::core::errors::Error;
+
+        let state = &self.state;
+        let label = self.info.source.as_str();
+        let mut scratch = ConversionScratch::default();
+        let mut fed_frames = 0usize;
+        let started = Instant::now();
+        let mut stopped = false;
+        let mut error = None;
+
+        loop {
+            if self.superseded() {
+                stopped = true;
+                break;
+            }
+            let packet = match format.next_packet() {
+                Ok(packet) => packet,
+                Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => {
+                    error = Some(e.to_string());
+                    break;
+                }
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            let decoded = match decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                // A corrupt frame is skipped, as a glitching device would be
+                Err(Error::DecodeError(e)) => {
+                    log::warn!("Skipping undecodable frame in {}: {}", self.info.path, e);
+                    continue;
+                }
+                Err(e) => {
+                    error = Some(e.to_string());
+                    break;
+                }
+            };
+            let mut samples = SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+            samples.copy_interleaved_ref(decoded);
+
+            let (target_rate, gain) = state
+                .settings
+                .lock()
+                .map(|s| (s.target_rate(label), s.downmix_gain(self.info.channels)))
+                .unwrap_or((TARGET_SAMPLE_RATE, 1.0));
+            // Device-sized frames, so the VAD sees the same granularity as live
+            let frame_len = (self.info.sample_rate as usize / 100).max(1) * self.info.channels as usize;
+            // Paused frames are dropped as a device callback would, keeping
+            // the playback clock running
+            let paused = state.privacy_paused.load(Ordering::Relaxed);
+            for frame in samples.samples().chunks(frame_len) {
+                if !paused {
+                    let mono = scratch.convert_f32(frame, self.info.channels, self.info.sample_rate, target_rate, gain);
+                    process_frame(state, label, mono);
+                }
+                fed_frames += frame.len() / self.info.channels as usize;
+            }
+
+            if self.speed > 0.0 {
+                let due = fed_frames as f32 / self.info.sample_rate as f32 / self.speed;
+                let ahead = due - started.elapsed().as_secs_f32();
+                if ahead > 0.0 {
+                    std::thread::sleep(std::time::Duration::from_secs_f32(ahead));
+                }
+            }
+        }
+
+        // A stop racing the end of the file would otherwise flush into, or
+        // stop, whichever session started after it
+        stopped = stopped || self.superseded();
+        if !stopped {
+            let settings = state.settings.lock().map(|s| s.clone()).unwrap_or_default();
+            let chunks = state
+                .accumulator(label)
+                .and_then(|vad| vad.lock().ok())
+                .map(|mut vad| vad.force_flush(&settings));
+            for chunk in chunks.unwrap_or_default() {
+                emit_chunk(state, chunk);
+            }
+            stop_capture(state);
+        }
+        if let Some(e) = &error {
+            log::error!("File source {} failed: {}", self.info.path, e);
+        }
+
+        emit_event(
+            state,
+            "file-source-finished",
+            FileSourceFinished {
+                path: self.info.path.clone(),
+                source: self.info.source.clone(),
+                fed_secs: fed_frames as f32 / self.info.sample_rate as f32,
+                stopped,
+                error,
+            },
+        );
+    }
+}
+
+/// Dry-run result for one source from `validate_capture_config`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceValidation {
+    pub ok: bool,
+    pub device: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+    pub error: Option<String>,
+}
+
+/// Result of `validate_capture_config`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigValidation {
+    pub mic: SourceValidation,
+    pub loopback: SourceValidation,
+}
+
+/// Resolves each source's device and builds its stream with the current
+/// settings, then drops it unplayed, so the UI can show what would fail
+/// before the user starts. Refused while capture runs, since a second open
+/// of the same hardware can fail for reasons unrelated to the config.
+pub fn validate_capture_config(state: Arc<AudioCaptureState>) -> Result<ConfigValidation, String> {
+    if state.running.load(Ordering::SeqCst) {
+        return Err("Capture is running; stop it before validating".into());
+    }
+
+    let validate = |source: &str| {
+        let device = match resolve_source_device(&state, source) {
+            Ok(device) => device,
+            Err(e) => {
+                return SourceValidation {
+                    ok: false,
+                    device: None,
+                    sample_rate: None,
+                    channels: None,
+                    error: Some(e),
+                }
+            }
+        };
+        let name = device.name().ok();
+        match build_capture_stream(&device, source, state.clone()) {
+            Ok((stream, config)) => {
+                drop(stream);
+                SourceValidation {
+                    ok: true,
+                    device: name,
+                    sample_rate: Some(config.sample_rate.0),
+                    channels: Some(config.channels),
+                    error: None,
+                }
+            }
+            Err(e) => SourceValidation {
+                ok: false,
+                device: name,
+                sample_rate: None,
+                channels: None,
+                error: Some(e),
+            },
+        }
+    };
+
+    Ok(ConfigValidation {
+        mic: validate("mic"),
+        loopback: validate("loopback"),
+    })
+}
+
+/// Opens the loopback device capture would use (the pinned
+/// `loopback_device`, else `find_loopback_device`) for `duration_ms` and
+/// reports whether any signal arrived. Blocks for the duration; call it off
+/// the main thread.
+pub fn probe_loopback(state: &AudioCaptureState, duration_ms: u64) -> Result<LoopbackProbe, String> {
+    let device = resolve_source_device(state, "loopback")?;
+    let device_name = device.name().unwrap_or_default();
+
+    let meter = measure_device_levels(&device, std::time::Duration::from_millis(duration_ms))?;
+    let rms = if meter.samples > 0 {
+        (meter.sum_squares / meter.samples as f64).sqrt() as f32
+    } else {
+        0.0
+    };
+
+    Ok(LoopbackProbe {
+        device_name,
+        peak: meter.peak,
+        rms,
+        samples: meter.samples,
+        signal_detected: meter.peak > PROBE_SIGNAL_FLOOR,
+    })
+}
+
+/// Monitor-source name keywords (lower index = higher priority)
+#[cfg(target_os = "linux")]
+const MONITOR_KEYWORDS: &[&str] = &[
+    "maestro",      // Our virtual sink from setup_audio.sh
+    "monitor of",   // PipeWire naming convention
+    ".monitor",     // PulseAudio naming convention
+    "monitor",      // Generic monitor source
+];
+
+/// Driver-provided loopback inputs, tried by the loopback fallback only
+#[cfg(target_os = "windows")]
+const MONITOR_KEYWORDS: &[&str] = &["stereo mix", "what u hear", "wave out mix"];
+#[cfg(target_os = "macos")]
+const MONITOR_KEYWORDS: &[&str] = &["blackhole", "loopback", "soundflower"];
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+const MONITOR_KEYWORDS: &[&str] = &[];
+
+/// Priority of an input device name as a monitor source, if it is one.
+fn monitor_priority(name: &str) -> Option<usize> {
+    let lower = name.to_lowercase();
+    MONITOR_KEYWORDS.iter().position(|kw| lower.contains(kw))
+}
+
+/// A device that could serve as the loopback source.
+#[derive(Debug, Clone, Serialize)]
+pub struct LoopbackCandidate {
+    pub name: String,
+    /// "monitor" (PipeWire/PulseAudio monitor source) or "output" (output
+    /// device opened as an input stream)
+    pub kind: String,
+    /// Whether auto-detection (`find_loopback_device`) would pick this one
+    pub auto_selected: bool,
+}
+
+/// Lists every device usable as loopback: on Linux all monitor sources (best
+/// match first) followed by output devices; elsewhere the output devices.
+pub fn list_loopback_candidates() -> Vec<LoopbackCandidate> {
+    let host = cpal::default_host();
+    let auto = find_loopback_device(&host).and_then(|d| d.name().ok());
+    let mut candidates: Vec<(usize, LoopbackCandidate)> = Vec::new();
+
+    #[cfg(target_os = "linux")]
+    if let Ok(devices) = host.input_devices() {
+        for name in devices.filter_map(|d| d.name().ok()) {
+            if let Some(priority) = monitor_priority(&name) {
+                candidates.push((
+                    priority,
+                    LoopbackCandidate {
+                        auto_selected: auto.as_deref() == Some(name.as_str()),
+                        name,
+                        kind: "monitor".into(),
+                    },
+                ));
+            }
+        }
+    }
+
+    if let Ok(devices) = host.output_devices() {
+        for name in devices.filter_map(|d| d.name().ok()) {
+            candidates.push((
+                usize::MAX,
+                LoopbackCandidate {
+                    auto_selected: auto.as_deref() == Some(name.as_str()),
+                    name,
+                    kind: "output".into(),
+                },
+            ));
+        }
+    }
+
+    candidates.sort_by_key(|(priority, _)| *priority);
+    candidates.into_iter().map(|(_, c)| c).collect()
+}
+
+/// Pins the loopback device used by `prepare_capture`, or returns to
+/// auto-detection with `None`.
+pub fn set_loopback_device(state: &AudioCaptureState, name: Option<String>) -> Result<(), String> {
+    if let Some(name) = &name {
+        if !list_loopback_candidates().iter().any(|c| &c.name == name) {
+            return Err(format!("'{}' is not a loopback candidate", name));
+        }
+    }
+    if let Ok(mut selected) = state.loopback_device.lock() {
+        *selected = name;
+    }
+    Ok(())
+}
+
+/// Platform-aware loopback device finder.
+/// On Linux, searches input devices for monitor sources using prioritized
+/// keywords. Logs all discovered devices for debugging.
+fn find_loopback_device(host: &cpal::Host) -> Option<cpal::Device> {
+    resolve_loopback(host).0
+}
+
+/// `find_loopback_device` plus the branch that produced the result, for
+/// `describe_loopback_strategy`.
+fn resolve_loopback(host: &cpal::Host) -> (Option<cpal::Device>, &'static str) {
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(device) = best_monitor_source(host) {
+            log::info!("Selected loopback: {}", device.name().unwrap_or_default());
+            return (Some(device), "linux-monitor-source");
+        }
+    }
+
+    // On Windows, WASAPI exposes loopback via the default output device
+    // opened as an input stream.
+    #[cfg(target_os = "windows")]
+    {
+        return (host.default_output_device(), "wasapi-default-output");
+    }
+
+    // On macOS, the default output works if a loopback driver is installed.
+    #[cfg(target_os = "macos")]
+    {
+        return (host.default_output_device(), "macos-default-output");
+    }
+
+    // Fallback for Linux: try default output device
+    #[cfg(target_os = "linux")]
+    {
+        log::warn!("No monitor source found, falling back to default output device");
+        (host.default_output_device(), "linux-default-output-fallback")
+    }
+}
+
+/// The input device whose name best matches `MONITOR_KEYWORDS`. Logs every
+/// input seen, for debugging loopback detection.
+fn best_monitor_source(host: &cpal::Host) -> Option<cpal::Device> {
+    let devices = host.input_devices().ok()?;
+    let mut all_names = Vec::new();
+    let mut best: Option<(usize, cpal::Device)> = None;
+
+    for device in devices {
+        if let Ok(name) = device.name() {
+            all_names.push(name.clone());
+            if let Some(priority) = monitor_priority(&name) {
+                if best.as_ref().map_or(true, |(p, _)| priority < *p) {
+                    best = Some((priority, device));
+                }
+            }
+        }
+    }
+
+    log::info!("Available input devices: {:?}", all_names);
+    best.map(|(_, device)| device)
+}
+
+/// Every loopback strategy available on this platform, most native first:
+/// the default output opened as an input (WASAPI loopback on Windows) and a
+/// named monitor or loopback-driver input, with the monitor first on Linux.
+fn loopback_strategies(host: &cpal::Host) -> Vec<(&'static str, cpal::Device)> {
+    let output = host.default_output_device().map(|d| ("default-output", d));
+    let monitor = best_monitor_source(host).map(|d| ("monitor-source", d));
+    if cfg!(target_os = "linux") {
+        monitor.into_iter().chain(output).collect()
+    } else {
+        output.into_iter().chain(monitor).collect()
+    }
+}
+
+/// Payload of the `"loopback-strategy"` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct LoopbackStrategyChosen {
+    /// "default-output" or "monitor-source"
+    pub method: String,
+    pub device: String,
+    /// False when no strategy delivered signal and the first that opened
+    /// was kept (nothing may be playing)
+    pub signal_detected: bool,
+    /// Strategies that were tried and rejected, with the reason
+    pub rejected: Vec<String>,
+}
+
+/// Probes each of `loopback_strategies` for `probe_ms` and returns the first
+/// device that delivers signal. If every strategy opens but stays silent
+/// (nothing playing yet), the first that opened is used. Emits
+/// `"loopback-strategy"` with the choice.
+fn probe_loopback_strategies(host: &cpal::Host, state: &AudioCaptureState, probe_ms: u32) -> Result<cpal::Device, String> {
+    let mut rejected = Vec::new();
+    let mut first_open: Option<(&'static str, cpal::Device)> = None;
+    let mut chosen = None;
+
+    for (method, device) in loopback_strategies(host) {
+        let name = device.name().unwrap_or_default();
+        match measure_device_levels(&device, std::time::Duration::from_millis(probe_ms as u64)) {
+            Ok(meter) if meter.samples == 0 => rejected.push(format!("{} ({}): no audio arrived", method, name)),
+            Ok(meter) if meter.peak > PROBE_SIGNAL_FLOOR => {
+                chosen = Some((method, device, true));
+                break;
+            }
+            Ok(_) => {
+                rejected.push(format!("{} ({}): silent", method, name));
+                first_open.get_or_insert((method, device));
+            }
+            Err(e) => rejected.push(format!("{} ({}): {}", method, name, e)),
+        }
+    }
+
+    let (method, device, signal_detected) = chosen
+        .or_else(|| first_open.map(|(method, device)| (method, device, false)))
+        .ok_or_else(|| format!("{} Tried: {}", LOOPBACK_NOT_FOUND, rejected.join("; ")))?;
+    let device_name = device.name().unwrap_or_default();
+    log::info!(
+        "Loopback strategy {} on {} (signal: {})",
+        method,
+        device_name,
+        signal_detected
+    );
+    let chosen = LoopbackStrategyChosen {
+        method: method.to_string(),
+        device: device_name,
+        signal_detected,
+        rejected,
+    };
+    if let Ok(mut probed) = state.probed_loopback.lock() {
+        *probed = Some(chosen.clone());
+    }
+    emit_event(state, "loopback-strategy", chosen);
+    Ok(device)
+}
+
+/// Turns the probing loopback fallback on or off; `probe_ms` (default
+/// `DEFAULT_LOOPBACK_PROBE_MS`) is spent on each strategy tried, so a start
+/// can take several probes longer. Applies from the next start.
+pub fn set_loopback_fallback(state: &AudioCaptureState, enabled: bool, probe_ms: Option<u32>) -> Result<(), String> {
+    let probe_ms = probe_ms.unwrap_or(DEFAULT_LOOPBACK_PROBE_MS);
+    if !(1..=MAX_LOOPBACK_PROBE_MS).contains(&probe_ms) {
+        return Err(format!(
+            "Loopback probe must be between 1 and {} ms, got {}",
+            MAX_LOOPBACK_PROBE_MS, probe_ms
+        ));
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.loopback_fallback = enabled;
+        settings.loopback_probe_ms = probe_ms;
+    }
+    Ok(())
+}
+
+/// OS-level microphone access for this app, from `check_mic_permission`.
+/// Without it macOS and Windows still open the input stream but deliver
+/// silence, which looks like a dead mic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub enum MicPermission {
+    Granted,
+    Denied,
+    /// macOS only: the user hasn't been asked yet
+    NotDetermined,
+    /// No permission model to query (Linux)
+    Unsupported,
+}
+
+/// Queries the OS microphone permission. On macOS, `prompt` shows the
+/// system prompt when the user hasn't been asked yet; the answer arrives
+/// asynchronously, so this still returns `NotDetermined` and the caller
+/// should check again once the prompt is dismissed. `prompt` is ignored
+/// elsewhere.
+pub fn check_mic_permission(prompt: bool) -> MicPermission {
+    #[cfg(target_os = "macos")]
+    {
+        use objc2_av_foundation::{AVAuthorizationStatus, AVCaptureDevice, AVMediaTypeAudio};
+
+        let Some(media_type) = (unsafe { AVMediaTypeAudio }) else {
+            return MicPermission::Unsupported;
+        };
+        let status = unsafe { AVCaptureDevice::authorizationStatusForMediaType(media_type) };
+        return match status {
+            AVAuthorizationStatus::Authorized => MicPermission::Granted,
+            AVAuthorizationStatus::Denied | AVAuthorizationStatus::Restricted => MicPermission::Denied,
+            _ => {
+                if prompt {
+                    let handler = block2::RcBlock::new(|granted: objc2::runtime::Bool| {
+                        log::info!("Microphone permission answered: granted={}", granted.as_bool());
+                    });
+                    unsafe { AVCaptureDevice::requestAccessForMediaType_completionHandler(media_type, &handler) };
+                }
+                MicPermission::NotDetermined
+            }
+        };
+    }
+
+    // Settings > Privacy > Microphone writes "Allow"/"Deny" to the consent
+    // store: the top-level key for the whole device, `NonPackaged` for all
+    // desktop (non-Store) apps, and under it one key per executable, named
+    // by its path with `\` replaced by `#`. Missing keys mean allowed.
+    #[cfg(target_os = "windows")]
+    {
+        use winreg::enums::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE};
+        use winreg::RegKey;
+
+        const CONSENT_STORE: &str =
+            r"Software\Microsoft\Windows\CurrentVersion\CapabilityAccessManager\ConsentStore\microphone";
+        let denied = |hive, subkey: &str| {
+            RegKey::predef(hive)
+                .open_subkey(subkey)
+                .and_then(|key| key.get_value::<String, _>("Value"))
+                .is_ok_and(|v| v.eq_ignore_ascii_case("Deny"))
+        };
+        let non_packaged = format!(r"{}\NonPackaged", CONSENT_STORE);
+        let this_exe = std::env::current_exe()
+            .ok()
+            .map(|exe| format!(r"{}\{}", non_packaged, exe.to_string_lossy().replace('\\', "#")));
+        let _ = prompt;
+        return if denied(HKEY_LOCAL_MACHINE, CONSENT_STORE)
+            || denied(HKEY_CURRENT_USER, CONSENT_STORE)
+            || denied(HKEY_CURRENT_USER, &non_packaged)
+            || this_exe.is_some_and(|key| denied(HKEY_CURRENT_USER, &key))
+        {
+            MicPermission::Denied
+        } else {
+            MicPermission::Granted
+        };
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        let _ = prompt;
+        MicPermission::Unsupported
+    }
+}
+
+/// Result of `describe_loopback_strategy`.
+#[derive(Debug, Clone, Serialize)]
+pub struct LoopbackStrategy {
+    /// `std::env::consts::OS`
+    pub platform: String,
+    /// cpal host backend (WASAPI, ALSA, CoreAudio...)
+    pub host: String,
+    /// Branch taken: "user-selected", "linux-monitor-source",
+    /// "linux-default-output-fallback", "wasapi-default-output" or
+    /// "macos-default-output". With `loopback_fallback` on, "probed-" plus
+    /// the strategy the last start's probe chose, or "probe-pending" before
+    /// any start has probed
+    pub method: String,
+    /// Device that branch resolved to, if any
+    pub device: Option<String>,
+}
+
+/// Reports the resolution `prepare_capture` would use and where it landed.
+/// With `loopback_fallback` on, that is the latest probe's choice; no new
+/// probe is run.
+pub fn describe_loopback_strategy(state: &AudioCaptureState) -> LoopbackStrategy {
+    let host = cpal::default_host();
+    let selected = state.loopback_device.lock().ok().and_then(|s| s.clone());
+    let fallback = state.settings.lock().is_ok_and(|s| s.loopback_fallback);
+    let (method, device) = match selected {
+        Some(name) => (
+            "user-selected".to_string(),
+            find_device_by_name(&host, &name).and_then(|d| d.name().ok()),
+        ),
+        None if fallback => match state.probed_loopback.lock().ok().and_then(|p| p.clone()) {
+            Some(probed) => (format!("probed-{}", probed.method), Some(probed.device)),
+            None => ("probe-pending".to_string(), None),
+        },
+        None => {
+            let (device, method) = resolve_loopback(&host);
+            (method.to_string(), device.and_then(|d| d.name().ok()))
+        }
+    };
+    LoopbackStrategy {
+        platform: std::env::consts::OS.to_string(),
+        host: host.id().name().to_string(),
+        method,
+        device,
+    }
+}
+
+/// A device's default config as reported in a `DiagnosticsSnapshot`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceConfigInfo {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub sample_format: String,
+}
+
+/// One device in a `DiagnosticsSnapshot`. Query failures are recorded in
+/// `errors` instead of dropping the device.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceEntry {
+    pub name: Option<String>,
+    pub default_config: Option<DeviceConfigInfo>,
+    pub errors: Vec<String>,
+}
+
+/// Everything about the audio setup a bug report needs, in one call.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticsSnapshot {
+    pub platform: String,
+    pub host: String,
+    pub available_hosts: Vec<String>,
+    pub default_input: Option<String>,
+    pub default_output: Option<String>,
+    pub inputs: Vec<DeviceEntry>,
+    pub outputs: Vec<DeviceEntry>,
+    /// Device the mic would open, or why there's none
+    pub mic: Option<String>,
+    pub mic_error: Option<String>,
+    pub loopback: LoopbackStrategy,
+    pub running: bool,
+    /// Devices the last session actually opened
+    pub last_devices: Option<DeviceSelection>,
+    pub settings: AudioSettings,
+    /// Top-level query failures (device enumeration)
+    pub errors: Vec<String>,
+}
+
+fn describe_device(device: &cpal::Device, output: bool) -> DeviceEntry {
+    let mut errors = Vec::new();
+    let name = device.name().map_err(|e| errors.push(format!("name: {}", e))).ok();
+    let config = if output {
+        device.default_output_config()
+    } else {
+        device.default_input_config()
+    };
+    let default_config = config
+        .map(|c| DeviceConfigInfo {
+            sample_rate: c.sample_rate().0,
+            channels: c.channels(),
+            sample_format: format!("{:?}", c.sample_format()),
+        })
+        .map_err(|e| errors.push(format!("default config: {}", e)))
+        .ok();
+    DeviceEntry {
+        name,
+        default_config,
+        errors,
+    }
+}
+
+/// Snapshot of the host, every input and output device with its default
+/// config, and how mic and loopback would resolve. Never fails: anything
+/// that errors is recorded in the nearest `errors` list.
+pub fn diagnostics_snapshot(state: &AudioCaptureState) -> DiagnosticsSnapshot {
+    let host = cpal::default_host();
+    let mut errors = Vec::new();
+    let inputs = match host.input_devices() {
+        Ok(devices) => devices.map(|d| describe_device(&d, false)).collect(),
+        Err(e) => {
+            errors.push(format!("input devices: {}", e));
+            Vec::new()
+        }
+    };
+    let (mic, mic_error) = match resolve_source_device(state, "mic") {
+        Ok(device) => (device.name().ok(), None),
+        Err(e) => (None, Some(e)),
+    };
+    let outputs = match host.output_devices() {
+        Ok(devices) => devices.map(|d| describe_device(&d, true)).collect(),
+        Err(e) => {
+            errors.push(format!("output devices: {}", e));
+            Vec::new()
+        }
+    };
+
+    DiagnosticsSnapshot {
+        platform: std::env::consts::OS.to_string(),
+        host: host.id().name().to_string(),
+        available_hosts: cpal::available_hosts().iter().map(|h| h.name().to_string()).collect(),
+        default_input: host.default_input_device().and_then(|d| d.name().ok()),
+        default_output: host.default_output_device().and_then(|d| d.name().ok()),
+        inputs,
+        outputs,
+        mic,
+        mic_error,
+        loopback: describe_loopback_strategy(state),
+        running: state.running.load(Ordering::SeqCst),
+        last_devices: state.last_devices.lock().ok().and_then(|d| d.clone()),
+        settings: state.settings.lock().map(|s| s.clone()).unwrap_or_default(),
+        errors,
+    }
+}
+
+/// Stops capture by setting the running flag to false.
+/// The Stream handles should be dropped by the caller to fully release devices.
+pub fn stop_capture(state: &AudioCaptureState) {
+    let was_running = state.running.swap(false, Ordering::SeqCst);
+    release_session(state, SessionKind::Devices);
+    release_session(state, SessionKind::FileSource);
+    if let Ok(mut rearm) = state.idle_rearm_at.lock() {
+        *rearm = None;
+    }
+    state.mic_speaking.store(false, Ordering::Relaxed);
+    state.loopback_speaking.store(false, Ordering::Relaxed);
+    if was_running {
+        emit_event(state, "capture-stopped", ());
+    }
+}
+
+/// Payload of the `"idle-stop"` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct IdleStop {
+    /// Time since the last emitted chunk (or the start)
+    pub idle_secs: u64,
+    /// When capture will reopen, if a re-arm is set
+    pub rearm_in_secs: Option<u64>,
+}
+
+/// Stops capture if no chunk has been emitted for `idle_stop_mins`, arms the
+/// re-open if one is configured, and emits `"idle-stop"`. Returns true if it
+/// stopped; the caller then drops the stream handles to release the devices.
+pub fn stop_if_idle(state: &AudioCaptureState) -> bool {
+    if !state.running.load(Ordering::SeqCst) {
+        return false;
+    }
+    let Ok((stop_mins, rearm_mins)) = state.settings.lock().map(|s| (s.idle_stop_mins, s.idle_rearm_mins)) else {
+        return false;
+    };
+    let now = state.epoch.elapsed().as_nanos() as u64;
+    let idle_secs = now.saturating_sub(state.last_activity_nanos.load(Ordering::Relaxed)) / 1_000_000_000;
+    if stop_mins == 0 || idle_secs < stop_mins as u64 * 60 {
+        return false;
+    }
+
+    log::info!("No speech for {}s; stopping capture", idle_secs);
+    stop_capture(state);
+    let rearm_in_secs = (rearm_mins > 0).then_some(rearm_mins as u64 * 60);
+    if let (Some(secs), Ok(mut rearm)) = (rearm_in_secs, state.idle_rearm_at.lock()) {
+        *rearm = Some(Instant::now() + std::time::Duration::from_secs(secs));
+    }
+    emit_event(state, "idle-stop", IdleStop { idle_secs, rearm_in_secs });
+    true
+}
+
+/// True once, when an idle-stopped session is due to reopen.
+pub fn take_due_rearm(state: &AudioCaptureState) -> bool {
+    let Ok(mut rearm) = state.idle_rearm_at.lock() else {
+        return false;
+    };
+    if rearm.is_some_and(|at| Instant::now() >= at) {
+        *rearm = None;
+        return true;
+    }
+    false
+}
+
+/// Sets the idle stop (0 disables it) and the optional re-open delay after
+/// one (0 stays stopped). Applies to a running session straight away.
+pub fn set_idle_stop(state: &AudioCaptureState, minutes: u32, rearm_minutes: u32) -> Result<(), String> {
+    if minutes > MAX_IDLE_MINS || rearm_minutes > MAX_IDLE_MINS {
+        return Err(format!("Idle stop and re-arm must be at most {} minutes", MAX_IDLE_MINS));
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.idle_stop_mins = minutes;
+        settings.idle_rearm_mins = rearm_minutes;
+    }
+    Ok(())
+}
+
+/// Final numbers logged (and returned) by `shutdown`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShutdownSummary {
+    pub chunks_emitted: u64,
+    pub mic_speech_secs: f32,
+    pub loopback_speech_secs: f32,
+    /// Chunks still queued at shutdown
+    pub pending_chunks: usize,
+    /// File the pending chunks were written to, if a recording dir is set
+    pub saved_to: Option<String>,
+}
+
+/// Ends every in-progress utterance (per-source and mixed) and emits what
+/// is long enough, in `flush_order`.
+fn flush_all(state: &AudioCaptureState) {
+    let settings = state.settings.lock().map(|s| s.clone()).unwrap_or_default();
+    let mut utterances = Vec::new();
+    for source in ["mic", "loopback"] {
+        let chunks = state
+            .accumulator(source)
+            .and_then(|vad| vad.lock().ok())
+            .map(|mut vad| vad.force_flush(&settings));
+        utterances.push(chunks.unwrap_or_default());
+    }
+    let mixed = state
+        .mixer
+        .lock()
+        .ok()
+        .map(|mut mixer| mixer.vad.force_flush(&settings));
+    utterances.push(mixed.unwrap_or_default());
+    emit_flushed(state, utterances, settings.flush_order);
+}
+
+/// Emits utterances that ended in the same flush, given in source order
+/// (mic, loopback, mixed). `FlushOrder::Onset` sorts them by the first
+/// part's `onset_ms`, earliest first; the sort is stable, so equal onsets
+/// keep source order. An utterance's parts always stay together and in
+/// order, and with the queue full the policy drops from the front, so the
+/// earlier speech goes first there too.
+fn emit_flushed(state: &AudioCaptureState, mut utterances: Vec<Vec<AudioChunk>>, order: FlushOrder) {
+    utterances.retain(|chunks| !chunks.is_empty());
+    if order == FlushOrder::Onset {
+        utterances.sort_by_key(|chunks| chunks[0].onset_ms);
+    }
+    for chunk in utterances.into_iter().flatten() {
+        emit_chunk(state, chunk);
+    }
+}
+
+/// Payload of the `"privacy-paused"` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct PrivacyPause {
+    pub paused: bool,
+}
+
+/// Overall capture state, for status displays.
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureStatus {
+    pub running: bool,
+    /// Running, but dropping audio because the overlay is hidden
+    pub privacy_paused: bool,
+    pub capture_when_visible_only: bool,
+    pub session_id: String,
+    /// "device capture", "file source" or "clip recording"; `None` when idle
+    pub active_session: Option<&'static str>,
+}
+
+pub fn get_capture_status(state: &AudioCaptureState) -> CaptureStatus {
+    CaptureStatus {
+        running: state.running.load(Ordering::SeqCst),
+        privacy_paused: state.privacy_paused.load(Ordering::Relaxed),
+        capture_when_visible_only: state.settings.lock().is_ok_and(|s| s.capture_when_visible_only),
+        session_id: get_session_id(state),
+        active_session: state
+            .active_session
+            .lock()
+            .ok()
+            .and_then(|a| a.map(SessionKind::as_str)),
+    }
+}
+
+/// Turns visibility-gated capture on or off. Turning it off resumes a
+/// paused capture.
+pub fn set_capture_when_visible_only(state: &AudioCaptureState, enabled: bool) {
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.capture_when_visible_only = enabled;
+    }
+    if !enabled {
+        set_privacy_paused(state, false);
+    }
+}
+
+/// Reports whether the overlay window can be seen. Pauses or resumes
+/// capture if `capture_when_visible_only` is on.
+pub fn set_window_visible(state: &AudioCaptureState, visible: bool) {
+    if state.settings.lock().is_ok_and(|s| s.capture_when_visible_only) {
+        set_privacy_paused(state, !visible);
+    }
+}
+
+/// Pausing emits what was heard up to that point (it was captured while
+/// visible) and clears the speaking flags. The devices stay open, so
+/// resuming is instant.
+fn set_privacy_paused(state: &AudioCaptureState, paused: bool) {
+    if state.privacy_paused.swap(paused, Ordering::SeqCst) == paused {
+        return;
+    }
+    if paused {
+        flush_all(state);
+        state.mic_speaking.store(false, Ordering::Relaxed);
+        state.loopback_speaking.store(false, Ordering::Relaxed);
+    }
+    log::info!("Capture {} for privacy", if paused { "paused" } else { "resumed" });
+    emit_event(state, "privacy-paused", PrivacyPause { paused });
+}
+
+/// Stops capture, force-flushes any in-progress utterance, and writes chunks
+/// nobody polled yet to the recording directory (if one is set) so they
+/// aren't lost on quit. The caller still drops the stream handles.
+pub fn shutdown(state: &AudioCaptureState) -> ShutdownSummary {
+    stop_capture(state);
+    flush_all(state);
+
+    let pending = drain_chunks(state);
+    let mut saved_to = None;
+    let dir = state.recording_dir.lock().ok().and_then(|d| d.clone());
+    if let (Some(dir), false) = (dir, pending.is_empty()) {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let path = dir.join(format!("pending-chunks-{}.json", millis));
+        let written = serde_json::to_string(&pending)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()));
+        match written {
+            Ok(()) => saved_to = Some(path.display().to_string()),
+            Err(e) => log::error!("Failed to save pending chunks to {}: {}", path.display(), e),
+        }
+    }
+
+    let (mic_speech_secs, loopback_speech_secs) = get_talk_ratio(state);
+    let summary = ShutdownSummary {
+        chunks_emitted: state.next_seq.load(Ordering::SeqCst),
+        mic_speech_secs,
+        loopback_speech_secs,
+        pending_chunks: pending.len(),
+        saved_to,
+    };
+    log::info!(
+        "Capture shutdown: {} chunks emitted, mic {:.1}s / loopback {:.1}s of speech, {} pending ({})",
+        summary.chunks_emitted,
+        summary.mic_speech_secs,
+        summary.loopback_speech_secs,
+        summary.pending_chunks,
+        summary.saved_to.as_deref().unwrap_or("not saved")
+    );
+    summary
+}
+
+/// Sets (or clears) the directory `shutdown` writes unpolled chunks to.
+pub fn set_recording_dir(state: &AudioCaptureState, dir: Option<String>) -> Result<(), String> {
+    let dir = match dir {
+        Some(dir) => {
+            let path = std::path::PathBuf::from(dir);
+            if !path.is_dir() {
+                return Err(format!("{} is not a directory", path.display()));
+            }
+            Some(path)
+        }
+        None => None,
+    };
+    if let Ok(mut current) = state.recording_dir.lock() {
+        *current = dir;
+    }
+    Ok(())
+}
+
+/// Drains all pending audio chunks from the shared state.
+pub fn drain_chunks(state: &AudioCaptureState) -> Vec<AudioChunk> {
+    if let Ok(mut chunks) = state.chunks.lock() {
+        chunks.drain(..).collect()
+    } else {
+        Vec::new()
+    }
+}
+
+/// Labels `source` ("mic" or "loopback") with a speaker name for
+/// `AudioChunk.speaker` on chunks emitted from now on. An empty name
+/// clears it, back to the source name.
+pub fn set_source_speaker(state: &AudioCaptureState, source: &str, name: &str) -> Result<(), String> {
+    if !matches!(source, "mic" | "loopback") {
+        return Err(format!("Unknown source '{}' (expected \"mic\" or \"loopback\")", source));
+    }
+    let name = name.trim();
+    let mut speakers = state
+        .speakers
+        .lock()
+        .map_err(|_| "Speaker labels are unavailable".to_string())?;
+    if name.is_empty() {
+        speakers.remove(source);
+    } else {
+        speakers.insert(source.to_string(), name.to_string());
+    }
+    Ok(())
+}
+
+fn speaker_for(state: &AudioCaptureState, source: &str) -> Option<String> {
+    if !matches!(source, "mic" | "loopback") {
+        return None;
+    }
+    let label = state.speakers.lock().ok().and_then(|s| s.get(source).cloned());
+    Some(label.unwrap_or_else(|| source.to_string()))
+}
+
+/// Buffered chunks (the last `MAX_RECENT_CHUNKS`) attributed to `speaker`,
+/// oldest first.
+pub fn get_recent_chunks_by_speaker(state: &AudioCaptureState, speaker: &str) -> Vec<AudioChunk> {
+    state
+        .recent_chunks
+        .lock()
+        .map(|recent| {
+            recent
+                .values()
+                .filter(|c| c.speaker.as_deref() == Some(speaker))
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Drains at most `max` of the oldest pending chunks, leaving the rest for
+/// the next poll, so a backlog doesn't go over IPC in one payload.
+pub fn drain_chunks_limited(state: &AudioCaptureState, max: usize) -> Result<Vec<AudioChunk>, String> {
+    if max == 0 {
+        return Err("Poll limit must be at least 1 chunk".into());
+    }
+    Ok(match state.chunks.lock() {
+        Ok(mut chunks) => {
+            let take = chunks.len().min(max);
+            chunks.drain(..take).collect()
+        }
+        Err(_) => Vec::new(),
+    })
+}
+
+/// Id stamped on chunks from the current (or last) capture session.
+pub fn get_session_id(state: &AudioCaptureState) -> String {
+    state.session_id.lock().map(|id| id.clone()).unwrap_or_default()
+}
+
+/// Sets the wall-clock minimum chunk length (0 disables it).
+pub fn set_min_chunk_secs(state: &AudioCaptureState, secs: f32) -> Result<(), String> {
+    if !(0.0..=30.0).contains(&secs) {
+        return Err("Minimum chunk length must be between 0 and 30 seconds".into());
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.min_chunk_secs = secs;
+    }
+    Ok(())
+}
+
+/// Sets the largest base64 payload per chunk; longer utterances are split.
+pub fn set_max_chunk_b64_bytes(state: &AudioCaptureState, bytes: usize) -> Result<(), String> {
+    if !(MIN_CHUNK_B64_BYTES..=MAX_CHUNK_B64_BYTES).contains(&bytes) {
+        return Err(format!(
+            "Chunk size limit must be between {} and {} bytes",
+            MIN_CHUNK_B64_BYTES, MAX_CHUNK_B64_BYTES
+        ));
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.max_chunk_b64_bytes = bytes;
+    }
+    Ok(())
+}
+
+/// Turns downmix gain compensation on or off. `gain` overrides the default
+/// sqrt(channels) factor.
+pub fn set_downmix_compensation(state: &AudioCaptureState, enabled: bool, gain: Option<f32>) -> Result<(), String> {
+    if gain.is_some_and(|g| !(MIN_DOWNMIX_GAIN..=MAX_DOWNMIX_GAIN).contains(&g)) {
+        return Err(format!(
+            "Downmix gain must be between {} and {}",
+            MIN_DOWNMIX_GAIN, MAX_DOWNMIX_GAIN
+        ));
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.downmix_compensation = enabled;
+        settings.downmix_gain = gain;
+    }
+    Ok(())
+}
+
+/// Turns automatic first-channel downmix for phase-inverted stereo on or
+/// off. Streams that already switched keep their downmix until restarted.
+pub fn set_phase_autocorrect(state: &AudioCaptureState, enabled: bool) {
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.phase_autocorrect = enabled;
+    }
+}
+
+/// Turns the per-chunk `energy_trace` on or off.
+pub fn set_energy_trace(state: &AudioCaptureState, enabled: bool) {
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.energy_trace = enabled;
+    }
+}
+
+/// Clean slate without reopening devices: clears both accumulators and the
+/// mixer (restarting warm-up), the speaking flags, talk-ratio totals,
+/// utterance averages and history, and the chunk queue. Streams keep
+/// running. The chunk queue stays locked throughout so nothing lands
+/// half-way through.
+pub fn reset_capture_state(state: &AudioCaptureState) {
+    let Ok(mut chunks) = state.chunks.lock() else { return };
+    state.generation.fetch_add(1, Ordering::SeqCst);
+
+    for source in ["mic", "loopback"] {
+        if let Some(Ok(mut vad)) = state.accumulator(source).map(|v| v.lock()) {
+            vad.reset();
+        }
+    }
+    if let Ok(mut mixer) = state.mixer.lock() {
+        mixer.reset();
+    }
+    if let Ok(mut aec) = state.echo_canceller.lock() {
+        aec.reset();
+    }
+    state.mic_speaking.store(false, Ordering::Relaxed);
+    state.loopback_speaking.store(false, Ordering::Relaxed);
+
+    for secs in [&state.mic_speech_secs, &state.loopback_speech_secs] {
+        if let Ok(mut secs) = secs.lock() {
+            *secs = 0.0;
+        }
+    }
+    for tally in [&state.mic_utterances, &state.loopback_utterances] {
+        if let Ok(mut tally) = tally.lock() {
+            *tally = UtteranceTally::default();
+        }
+    }
+    if let Ok(mut history) = state.talk_history.lock() {
+        history.clear();
+    }
+    chunks.clear();
+}
+
+/// Fixes auto-detected roles that came out reversed, without reopening
+/// devices: the accumulators fed by the two streams trade `source` labels
+/// (and with them per-source VAD settings), and the talk-ratio totals and
+/// history are swapped to match. Calling it again swaps back; the next
+/// start reverts to the detected roles. Returns the device now behind each
+/// label.
+pub fn swap_sources(state: &AudioCaptureState) -> Result<DeviceSelection, String> {
+    if !state.running.load(Ordering::Relaxed) {
+        return Err("Audio capture is not running".into());
+    }
+    let mixed = state
+        .capture_mode
+        .lock()
+        .is_ok_and(|m| *m == CaptureMode::MixedMono);
+    if mixed {
+        return Err("Sources can't be swapped in mixed mode".into());
+    }
+    for source in ["mic", "loopback"] {
+        let called_back = state
+            .last_callback(source)
+            .is_some_and(|t| t.load(Ordering::Relaxed) != 0);
+        if !called_back {
+            return Err(format!("The {} stream isn't delivering audio; nothing to swap", source));
+        }
+    }
+
+    // Hold the queue so no chunk is relabeled half-way
+    let Ok(_chunks) = state.chunks.lock() else {
+        return Err("Chunk queue unavailable".into());
+    };
+    let (Ok(mut mic), Ok(mut loopback)) = (state.mic_vad.lock(), state.loopback_vad.lock()) else {
+        return Err("Accumulators unavailable".into());
+    };
+    std::mem::swap(&mut mic.source_label, &mut loopback.source_label);
+    // A frame in flight may not have refreshed the flags yet
+    state.mic_speaking.store(loopback.is_speaking, Ordering::Relaxed);
+    state.loopback_speaking.store(mic.is_speaking, Ordering::Relaxed);
+
+    if let (Ok(mut mic_secs), Ok(mut loopback_secs)) =
+        (state.mic_speech_secs.lock(), state.loopback_speech_secs.lock())
+    {
+        std::mem::swap(&mut *mic_secs, &mut *loopback_secs);
+    }
+    if let (Ok(mut mic_tally), Ok(mut loopback_tally)) =
+        (state.mic_utterances.lock(), state.loopback_utterances.lock())
+    {
+        std::mem::swap(&mut *mic_tally, &mut *loopback_tally);
+    }
+    if let Ok(mut history) = state.talk_history.lock() {
+        for record in history.iter_mut() {
+            record.source = match record.source.as_str() {
+                "mic" => "loopback".into(),
+                "loopback" => "mic".into(),
+                _ => continue,
+            };
+        }
+    }
+
+    log::info!(
+        "Swapped sources: mic is now '{}', loopback is now '{}'",
+        loopback.device_name,
+        mic.device_name
+    );
+    Ok(DeviceSelection {
+        mic: loopback.device_name.clone(),
+        loopback: mic.device_name.clone(),
+    })
+}
+
+/// Breakdown returned by `measure_latency`.
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyReport {
+    /// Callback size the simulation fed frames at: `buffer_ms`, or 10 ms
+    /// before any stream has run
+    pub frame_ms: f32,
+    /// Audio length of the mic's latest callback buffer, at the device's
+    /// rate; `None` before the first callback
+    pub buffer_ms: Option<f32>,
+    /// Audio time from the end of the impulse until the VAD emitted it;
+    /// dominated by the endpoint silence
+    pub vad_endpoint_ms: f32,
+    /// Wall time spent processing the synthetic signal
+    pub processing_ms: f32,
+    /// Sum of the above: end of speech to chunk emission
+    pub total_ms: f32,
+}
+
+const LATENCY_IMPULSE_SECS: f32 = 0.5;
+const LATENCY_MAX_TAIL_SECS: f32 = 10.0;
+
+/// Feeds a synthetic speech-level burst through a fresh accumulator with the
+/// current settings and measures how long after the burst ends its chunk
+/// comes out. Covers the software path (VAD endpointing, frame and buffer
+/// size), not the acoustic or driver path, and doesn't touch the live
+/// accumulators.
+pub fn measure_latency(state: &AudioCaptureState) -> Result<LatencyReport, String> {
+    let settings = state.settings.lock().map(|s| s.clone()).unwrap_or_default();
+    let rate = settings.mic_target_rate;
+    let buffer_ms = Some(state.mic_callback_us.load(Ordering::Relaxed))
+        .filter(|&us| us > 0)
+        .map(|us| us as f32 / 1000.0);
+    let frame_ms = buffer_ms.unwrap_or(10.0);
+    let frame_len = ((frame_ms / 1000.0 * rate as f32) as usize).max(1);
+
+    let mut vad = VadAccumulator::new("mic").with_rate(rate);
+    let silence = vec![0.0f32; frame_len];
+    let started = Instant::now();
+
+    // Get through warm-up so it doesn't swallow the impulse
+    let warmup_frames = vad.ms_to_samples(settings.warmup_ms).div_ceil(frame_len);
+    for _ in 0..warmup_frames {
+        vad.feed(&silence, &settings);
+    }
+
+    let impulse_len = (LATENCY_IMPULSE_SECS * rate as f32) as usize;
+    let impulse: Vec<f32> = (0..impulse_len)
+        .map(|i| 0.1 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / rate as f32).sin())
+        .collect();
+    for frame in impulse.chunks(frame_len) {
+        if !vad.feed(frame, &settings).is_empty() {
+            return Err("VAD emitted before the impulse ended; check the endpoint settings".into());
+        }
+    }
+
+    let max_tail_frames = (LATENCY_MAX_TAIL_SECS * rate as f32) as usize / frame_len;
+    let tail_frames = (1..=max_tail_frames)
+        .find(|_| !vad.feed(&silence, &settings).is_empty())
+        .ok_or("The impulse was never emitted (below the VAD threshold or minimum length?)")?;
+    let processing_ms = started.elapsed().as_secs_f32() * 1000.0;
+
+    let vad_endpoint_ms = (tail_frames * frame_len) as f32 * 1000.0 / rate as f32;
+    Ok(LatencyReport {
+        frame_ms,
+        buffer_ms,
+        vad_endpoint_ms,
+        processing_ms,
+        total_ms: buffer_ms.unwrap_or(0.0) + vad_endpoint_ms + processing_ms,
+    })
+}
+
+/// One source's live share of the delay from sound to emitted chunk, in ms.
+#[derive(Debug, Clone, Serialize)]
+pub struct StageLatency {
+    /// Audio length of the device's latest callback buffer; `None` before
+    /// the first callback
+    pub buffer_ms: Option<f32>,
+    /// Resampler group delay. Always 0: linear interpolation weighs the two
+    /// samples around each output instant, so it filters (slightly) but
+    /// doesn't delay
+    pub resampler_ms: f32,
+    /// Silence the VAD waits for before ending an utterance; the window
+    /// length in continuous recording, 0 for push-to-talk
+    pub vad_endpoint_ms: f32,
+    /// Shorter endpoint used after a rising-energy tail, when configured
+    /// below `vad_endpoint_ms`
+    pub vad_short_endpoint_ms: Option<f32>,
+    /// Window after each flush in which a new utterance only opens on
+    /// sustained speech. Held frames are kept, so it gates rather than
+    /// delays and isn't part of `total_ms`; 0 where it doesn't apply
+    pub vad_cooldown_ms: f32,
+    /// Wall time the latest chunk took to encode; `None` before the first
+    pub encode_ms: Option<f32>,
+    /// Buffer, endpoint and encode time, with the full endpoint; a rising
+    /// tail ends that much sooner with the short one
+    pub total_ms: f32,
+}
+
+/// Result of `get_latency_breakdown`.
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyBreakdown {
+    pub mic: StageLatency,
+    pub loopback: StageLatency,
+}
+
+/// Current per-stage latency for each source, from the running streams and
+/// settings. Unlike `measure_latency` it simulates nothing, so it's cheap
+/// enough to poll while tuning.
+pub fn get_latency_breakdown(state: &AudioCaptureState) -> LatencyBreakdown {
+    let settings = state.settings.lock().map(|s| s.clone()).unwrap_or_default();
+    let stage = |source: &str| {
+        let buffer_ms = state
+            .callback_us(source)
+            .map(|us| us.load(Ordering::Relaxed))
+            .filter(|&us| us > 0)
+            .map(|us| us as f32 / 1000.0);
+        let ptt = settings.ptt_mode && source == "mic";
+        let endpointed = !(settings.continuous_record || ptt);
+        let vad_endpoint_ms = if settings.continuous_record {
+            settings.continuous_window_secs as f32 * 1000.0
+        } else if ptt {
+            0.0
+        } else {
+            settings.endpoint_silence_ms as f32
+        };
+        let vad_short_endpoint_ms = (endpointed && settings.endpoint_short_silence_ms < settings.endpoint_silence_ms)
+            .then_some(settings.endpoint_short_silence_ms as f32);
+        let vad_cooldown_ms = if endpointed { settings.cooldown_ms as f32 } else { 0.0 };
+        let encode_ms = state
+            .accumulator(source)
+            .and_then(|vad| vad.lock().ok())
+            .and_then(|vad| vad.last_encode_ms);
+        StageLatency {
+            buffer_ms,
+            resampler_ms: 0.0,
+            vad_endpoint_ms,
+            vad_short_endpoint_ms,
+            vad_cooldown_ms,
+            encode_ms,
+            total_ms: buffer_ms.unwrap_or(0.0) + vad_endpoint_ms + encode_ms.unwrap_or(0.0),
+        }
+    };
+    LatencyBreakdown {
+        mic: stage("mic"),
+        loopback: stage("loopback"),
+    }
+}
+
+/// Per-source channel counts; `effective_channels` below `channels` means
+/// the device duplicates channels (e.g. a "stereo" virtual cable carrying
+/// mono), so selecting a single channel loses nothing.
+pub fn get_channel_stats(state: &AudioCaptureState) -> ChannelReport {
+    let read = |stats: &Mutex<ChannelStats>| stats.lock().map(|s| s.clone()).unwrap_or_default();
+    ChannelReport {
+        mic: read(&state.mic_channel_stats),
+        loopback: read(&state.loopback_channel_stats),
+    }
+}
+
+/// Overruns per source since the current (or last) session started.
+pub fn get_xrun_stats(state: &AudioCaptureState) -> XrunStats {
+    let source = |counters: &XrunCounters| SourceXruns {
+        xruns: counters.xruns.load(Ordering::Relaxed),
+        dropped_frames: counters.dropped_frames.load(Ordering::Relaxed),
+    };
+    XrunStats {
+        mic: source(&state.mic_xruns),
+        loopback: source(&state.loopback_xruns),
+    }
+}
+
+/// Reports per-source stream liveness. `held` comes from the caller, which
+/// owns the stream handles; a held stream with no recent callback is dead
+/// even while `running` is still set.
+pub fn stream_health(state: &AudioCaptureState, held: bool) -> StreamHealth {
+    let devices = state.last_devices.lock().ok().and_then(|d| d.clone());
+    let now = state.epoch.elapsed().as_nanos() as u64;
+    let source = |label: &str, device: Option<String>| {
+        let last = state
+            .last_callback(label)
+            .map(|t| t.load(Ordering::Relaxed))
+            .unwrap_or(0);
+        let last_callback_ms_ago = (last != 0).then(|| now.saturating_sub(last) / 1_000_000);
+        SourceHealth {
+            held,
+            device: device.filter(|_| held),
+            last_callback_ms_ago,
+            stale: held && last_callback_ms_ago.map_or(true, |ms| ms > STREAM_STALE_MS),
+        }
+    };
+    StreamHealth {
+        running: state.running.load(Ordering::Relaxed),
+        mic: source("mic", devices.as_ref().map(|d| d.mic.clone())),
+        loopback: source("loopback", devices.map(|d| d.loopback)),
+    }
+}
+
+/// Queue health and what the backpressure policy has done so far.
+#[derive(Debug, Clone, Serialize)]
+pub struct BufferStats {
+    pub queued: usize,
+    /// Queue length where the policy acts
+    pub capacity: usize,
+    pub policy: String,
+    pub dropped: u64,
+    pub coalesced: u64,
+}
+
+pub fn get_buffer_stats(state: &AudioCaptureState) -> BufferStats {
+    let policy = state
+        .backpressure
+        .lock()
+        .map(|p| *p)
+        .unwrap_or(BackpressurePolicy::Buffer);
+    BufferStats {
+        queued: state.chunks.lock().map(|c| c.len()).unwrap_or(0),
+        capacity: MAX_QUEUED_CHUNKS,
+        policy: policy.as_str().to_string(),
+        dropped: state.dropped_chunks.load(Ordering::Relaxed),
+        coalesced: state.coalesced_chunks.load(Ordering::Relaxed),
+    }
+}
+
+/// Sets what happens when the poll queue is full ("buffer", "drop-oldest"
+/// or "coalesce").
+pub fn set_backpressure_policy(state: &AudioCaptureState, policy: &str) -> Result<(), String> {
+    let policy = BackpressurePolicy::parse(policy)?;
+    if let Ok(mut current) = state.backpressure.lock() {
+        *current = policy;
+    }
+    Ok(())
+}
+
+/// Every user-tunable audio setting, as exported by `export_settings`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsProfile {
+    pub settings: AudioSettings,
+    /// "per-source" or "mixed-mono"
+    pub capture_mode: String,
+    /// "buffer", "drop-oldest" or "coalesce"
+    pub backpressure: String,
+    pub mic_gain: f32,
+    pub loopback_gain: f32,
+    /// Chosen loopback device; `None` auto-detects
+    pub loopback_device: Option<String>,
+}
+
+/// What `import_settings` couldn't apply right away.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    /// Changed device-level settings that take effect on the next start
+    pub restart_required: Vec<String>,
+    /// Settings this platform doesn't support, left at their defaults
+    pub ignored: Vec<String>,
+}
+
+pub fn export_settings(state: &AudioCaptureState) -> SettingsProfile {
+    let (mic_gain, loopback_gain) = state
+        .mixer
+        .lock()
+        .map(|m| (m.mic_gain, m.loopback_gain))
+        .unwrap_or((1.0, 1.0));
+    SettingsProfile {
+        settings: state.settings.lock().map(|s| s.clone()).unwrap_or_default(),
+        capture_mode: state
+            .capture_mode
+            .lock()
+            .map(|m| m.as_str())
+            .unwrap_or("per-source")
+            .to_string(),
+        backpressure: state
+            .backpressure
+            .lock()
+            .map(|p| p.as_str())
+            .unwrap_or("buffer")
+            .to_string(),
+        mic_gain,
+        loopback_gain,
+        loopback_device: state.loopback_device.lock().ok().and_then(|d| d.clone()),
+    }
+}
+
+/// Applies a profile from `export_settings` all at once: everything is
+/// validated first, so an invalid profile changes nothing. Works while
+/// capturing, except that target rates can't change mid-stream; device-level
+/// settings (buffer size, input format, loopback device) are stored and
+/// listed in the report as waiting for a restart.
+pub fn import_settings(state: &AudioCaptureState, json: &str) -> Result<ImportReport, String> {
+    let profile: SettingsProfile =
+        serde_json::from_str(json).map_err(|e| format!("Invalid settings profile: {}", e))?;
+    profile.settings.validate()?;
+    let capture_mode = CaptureMode::parse(&profile.capture_mode)?;
+    let backpressure = BackpressurePolicy::parse(&profile.backpressure)?;
+    let gains_ok = profile.mic_gain.is_finite() && profile.loopback_gain.is_finite();
+    if !gains_ok || profile.mic_gain < 0.0 || profile.loopback_gain < 0.0 {
+        return Err("Mix gains must be finite and non-negative".into());
+    }
+
+    let mut report = ImportReport {
+        restart_required: Vec::new(),
+        ignored: Vec::new(),
+    };
+
+    let current = export_settings(state);
+    if state.running.load(Ordering::SeqCst) {
+        let rates_changed = profile.settings.mic_target_rate != current.settings.mic_target_rate
+            || profile.settings.loopback_target_rate != current.settings.loopback_target_rate;
+        if rates_changed {
+            return Err("Stop capture before importing a profile with different target rates".into());
+        }
+        if profile.settings.buffer_frames != current.settings.buffer_frames {
+            report.restart_required.push("buffer_frames".into());
+        }
+        if profile.settings.preferred_input_config != current.settings.preferred_input_config {
+            report.restart_required.push("preferred_input_config".into());
+        }
+        if profile.loopback_device != current.loopback_device {
+            report.restart_required.push("loopback_device".into());
+        }
+    }
+
+    if let Ok(mut settings) = state.settings.lock() {
+        *settings = profile.settings;
+    }
+    if profile.capture_mode != current.capture_mode {
+        if let Ok(mut mode) = state.capture_mode.lock() {
+            *mode = capture_mode;
+        }
+        if let Ok(mut mixer) = state.mixer.lock() {
+            mixer.reset();
+        }
+    }
+    if let Ok(mut policy) = state.backpressure.lock() {
+        *policy = backpressure;
+    }
+    if let Ok(mut mixer) = state.mixer.lock() {
+        mixer.mic_gain = profile.mic_gain;
+        mixer.loopback_gain = profile.loopback_gain;
+    }
+    if let Ok(mut device) = state.loopback_device.lock() {
+        *device = profile.loopback_device;
+    }
+    Ok(report)
+}
+
+/// Switches the mic between VAD and push-to-talk capture. Turning it off
+/// mid-press leaves the utterance to the VAD to end.
+pub fn set_ptt_mode(state: &AudioCaptureState, enabled: bool) {
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.ptt_mode = enabled;
+    }
+    if !enabled {
+        state.ptt_active.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Presses or releases push-to-talk. Takes effect on the next callback; has
+/// no effect unless `ptt_mode` is on.
+pub fn set_ptt(state: &AudioCaptureState, active: bool) -> Result<(), String> {
+    if !state.settings.lock().is_ok_and(|s| s.ptt_mode) {
+        return Err("Push-to-talk mode is off".into());
+    }
+    state.ptt_active.store(active, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Turns VAD transition logging on or off. Turning it off keeps what was
+/// logged; `clear` empties the logs.
+pub fn set_vad_log(state: &AudioCaptureState, enabled: bool, clear: bool) {
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.vad_log = enabled;
+    }
+    if clear {
+        for source in ["mic", "loopback"] {
+            if let Some(Ok(mut vad)) = state.accumulator(source).map(|v| v.lock()) {
+                vad.log.clear();
+            }
+        }
+        if let Ok(mut mixer) = state.mixer.lock() {
+            mixer.vad.log.clear();
+        }
+    }
+}
+
+/// The last `MAX_VAD_LOG` VAD transitions of each accumulator in the
+/// current session, for tracing why an utterance was cut where it was.
+pub fn get_vad_log(state: &AudioCaptureState) -> VadLog {
+    let log_of = |source: &str| {
+        state
+            .accumulator(source)
+            .and_then(|v| v.lock().ok())
+            .map(|v| v.log.iter().cloned().collect())
+            .unwrap_or_default()
+    };
+    VadLog {
+        enabled: state.settings.lock().is_ok_and(|s| s.vad_log),
+        mic: log_of("mic"),
+        loopback: log_of("loopback"),
+        mixed: state
+            .mixer
+            .lock()
+            .map(|m| m.vad.log.iter().cloned().collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// Returns the live per-source speaking flags, without waiting for a chunk.
+pub fn get_speaking_state(state: &AudioCaptureState) -> SpeakingState {
+    SpeakingState {
+        mic: state.mic_speaking.load(Ordering::Relaxed),
+        loopback: state.loopback_speaking.load(Ordering::Relaxed),
+    }
+}
+
+/// Includes or excludes a source from the talk ratio (and the balance score
+/// and history built on it). Excluded chunks still emit. Totals already
+/// counted are kept.
+pub fn set_source_counts_in_ratio(state: &AudioCaptureState, source: &str, counts: bool) -> Result<(), String> {
+    let Ok(mut settings) = state.settings.lock() else {
+        return Err("Settings unavailable".into());
+    };
+    match source {
+        "mic" => settings.mic_counts_in_ratio = counts,
+        "loopback" => settings.loopback_counts_in_ratio = counts,
+        other => return Err(format!("Unknown source '{}' (expected \"mic\" or \"loopback\")", other)),
+    }
+    Ok(())
+}
+
+/// Debounced live indicator: true while either source is speaking and for
+/// `speaking_hold_ms` afterwards, so it doesn't flicker between words.
+pub fn anyone_speaking(state: &AudioCaptureState) -> bool {
+    if state.mic_speaking.load(Ordering::Relaxed) || state.loopback_speaking.load(Ordering::Relaxed) {
+        return true;
+    }
+    let last = state.last_speaking_nanos.load(Ordering::Relaxed);
+    if last == 0 {
+        return false;
+    }
+    let hold_ms = state.settings.lock().map(|s| s.speaking_hold_ms).unwrap_or(0);
+    let since = (state.epoch.elapsed().as_nanos() as u64).saturating_sub(last);
+    since < hold_ms as u64 * 1_000_000
+}
+
+/// Sets the `anyone_speaking` trailing hold (0 disables it).
+pub fn set_speaking_hold(state: &AudioCaptureState, ms: u32) -> Result<(), String> {
+    if ms > MAX_SPEAKING_HOLD_MS {
+        return Err(format!("Speaking hold must be at most {} ms", MAX_SPEAKING_HOLD_MS));
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.speaking_hold_ms = ms;
+    }
+    Ok(())
+}
+
+/// Enables the `"wake"` event and sets how long the mic must speak, after at
+/// least `WAKE_MIN_SILENCE_MS` of silence, before it fires.
+pub fn set_wake_trigger(state: &AudioCaptureState, enabled: bool, speech_ms: u32) -> Result<(), String> {
+    if !WAKE_SPEECH_MS_RANGE.contains(&speech_ms) {
+        return Err(format!(
+            "Wake speech duration must be between {} and {} ms",
+            WAKE_SPEECH_MS_RANGE.start(),
+            WAKE_SPEECH_MS_RANGE.end()
+        ));
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.wake_trigger = enabled;
+        settings.wake_speech_ms = speech_ms;
+    }
+    Ok(())
+}
+
+/// Returns (mic_secs, loopback_secs) for talk-ratio calculation.
+pub fn get_talk_ratio(state: &AudioCaptureState) -> (f32, f32) {
+    let mic = state
+        .mic_speech_secs
+        .lock()
+        .map(|s| *s)
+        .unwrap_or(0.0);
+    let loopback = state
+        .loopback_speech_secs
+        .lock()
+        .map(|s| *s)
+        .unwrap_or(0.0);
+    (mic, loopback)
+}
+
+/// Talk ratio folded into one number for the UI.
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceScore {
+    /// 0–100: 50 is balanced, above 50 mic-heavy, below 50 loopback-heavy
+    pub score: f32,
+    pub mic_speech_secs: f32,
+    pub loopback_speech_secs: f32,
+}
+
+/// Balance score from the talk ratio. `BALANCE_PRIOR_SECS` of imaginary,
+/// evenly split speech is added to both sides, so the first few utterances
+/// nudge the score instead of swinging it to 0 or 100; with no speech yet
+/// the score is 50.
+pub fn get_balance_score(state: &AudioCaptureState) -> BalanceScore {
+    let (mic, loopback) = get_talk_ratio(state);
+    let half_prior = BALANCE_PRIOR_SECS / 2.0;
+    let score = 100.0 * (mic + half_prior) / (mic + loopback + BALANCE_PRIOR_SECS);
+    BalanceScore {
+        score,
+        mic_speech_secs: mic,
+        loopback_speech_secs: loopback,
+    }
+}
+
+/// Running utterance count and length for one source.
+#[derive(Debug, Clone, Copy, Default)]
+struct UtteranceTally {
+    count: u64,
+    total_secs: f64,
+}
+
+impl UtteranceTally {
+    /// Every part adds its length; the last part completes the utterance.
+    fn add(&mut self, chunk: &AudioChunk) {
+        self.total_secs += chunk.duration_secs as f64;
+        if chunk.part + 1 == chunk.parts {
+            self.count += 1;
+        }
+    }
+
+    fn average(&self) -> UtteranceAverage {
+        UtteranceAverage {
+            count: self.count,
+            avg_secs: if self.count == 0 {
+                0.0
+            } else {
+                (self.total_secs / self.count as f64) as f32
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UtteranceAverage {
+    /// Utterances counted (a split utterance counts once)
+    pub count: u64,
+    /// 0 until the first utterance
+    pub avg_secs: f32,
+}
+
+/// Result of `get_avg_utterance_length`.
+#[derive(Debug, Clone, Serialize)]
+pub struct UtteranceLengths {
+    pub mic: UtteranceAverage,
+    pub loopback: UtteranceAverage,
+}
+
+/// Average utterance length per source, over the same utterances as the
+/// talk ratio (and reset with it): long monologues versus short answers.
+pub fn get_avg_utterance_length(state: &AudioCaptureState) -> UtteranceLengths {
+    let average = |tally: &Mutex<UtteranceTally>| tally.lock().map(|t| *t).unwrap_or_default().average();
+    UtteranceLengths {
+        mic: average(&state.mic_utterances),
+        loopback: average(&state.loopback_utterances),
+    }
+}
+
+/// Switches between per-source and mixed-mono chunking. Takes effect on the
+/// next audio callback.
+pub fn set_capture_mode(state: &AudioCaptureState, mode: &str) -> Result<(), String> {
+    let mode = CaptureMode::parse(mode)?;
+    if let Ok(mut current) = state.capture_mode.lock() {
+        *current = mode;
+    }
+    if let Ok(mut mixer) = state.mixer.lock() {
+        mixer.reset();
+    }
+    Ok(())
+}
+
+/// Sets the linear gains applied to each source before mixing.
+pub fn set_mix_gains(state: &AudioCaptureState, mic_gain: f32, loopback_gain: f32) -> Result<(), String> {
+    if !(mic_gain.is_finite() && loopback_gain.is_finite()) || mic_gain < 0.0 || loopback_gain < 0.0 {
+        return Err("Mix gains must be finite and non-negative".into());
+    }
+    if let Ok(mut mixer) = state.mixer.lock() {
+        mixer.mic_gain = mic_gain;
+        mixer.loopback_gain = loopback_gain;
+    }
+    Ok(())
+}
+
+/// Attaches downstream metadata to an already-emitted chunk. If the chunk is
+/// still waiting to be polled it carries the metadata itself; either way the
+/// annotation is kept for the most recent `MAX_ANNOTATIONS` chunks.
+pub fn annotate_chunk(state: &AudioCaptureState, seq: u64, meta: serde_json::Value) -> Result<(), String> {
+    if seq >= state.next_seq.load(Ordering::SeqCst) {
+        return Err(format!("Chunk {} has not been emitted yet", seq));
+    }
+
+    if let Ok(mut chunks) = state.chunks.lock() {
+        if let Some(chunk) = chunks.iter_mut().find(|c| c.seq == seq) {
+            chunk.meta = Some(meta.clone());
+        }
+    }
+
+    if let Ok(mut annotations) = state.annotations.lock() {
+        annotations.insert(seq, meta);
+        while annotations.len() > MAX_ANNOTATIONS {
+            annotations.pop_first();
+        }
+    }
+    Ok(())
+}
+
+/// Returns the annotation stored for `seq`, if it is still retained.
+pub fn get_chunk_meta(state: &AudioCaptureState, seq: u64) -> Option<serde_json::Value> {
+    state
+        .annotations
+        .lock()
+        .ok()
+        .and_then(|annotations| annotations.get(&seq).cloned())
+}
+
+/// Magnitude spectrogram of one chunk, returned by `compute_spectrogram`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Spectrogram {
+    pub seq: u64,
+    pub sample_rate: u32,
+    pub n_fft: usize,
+    pub hop: usize,
+    /// One row per hop, `n_fft / 2 + 1` linear magnitudes (DC to Nyquist)
+    pub frames: Vec<Vec<f32>>,
+}
+
+/// Decodes a chunk's base64 PCM back to f32 samples.
+fn decode_chunk_pcm(chunk: &AudioChunk) -> Result<Vec<f32>, String> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(&chunk.audio_b64)
+        .map_err(|e| format!("Chunk {} has invalid audio data: {}", chunk.seq, e))?;
+    let header_len = Container::parse(&chunk.container)?.header_len();
+    let bytes = bytes.get(header_len..).unwrap_or_default();
+    Ok(match chunk.sample_type.as_str() {
+        "f32" => bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+        _ => bytes
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / i16::MAX as f32)
+            .collect(),
+    })
+}
+
+/// Hann-windowed STFT magnitudes of a recently emitted chunk. Errors if the
+/// chunk has aged out of the last `MAX_RECENT_CHUNKS`.
+pub fn compute_spectrogram(state: &AudioCaptureState, seq: u64, n_fft: usize, hop: usize) -> Result<Spectrogram, String> {
+    if !(MIN_FFT_SIZE..=MAX_FFT_SIZE).contains(&n_fft) {
+        return Err(format!("n_fft must be between {} and {}", MIN_FFT_SIZE, MAX_FFT_SIZE));
+    }
+    if hop == 0 || hop > n_fft {
+        return Err("hop must be between 1 and n_fft".into());
+    }
+    let chunk = state
+        .recent_chunks
+        .lock()
+        .ok()
+        .and_then(|recent| recent.get(&seq).cloned())
+        .ok_or_else(|| format!("Chunk {} is no longer buffered", seq))?;
+    let samples = decode_chunk_pcm(&chunk)?;
+
+    let window: Vec<f32> = (0..n_fft)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / n_fft as f32).cos())
+        .collect();
+    let fft = rustfft::FftPlanner::new().plan_fft_forward(n_fft);
+    let mut buffer = vec![rustfft::num_complex::Complex::new(0.0f32, 0.0); n_fft];
+
+    let mut frames = Vec::new();
+    let mut start = 0;
+    while start + n_fft <= samples.len() {
+        for ((slot, &s), &w) in buffer.iter_mut().zip(&samples[start..start + n_fft]).zip(&window) {
+            *slot = rustfft::num_complex::Complex::new(s * w, 0.0);
+        }
+        fft.process(&mut buffer);
+        frames.push(buffer[..=n_fft / 2].iter().map(|c| c.norm()).collect());
+        start += hop;
+    }
+
+    Ok(Spectrogram {
+        seq,
+        sample_rate: chunk.sample_rate,
+        n_fft,
+        hop,
+        frames,
+    })
+}
+
+/// Returns a copy of a recently emitted chunk resampled to `target_rate`,
+/// keeping its seq, session, timing and annotations, and its own sample
+/// type and container. Uses the capture path's resampler. Errors if the
+/// chunk has aged out of the last `MAX_RECENT_CHUNKS`.
+pub fn resample_chunk(state: &AudioCaptureState, seq: u64, target_rate: u32) -> Result<AudioChunk, String> {
+    if !(MIN_TARGET_RATE..=MAX_TARGET_RATE).contains(&target_rate) {
+        return Err(format!(
+            "Target rate must be between {} and {} Hz",
+            MIN_TARGET_RATE, MAX_TARGET_RATE
+        ));
+    }
+    let chunk = state
+        .recent_chunks
+        .lock()
+        .ok()
+        .and_then(|recent| recent.get(&seq).cloned())
+        .ok_or_else(|| format!("Chunk {} is no longer buffered", seq))?;
+    let samples = decode_chunk_pcm(&chunk)?;
+
+    let (mut mono, mut resampled) = (Vec::new(), Vec::new());
+    to_mono_into(&samples, 1, chunk.sample_rate, target_rate, Downmix::Average, &mut mono, &mut resampled);
+
+    // Encode like the original; the limiter already ran when it was captured
+    let mut settings = state.settings.lock().map(|s| s.clone()).unwrap_or_default();
+    settings.sample_type = SampleType::parse(&chunk.sample_type)?;
+    settings.container = Container::parse(&chunk.container)?;
+    settings.limiter = false;
+    let encoded = encode_chunk(
+        &resampled,
+        target_rate,
+        &chunk.source,
+        &chunk.device_name,
+        &chunk.layout,
+        &settings,
+    );
+    Ok(AudioChunk {
+        audio_b64: encoded.audio_b64,
+        duration_secs: encoded.duration_secs,
+        sample_count: encoded.sample_count,
+        sample_rate: target_rate,
+        ..chunk
+    })
+}
+
+/// Enables or disables noise-floor tracking. With adaptive mode on, the
+/// threshold follows ambient noise × `margin`, bounded by the configured
+/// min/max thresholds.
+pub fn set_adaptive_vad(state: &AudioCaptureState, enabled: bool, margin: f32) -> Result<(), String> {
+    if !margin.is_finite() || margin < 1.0 {
+        return Err("Adaptive VAD margin must be at least 1.0".into());
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.adaptive = enabled;
+        settings.adaptive_margin = margin;
+    }
+    Ok(())
+}
+
+/// Tunes loopback-only rejection: `threshold_scale` multiplies the loopback
+/// energy threshold and `min_speech_frames` sets how long loopback speech
+/// must last before it becomes a chunk. The mic keeps the defaults.
+pub fn set_loopback_rejection(
+    state: &AudioCaptureState,
+    threshold_scale: f32,
+    min_speech_frames: usize,
+) -> Result<(), String> {
+    if !threshold_scale.is_finite() || threshold_scale < 1.0 {
+        return Err("Loopback threshold scale must be at least 1.0".into());
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.loopback_threshold_scale = threshold_scale;
+        settings.loopback_min_speech_frames = min_speech_frames;
+    }
+    Ok(())
+}
+
+/// Writes the per-utterance talk history as CSV
+/// (`seq,source,start_ts_ms,duration_secs`).
+pub fn export_talk_history_csv(state: &AudioCaptureState, path: &str) -> Result<usize, String> {
+    let history = state
+        .talk_history
+        .lock()
+        .map_err(|_| "Talk history is unavailable".to_string())?;
+
+    let mut csv = String::from("seq,source,start_ts_ms,duration_secs\n");
+    for record in history.iter() {
+        csv.push_str(&format!(
+            "{},{},{},{:.3}\n",
+            record.seq, record.source, record.start_ts_ms, record.duration_secs
+        ));
+    }
+
+    std::fs::write(path, csv).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(history.len())
+}
+
+/// Speech per source in one fixed slice of the talk history.
+#[derive(Debug, Clone, Serialize)]
+pub struct TalkBucket {
+    /// Milliseconds since the UNIX epoch; a multiple of the bucket length
+    pub bucket_start: u64,
+    pub mic_secs: f32,
+    pub loopback_secs: f32,
+}
+
+/// Splits the talk history into consecutive `bucket_secs` buckets aligned
+/// to wall-clock multiples (so 60 gives calendar minutes), from the first
+/// utterance to the end of the last. Utterances crossing a boundary are
+/// split between the buckets they overlap; buckets without speech are
+/// zeros. At most `MAX_TALK_BUCKETS` are returned, the most recent.
+pub fn get_talk_ratio_buckets(state: &AudioCaptureState, bucket_secs: u32) -> Result<Vec<TalkBucket>, String> {
+    if !(1..=MAX_TALK_BUCKET_SECS).contains(&bucket_secs) {
+        return Err(format!(
+            "bucket_secs must be between 1 and {}, got {}",
+            MAX_TALK_BUCKET_SECS, bucket_secs
+        ));
+    }
+    let history = state
+        .talk_history
+        .lock()
+        .map_err(|_| "Talk history is unavailable".to_string())?;
+
+    let end_ms = |r: &TalkRecord| r.start_ts_ms + (r.duration_secs * 1000.0) as u64;
+    let (Some(first), Some(last)) = (
+        history.iter().map(|r| r.start_ts_ms).min(),
+        history.iter().map(end_ms).max(),
+    ) else {
+        return Ok(Vec::new());
+    };
+
+    let bucket_ms = bucket_secs as u64 * 1000;
+    let last_bucket = last.saturating_sub(1).max(first) / bucket_ms;
+    let first_bucket = (first / bucket_ms).max((last_bucket + 1).saturating_sub(MAX_TALK_BUCKETS as u64));
+    let mut buckets: Vec<TalkBucket> = (first_bucket..=last_bucket)
+        .map(|b| TalkBucket {
+            bucket_start: b * bucket_ms,
+            mic_secs: 0.0,
+            loopback_secs: 0.0,
+        })
+        .collect();
+
+    let window_start = first_bucket * bucket_ms;
+    for record in history.iter() {
+        let mut from = record.start_ts_ms.max(window_start);
+        let to = end_ms(record);
+        while from < to {
+            let index = (from / bucket_ms - first_bucket) as usize;
+            let until = to.min((from / bucket_ms + 1) * bucket_ms);
+            let secs = (until - from) as f32 / 1000.0;
+            match (buckets.get_mut(index), record.source.as_str()) {
+                (Some(bucket), "mic") => bucket.mic_secs += secs,
+                (Some(bucket), "loopback") => bucket.loopback_secs += secs,
+                _ => {}
+            }
+            from = until;
+        }
+    }
+    Ok(buckets)
+}
+
+/// Allowed comfort-noise levels for `export_source_session_wav`: audible
+/// enough to show playback is running, too quiet to mask speech
+const COMFORT_NOISE_MIN_DBFS: f32 = -90.0;
+const COMFORT_NOISE_MAX_DBFS: f32 = -30.0;
+
+/// Result of `export_source_session_wav`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceExport {
+    pub chunks: usize,
+    /// Length of the written file, silence between utterances included
+    pub duration_secs: f32,
+}
+
+/// Writes every buffered chunk of `source` ("mic", "loopback" or "mixed")
+/// from its latest session to one WAV, in seq order, with silence inserted
+/// for the gaps between utterances so the file plays as a timeline. Gaps
+/// are measured from `emit_nanos`, so they're only as exact as the callback
+/// timing. Only the last `MAX_RECENT_CHUNKS` chunks are buffered.
+///
+/// `comfort_noise_dbfs` fills the gaps with white noise at that RMS level
+/// instead of digital silence, so a pause doesn't sound like a stall. The
+/// chunks themselves are written untouched.
+pub fn export_source_session_wav(
+    state: &AudioCaptureState,
+    source: &str,
+    path: &str,
+    comfort_noise_dbfs: Option<f32>,
+) -> Result<SourceExport, String> {
+    if let Some(level) = comfort_noise_dbfs {
+        if !(COMFORT_NOISE_MIN_DBFS..=COMFORT_NOISE_MAX_DBFS).contains(&level) {
+            return Err(format!(
+                "Comfort noise level must be between {} and {} dBFS, got {}",
+                COMFORT_NOISE_MIN_DBFS, COMFORT_NOISE_MAX_DBFS, level
+            ));
+        }
+    }
+    let mut chunks: Vec<AudioChunk> = state
+        .recent_chunks
+        .lock()
+        .map_err(|_| "Chunk buffer is unavailable".to_string())?
+        .values()
+        .filter(|c| c.source == source)
+        .cloned()
+        .collect();
+    let Some(last) = chunks.last() else {
+        return Err(format!("No buffered chunks for source '{}'", source));
+    };
+    let (session_id, sample_rate, sample_type) = (last.session_id.clone(), last.sample_rate, last.sample_type.clone());
+    chunks.retain(|c| c.session_id == session_id);
+    if chunks.iter().any(|c| c.sample_rate != sample_rate || c.sample_type != sample_type) {
+        return Err(format!("Buffered {} chunks mix sample rates or types", source));
+    }
+
+    let float = SampleType::parse(&sample_type)? == SampleType::F32;
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: if float { 32 } else { 16 },
+        sample_format: if float {
+            hound::SampleFormat::Float
+        } else {
+            hound::SampleFormat::Int
+        },
+    };
+    let mut writer = hound::WavWriter::create(path, spec).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let write_err = |e: hound::Error| format!("Failed to write {}: {}", path, e);
+    // A uniform ±0.5 draw has an RMS of 1/sqrt(12)
+    let mut noise = comfort_noise_dbfs.map(|level| (TpdfDither::new(), 10f32.powf(level / 20.0) * 12f32.sqrt()));
+
+    let mut written = 0usize;
+    let mut prev_end_nanos: Option<u64> = None;
+    for chunk in &chunks {
+        let duration_nanos = (chunk.duration_secs as f64 * 1e9) as u64;
+        let start_nanos = chunk.emit_nanos.saturating_sub(duration_nanos);
+        // Later parts of a split utterance continue the previous part
+        let gap_nanos = match prev_end_nanos {
+            Some(prev_end) if chunk.part == 0 => start_nanos.saturating_sub(prev_end),
+            _ => 0,
+        };
+        let gap_samples = (gap_nanos as f64 * sample_rate as f64 / 1e9) as usize;
+        for _ in 0..gap_samples {
+            let s = noise.as_mut().map_or(0.0, |(rng, scale)| rng.uniform() * *scale);
+            if float {
+                writer.write_sample(s).map_err(write_err)?;
+            } else {
+                writer.write_sample((s * i16::MAX as f32) as i16).map_err(write_err)?;
+            }
+        }
+        let samples = decode_chunk_pcm(chunk)?;
+        for &s in &samples {
+            if float {
+                writer.write_sample(s).map_err(write_err)?;
+            } else {
+                writer.write_sample((s * i16::MAX as f32) as i16).map_err(write_err)?;
+            }
+        }
+        written += gap_samples + samples.len();
+        prev_end_nanos = Some(chunk.emit_nanos);
+    }
+    writer.finalize().map_err(write_err)?;
+
+    Ok(SourceExport {
+        chunks: chunks.len(),
+        duration_secs: written as f32 / sample_rate as f32,
+    })
+}
+
+/// Toggles the soft limiter applied before i16 conversion. Off by default
+/// so the output stays bit-exact with the plain clamp.
+pub fn set_limiter(state: &AudioCaptureState, enabled: bool) {
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.limiter = enabled;
+    }
+}
+
+/// Sets the trailing silence (ms) that ends an utterance. `short_ms`, when
+/// given, is used instead after a rising-energy tail; it can't exceed `ms`.
+pub fn set_endpoint_silence(state: &AudioCaptureState, ms: u32, short_ms: Option<u32>) -> Result<(), String> {
+    let short_ms = short_ms.unwrap_or(ms);
+    if ms == 0 || short_ms == 0 {
+        return Err("Endpoint silence must be greater than 0 ms".into());
+    }
+    if short_ms > ms {
+        return Err("Short endpoint silence can't exceed the regular endpoint silence".into());
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.endpoint_silence_ms = ms;
+        settings.endpoint_short_silence_ms = short_ms;
+    }
+    Ok(())
+}
+
+/// Sets how much audio after each start is ignored by the VAD (0 disables).
+pub fn set_vad_warmup(state: &AudioCaptureState, ms: u32) {
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.warmup_ms = ms;
+    }
+}
+
+/// Sets the post-flush cooldown (0 disables it).
+pub fn set_vad_cooldown(state: &AudioCaptureState, ms: u32) {
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.cooldown_ms = ms;
+    }
+}
+
+/// Chooses whether chunks keep the endpoint silence after the last speech
+/// frame (the default) or end right where speech stopped.
+pub fn set_include_tail_silence(state: &AudioCaptureState, enabled: bool) {
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.include_tail_silence = enabled;
+    }
+}
+
+/// Sets the release ramp: how many silence frames each speech frame cancels
+/// during a pause (0 restores the full reset).
+pub fn set_vad_release_decrement(state: &AudioCaptureState, frames: usize) -> Result<(), String> {
+    if frames > MAX_RELEASE_DECREMENT_FRAMES {
+        return Err(format!(
+            "Release decrement must be at most {} frames",
+            MAX_RELEASE_DECREMENT_FRAMES
+        ));
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.release_decrement_frames = frames;
+    }
+    Ok(())
+}
+
+/// Turns the speech band-pass in front of the VAD on or off; with
+/// `filter_output` the emitted audio is band-passed too.
+pub fn set_speech_bandpass(state: &AudioCaptureState, enabled: bool, filter_output: bool) {
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.analysis_filter = if enabled {
+            AnalysisFilter::Bandpass
+        } else {
+            AnalysisFilter::None
+        };
+        settings.filter_output = enabled && filter_output;
+    }
+}
+
+/// Picks the filter the VAD decides on ("none", "highpass" or "bandpass")
+/// while the emitted audio stays full-band.
+pub fn set_vad_analysis_filter(state: &AudioCaptureState, kind: &str) -> Result<(), String> {
+    let kind = AnalysisFilter::parse(kind)?;
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.analysis_filter = kind;
+        settings.filter_output = false;
+    }
+    Ok(())
+}
+
+/// Turns mic echo cancellation on or off, with the echo path length it
+/// covers (`tail_ms`, default 128). Needs both sources at the same target
+/// rate. Cost is two multiply-adds per tap per mic sample: at 16 kHz the
+/// default tail is ~65M/s, about 5% of one core; it adds no latency. The
+/// filter takes a few seconds of far-end speech to converge.
+pub fn set_echo_cancellation(state: &AudioCaptureState, enabled: bool, tail_ms: Option<u32>) -> Result<(), String> {
+    let tail_ms = tail_ms.unwrap_or(AEC_DEFAULT_TAIL_MS);
+    if tail_ms == 0 || tail_ms > AEC_MAX_TAIL_MS {
+        return Err(format!("Echo tail must be between 1 and {} ms", AEC_MAX_TAIL_MS));
+    }
+    let Ok(mut settings) = state.settings.lock() else {
+        return Err("Settings unavailable".into());
+    };
+    if enabled && settings.mic_target_rate != settings.loopback_target_rate {
+        return Err("Echo cancellation needs mic and loopback at the same target rate".into());
+    }
+    settings.echo_cancellation = enabled;
+    settings.echo_tail_ms = tail_ms;
+    Ok(())
+}
+
+/// Sets how long a source must stay silent before its callback stops
+/// resampling and running the VAD (0 disables suspending). For 10 ms
+/// callbacks at 48 kHz stereo, a suspended callback measured ~1.4 µs
+/// against ~5.7 µs processing (release build, one Xeon core): about 4x
+/// less, though both are under 0.1% of a core. Resume costs at most one
+/// callback of latency.
+pub fn set_suspend_on_silence(state: &AudioCaptureState, ms: u32) {
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.suspend_after_ms = ms;
+    }
+}
+
+/// Sets the rate a source is resampled to ("mic" or "loopback"). Takes
+/// effect at the next start, so it is rejected while capture runs. Mixed
+/// mode always runs at 16 kHz.
+pub fn set_source_target_rate(state: &AudioCaptureState, source: &str, hz: u32) -> Result<(), String> {
+    if state.running.load(Ordering::SeqCst) {
+        return Err("Stop capture before changing a source's target rate".into());
+    }
+    if !(MIN_TARGET_RATE..=MAX_TARGET_RATE).contains(&hz) {
+        return Err(format!(
+            "Target rate must be between {} and {} Hz",
+            MIN_TARGET_RATE, MAX_TARGET_RATE
+        ));
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        match source {
+            "mic" => settings.mic_target_rate = hz,
+            "loopback" => settings.loopback_target_rate = hz,
+            other => return Err(format!("Unknown source '{}' (expected \"mic\" or \"loopback\")", other)),
+        }
+    }
+    Ok(())
+}
+
+/// Turns barge-in suppression on or off and sets how far (in dB) the mic
+/// threshold is raised while loopback is speaking.
+pub fn set_barge_in_suppression(state: &AudioCaptureState, enabled: bool, db_raise: f32) -> Result<(), String> {
+    if !(0.0..=40.0).contains(&db_raise) {
+        return Err("Barge-in raise must be between 0 and 40 dB".into());
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.barge_in_suppression = enabled;
+        settings.barge_in_raise_db = db_raise;
+    }
+    Ok(())
+}
+
+/// Requests a fixed device buffer of `frames` for the next start (0 restores
+/// the driver default). Devices that report a supported range are checked
+/// against it; one that rejects the size falls back to the default with a
+/// warning.
+pub fn set_buffer_size(state: &AudioCaptureState, frames: u32) -> Result<(), String> {
+    if frames > MAX_BUFFER_FRAMES {
+        return Err(format!("Buffer size must be at most {} frames", MAX_BUFFER_FRAMES));
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.buffer_frames = (frames > 0).then_some(frames);
+    }
+    Ok(())
+}
+
+/// Requests a mic stream of `channels` at `sample_rate` Hz for the next
+/// start; 0 for both clears the preference. The request is checked against
+/// the configs the current default input advertises. If the device can't
+/// provide it at start (e.g. it was swapped), the default config is used
+/// with a warning.
+pub fn set_preferred_input_config(state: &AudioCaptureState, sample_rate: u32, channels: u16) -> Result<(), String> {
+    let preferred = match (sample_rate, channels) {
+        (0, 0) => None,
+        (0, _) | (_, 0) => return Err("Sample rate and channels must both be set, or both 0 to clear".into()),
+        _ => Some(PreferredInputConfig { sample_rate, channels }),
+    };
+    if let Some(p) = preferred {
+        let device = resolve_source_device(state, "mic")?;
+        if find_supported_config(&device, p)?.is_none() {
+            return Err(format!(
+                "{} doesn't support {}ch @ {}Hz",
+                device.name().unwrap_or_else(|_| "The input device".into()),
+                channels,
+                sample_rate
+            ));
+        }
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.preferred_input_config = preferred;
+    }
+    Ok(())
+}
+
+/// Toggles raising the capture callback threads to maximum priority, applied
+/// from the next start. Per platform:
+/// - Linux: needs `CAP_SYS_NICE` or an `RLIMIT_RTPRIO`/nice limit granted
+///   via `/etc/security/limits.conf`; usually denied for desktop users.
+/// - Windows: sets `THREAD_PRIORITY_TIME_CRITICAL`; no privileges needed.
+/// - macOS: CoreAudio already runs input callbacks on a real-time thread,
+///   so this rarely changes anything.
+///
+/// When the OS refuses, capture runs at normal priority with a warning.
+pub fn set_realtime_priority(state: &AudioCaptureState, enabled: bool) {
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.realtime_priority = enabled;
+    }
+}
+
+/// WASAPI exclusive mode. cpal opens every WASAPI stream in shared mode and
+/// can't request exclusive access, so enabling it is refused rather than
+/// stored; disabling is a no-op. Not part of the settings profile.
+pub fn set_wasapi_exclusive(enabled: bool) -> Result<(), String> {
+    if enabled {
+        return Err("WASAPI exclusive mode is unsupported by the audio backend".into());
+    }
+    Ok(())
+}
+
+/// Turns per-utterance peak normalization on or off, with the target peak
+/// (dBFS, default -1) and whether quiet utterances are boosted as well as
+/// loud ones attenuated.
+pub fn set_peak_normalize(
+    state: &AudioCaptureState,
+    enabled: bool,
+    target_dbfs: Option<f32>,
+    boost: bool,
+) -> Result<(), String> {
+    let target_dbfs = target_dbfs.unwrap_or(DEFAULT_PEAK_TARGET_DBFS);
+    if !(-60.0..=0.0).contains(&target_dbfs) {
+        return Err("Peak target must be between -60 and 0 dBFS".into());
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.peak_normalize = enabled;
+        settings.peak_target_dbfs = target_dbfs;
+        settings.peak_boost = boost;
+    }
+    Ok(())
+}
+
+/// Switches between speech-only utterances and continuous recording in
+/// `window_secs` windows (default `DEFAULT_CONTINUOUS_WINDOW_SECS`). Pending
+/// audio is flushed under the old policy first, so no window mixes the two.
+pub fn set_continuous_record(state: &AudioCaptureState, enabled: bool, window_secs: Option<u32>) -> Result<(), String> {
+    let window_secs = window_secs.unwrap_or(DEFAULT_CONTINUOUS_WINDOW_SECS);
+    if !(1..=MAX_CONTINUOUS_WINDOW_SECS).contains(&window_secs) {
+        return Err(format!(
+            "Continuous window must be between 1 and {} seconds, got {}",
+            MAX_CONTINUOUS_WINDOW_SECS, window_secs
+        ));
+    }
+    flush_all(state);
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.continuous_record = enabled;
+        settings.continuous_window_secs = window_secs;
+    }
+    Ok(())
+}
+
+/// Sets how utterances that end in the same flush are ordered: "onset"
+/// (earliest speech first, default) or "source" (mic, loopback, mixed).
+pub fn set_flush_order(state: &AudioCaptureState, order: &str) -> Result<(), String> {
+    let order = FlushOrder::parse(order)?;
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.flush_order = order;
+    }
+    Ok(())
+}
+
+/// Turns automatic "turn-end" marker chunks after each utterance on or off.
+pub fn set_turn_end_markers(state: &AudioCaptureState, enabled: bool) {
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.turn_end_markers = enabled;
+    }
+}
+
+/// Sets the hard cap on buffered audio per accumulator, in seconds.
+pub fn set_max_utterance_secs(state: &AudioCaptureState, secs: u32) -> Result<(), String> {
+    if !(1..=MAX_UTTERANCE_SECS).contains(&secs) {
+        return Err(format!(
+            "Utterance cap must be between 1 and {} seconds, got {}",
+            MAX_UTTERANCE_SECS, secs
+        ));
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.max_utterance_secs = secs;
+    }
+    Ok(())
+}
+
+/// Sets the brickwall output ceiling in dBFS (0 disables it).
+pub fn set_output_ceiling_dbfs(state: &AudioCaptureState, value: f32) -> Result<(), String> {
+    if !(-60.0..=0.0).contains(&value) {
+        return Err("Output ceiling must be between -60 and 0 dBFS".into());
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.output_ceiling_dbfs = value;
+    }
+    Ok(())
+}
+
+/// Chooses whether chunks carry bare PCM ("raw", default) or a WAV file
+/// ("wav") that an `<audio>` element can play directly.
+pub fn set_chunk_container(state: &AudioCaptureState, container: &str) -> Result<(), String> {
+    let container = Container::parse(container)?;
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.container = container;
+    }
+    Ok(())
+}
+
+/// Sets the PCM encoding of emitted chunks ("i16" or "f32").
+pub fn set_sample_type(state: &AudioCaptureState, sample_type: &str) -> Result<(), String> {
+    let sample_type = SampleType::parse(sample_type)?;
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.sample_type = sample_type;
+    }
+    Ok(())
+}
+
+/// Turns TPDF dithering of the i16 conversion on or off.
+pub fn set_dither(state: &AudioCaptureState, enabled: bool) {
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.dither = enabled;
+    }
+}
+
+pub fn get_dither(state: &AudioCaptureState) -> bool {
+    state.settings.lock().map(|s| s.dither).unwrap_or(false)
+}
+
+/// Attaches the app handle so the audio side can emit events.
+pub fn attach_app(state: &AudioCaptureState, app: tauri::AppHandle) {
+    if let Ok(mut slot) = state.app.lock() {
+        *slot = Some(app);
+    }
+}
+
+/// Sets the level below which meters report silence (see
+/// `AudioSettings::meter_floor_dbfs`).
+pub fn set_meter_floor(state: &AudioCaptureState, dbfs: f32) -> Result<(), String> {
+    if !(DBFS_FLOOR..=0.0).contains(&dbfs) {
+        return Err(format!("Meter floor must be between {} and 0 dBFS", DBFS_FLOOR));
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.meter_floor_dbfs = dbfs;
+    }
+    Ok(())
+}
+
+/// Turns `"energy-frame"` level events on or off and sets their interval.
+pub fn set_energy_events(state: &AudioCaptureState, enabled: bool, interval_ms: u32) -> Result<(), String> {
+    if interval_ms == 0 {
+        return Err("Energy frame interval must be greater than 0 ms".into());
+    }
+    if let Ok(mut settings) = state.settings.lock() {
+        settings.energy_events = enabled;
+        settings.energy_interval_ms = interval_ms;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stream stand-in for `start_streams`; plays and pauses successfully.
+    struct FakeStream;
+
+    impl StreamTrait for FakeStream {
+        fn play(&self) -> Result<(), cpal::PlayStreamError> {
+            Ok(())
+        }
+
+        fn pause(&self) -> Result<(), cpal::PauseStreamError> {
+            Ok(())
+        }
+    }
+
+    fn fake_config() -> StreamConfig {
+        StreamConfig {
+            channels: 1,
+            sample_rate: cpal::SampleRate(TARGET_SAMPLE_RATE),
+            buffer_size: BufferSize::Default,
+        }
+    }
+
+    #[test]
+    fn loopback_build_failure_leaves_capture_stopped() {
+        let state = AudioCaptureState::default();
+        let claim = claim_session(&state, SessionKind::Devices).unwrap();
+        let result = start_streams(&state, claim, "mic", "loopback", |source| match source {
+            "mic" => Ok((FakeStream, fake_config())),
+            _ => Err("Loopback build failed".to_string()),
+        });
+        assert_eq!(result.err().as_deref(), Some("Loopback build failed"));
+        assert!(!state.running.load(Ordering::SeqCst));
+        assert_eq!(*state.active_session.lock().unwrap(), None);
+    }
+
+    #[test]
+    fn both_streams_playing_marks_capture_running() {
+        let state = AudioCaptureState::default();
+        let claim = claim_session(&state, SessionKind::Devices).unwrap();
+        let result = start_streams(&state, claim, "mic", "loopback", |_| Ok((FakeStream, fake_config())));
+        assert!(result.is_ok());
+        assert!(state.running.load(Ordering::SeqCst));
+        assert_eq!(*state.active_session.lock().unwrap(), Some(SessionKind::Devices));
+    }
+
+    #[test]
+    fn overlapping_sessions_are_refused_naming_the_active_kind() {
+        let kinds = [SessionKind::Devices, SessionKind::Clip, SessionKind::FileSource];
+        for active in kinds {
+            let state = AudioCaptureState::default();
+            let claim = claim_session(&state, active).unwrap();
+            for attempt in kinds {
+                let err = claim_session(&state, attempt).err().expect("second claim must fail");
+                assert_eq!(err, format!("Already running: {} is active", active.as_str()));
+            }
+            drop(claim);
+            assert_eq!(*state.active_session.lock().unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn kept_claim_holds_the_slot_until_released() {
+        let state = AudioCaptureState::default();
+        claim_session(&state, SessionKind::FileSource).unwrap().keep();
+        assert!(claim_session(&state, SessionKind::Devices).is_err());
+        release_session(&state, SessionKind::FileSource);
+        assert!(claim_session(&state, SessionKind::Devices).is_ok());
+    }
+
+    #[test]
+    fn prepared_start_is_cancelled_by_a_stop_in_between() {
+        let state = AudioCaptureState::default();
+        claim_session(&state, SessionKind::Devices).unwrap().keep();
+        assert!(adopt_session(&state, SessionKind::Devices).is_ok());
+        // Dropped without `keep`, the adopted claim released the slot
+        assert!(claim_session(&state, SessionKind::FileSource).is_ok());
+        release_session(&state, SessionKind::FileSource);
+        claim_session(&state, SessionKind::Devices).unwrap().keep();
+        release_session(&state, SessionKind::Devices);
+        assert_eq!(
+            adopt_session(&state, SessionKind::Devices).err().as_deref(),
+            Some("Capture start was cancelled")
+        );
+    }
+
+    /// Decodes a raw i16 chunk's PCM.
+    fn pcm_i16(chunk: &AudioChunk) -> Vec<i16> {
+        base64::engine::general_purpose::STANDARD
+            .decode(&chunk.audio_b64)
+            .unwrap()
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn limiter_reduces_full_scale_samples_on_overdriven_input() {
+        // A sine driven 3x past full scale and clipped flat at 1.5
+        let overdriven: Vec<f32> = (0..1600)
+            .map(|i| (3.0 * (i as f32 * 0.05).sin()).clamp(-1.5, 1.5))
+            .collect();
+        let full_scale = |limiter: bool| {
+            let settings = AudioSettings {
+                limiter,
+                ..AudioSettings::default()
+            };
+            let chunk = encode_chunk(&overdriven, TARGET_SAMPLE_RATE, "mic", "", "mono", &settings);
+            pcm_i16(&chunk)
+                .iter()
+                .filter(|s| s.unsigned_abs() >= i16::MAX as u16)
+                .count()
+        };
+        let (off, on) = (full_scale(false), full_scale(true));
+        assert!(off > 0);
+        assert!(on < off, "limiter on: {} full-scale samples, off: {}", on, off);
+    }
+
+    /// Samples in one 20 ms VAD frame at the default target rate.
+    const FRAME: usize = TARGET_SAMPLE_RATE as usize / 50;
+
+    /// Feeds `frames` frames at a constant `level` and collects the chunks.
+    fn feed_level(vad: &mut VadAccumulator, level: f32, frames: usize, settings: &AudioSettings) -> Vec<AudioChunk> {
+        let frame = vec![level; FRAME];
+        (0..frames).flat_map(|_| vad.feed(&frame, settings)).collect()
+    }
+
+    #[test]
+    fn warm_up_swallows_a_loud_start() {
+        let settings = AudioSettings::default();
+        let warmup_frames = settings.warmup_ms as usize / 20;
+        let mut vad = VadAccumulator::new("mic");
+        let mut chunks = feed_level(&mut vad, 0.5, warmup_frames, &settings);
+        chunks.extend(feed_level(&mut vad, 0.0, 100, &settings));
+        assert!(chunks.is_empty());
+        assert!(!vad.is_speaking);
+
+        // The same start with no warm-up is emitted
+        let settings = AudioSettings {
+            warmup_ms: 0,
+            ..AudioSettings::default()
+        };
+        let mut vad = VadAccumulator::new("mic");
+        let mut chunks = feed_level(&mut vad, 0.5, warmup_frames, &settings);
+        chunks.extend(feed_level(&mut vad, 0.0, 100, &settings));
+        assert_eq!(chunks.len(), 1);
+    }
+
+    #[test]
+    fn speech_across_a_gap_shorter_than_the_cooldown_merges() {
+        let settings = AudioSettings {
+            warmup_ms: 0,
+            ..AudioSettings::default()
+        };
+        let gap_frames = settings.cooldown_ms as usize / 20 - 1;
+        let mut vad = VadAccumulator::new("mic");
+        let mut chunks = feed_level(&mut vad, 0.5, 20, &settings);
+        chunks.extend(feed_level(&mut vad, 0.0, gap_frames, &settings));
+        chunks.extend(feed_level(&mut vad, 0.5, 20, &settings));
+        chunks.extend(feed_level(&mut vad, 0.0, 100, &settings));
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].sample_count >= (40 + gap_frames) * FRAME);
+    }
+
+    #[test]
+    fn dither_spreads_a_sub_lsb_level_across_codes() {
+        // A third of an LSB: truncation turns it into digital silence
+        let quiet = vec![0.3 / i16::MAX as f32; 16_000];
+        let stats = |dither: bool| {
+            let settings = AudioSettings {
+                dither,
+                ..AudioSettings::default()
+            };
+            let pcm = pcm_i16(&encode_chunk(&quiet, TARGET_SAMPLE_RATE, "mic", "", "mono", &settings));
+            let mean = pcm.iter().map(|&s| s as f64).sum::<f64>() / pcm.len() as f64;
+            let variance = pcm.iter().map(|&s| (s as f64 - mean).powi(2)).sum::<f64>() / pcm.len() as f64;
+            (mean, variance)
+        };
+        assert_eq!(stats(false), (0.0, 0.0));
+        let (mean, variance) = stats(true);
+        assert!(variance > 0.1, "dithered variance {}", variance);
+        // TPDF dither is unbiased: the average stays near the input level
+        assert!((mean - 0.3).abs() < 0.1, "dithered mean {}", mean);
+    }
+
+    #[test]
+    fn release_ramp_closes_an_utterance_in_noisy_silence() {
+        // Every fourth frame of the "silence" is a noise blip over threshold
+        let run = |release_decrement_frames: usize| {
+            let settings = AudioSettings {
+                warmup_ms: 0,
+                release_decrement_frames,
+                ..AudioSettings::default()
+            };
+            let mut vad = VadAccumulator::new("mic");
+            assert!(feed_level(&mut vad, 0.5, 20, &settings).is_empty());
+            // Frame of the noisy silence on which the speech was flushed
+            (0..500).find(|i| {
+                let level = if i % 4 == 3 { 0.05 } else { 0.0 };
+                !feed_level(&mut vad, level, 1, &settings).is_empty()
+            })
+        };
+        assert_eq!(run(0), None, "without the ramp each blip restarts the endpoint");
+        let closed_at = run(1).expect("utterance never closed");
+        // Net two silent frames per four, against the 75-frame endpoint
+        assert!(closed_at < 160, "closed after {} frames", closed_at);
+    }
+
+    #[test]
+    fn downmix_compensation_restores_a_single_active_channel() {
+        // Stereo with the signal on the left channel only
+        let left: Vec<f32> = (0..1600).map(|i| 0.5 * (i as f32 * 0.05).sin()).collect();
+        let stereo: Vec<f32> = left.iter().flat_map(|&l| [l, 0.0]).collect();
+        let downmixed_rms = |settings: &AudioSettings| {
+            let mut scratch = ConversionScratch::default();
+            let gain = settings.downmix_gain(2);
+            frame_rms(scratch.convert_f32(&stereo, 2, TARGET_SAMPLE_RATE, TARGET_SAMPLE_RATE, gain))
+        };
+        let single = frame_rms(&left);
+
+        let plain = downmixed_rms(&AudioSettings::default());
+        assert!((plain - single / 2.0).abs() < 1e-4, "averaging halves one channel");
+
+        let compensated = downmixed_rms(&AudioSettings {
+            downmix_compensation: true,
+            downmix_gain: Some(2.0),
+            ..AudioSettings::default()
+        });
+        assert!((compensated - single).abs() < 1e-4, "{} vs {}", compensated, single);
+
+        // The default sqrt(channels) gain lands between the two
+        let default_gain = downmixed_rms(&AudioSettings {
+            downmix_compensation: true,
+            ..AudioSettings::default()
+        });
+        assert!(default_gain > plain && default_gain < single);
+    }
+
+    #[test]
+    fn file_playback_is_superseded_by_a_restart() {
+        let state = Arc::new(AudioCaptureState::default());
+        begin_session(&state, "", "a.wav");
+        state.running.store(true, Ordering::SeqCst);
+        let playback = FilePlayback {
+            session_id: get_session_id(&state),
+            state: state.clone(),
+            info: FileSourceInfo {
+                path: "a.wav".into(),
+                source: "loopback".into(),
+                sample_rate: 16000,
+                channels: 1,
+                duration_secs: None,
+            },
+            track_id: 0,
+            speed: 0.0,
+        };
+        assert!(!playback.superseded());
+        // Stopped and restarted before the thread looked at `running`
+        begin_session(&state, "", "b.wav");
+        assert!(playback.superseded());
+    }
+
+    #[test]
+    fn overruns_are_measured_against_the_buffer_period() {
+        let ms = std::time::Duration::from_millis;
+        // 3 ms is jitter on a 10 ms buffer but most of a 2 ms one
+        assert_eq!(overrun_frames(ms(3), ms(10), 48000), None);
+        assert_eq!(overrun_frames(ms(3), ms(2), 48000), Some(144));
+        assert_eq!(overrun_frames(ms(20), ms(10), 48000), Some(960));
+        // Seconds without callbacks is silence, not dropped frames
+        assert_eq!(overrun_frames(ms(3000), ms(10), 48000), None);
+    }
+
+    #[test]
+    fn talk_history_starts_at_the_chunk_onset() {
+        let state = AudioCaptureState::default();
+        let settings = AudioSettings::default();
+        let mut chunk = encode_chunk(&[0.0; 16000], TARGET_SAMPLE_RATE, "mic", "", "mono", &settings);
+        // Flushed long after it was spoken, e.g. held by a merge window
+        chunk.onset_ms = 1_000;
+        record_talk(&state, &chunk);
+        let history = state.talk_history.lock().unwrap();
+        assert_eq!(history.back().map(|r| r.start_ts_ms), Some(1_000));
+    }
+
+    #[test]
+    fn zero_channel_or_zero_rate_configs_are_rejected() {
+        let config = |channels, rate| StreamConfig {
+            channels,
+            sample_rate: cpal::SampleRate(rate),
+            buffer_size: BufferSize::Default,
+        };
+        assert!(check_stream_config(&config(0, 48_000), "mic").is_err());
+        assert!(check_stream_config(&config(2, 0), "loopback").is_err());
+        assert!(check_stream_config(&config(2, 48_000), "mic").is_ok());
+
+        let (mut mono, mut out) = (Vec::new(), Vec::new());
+        to_mono_into(&[0.5; 64], 0, 48_000, TARGET_SAMPLE_RATE, Downmix::Average, &mut mono, &mut out);
+        assert!(out.is_empty());
+        to_mono_into(&[0.5; 64], 2, 0, TARGET_SAMPLE_RATE, Downmix::FirstChannel, &mut mono, &mut out);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn constant_energy_is_flushed_at_the_utterance_cap() {
+        let settings = AudioSettings {
+            warmup_ms: 0,
+            max_utterance_secs: 1,
+            ..AudioSettings::default()
+        };
+        let mut vad = VadAccumulator::new("mic");
+        // 1.5 s of unbroken energy never reaches an endpoint
+        let chunks = feed_level(&mut vad, 0.5, 75, &settings);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].sample_count, TARGET_SAMPLE_RATE as usize);
+        assert!(vad.buffer.len() < TARGET_SAMPLE_RATE as usize);
+    }
+
+    /// Counts allocations made on the current thread while `COUNTING` is
+    /// set, to check the callback path reuses its buffers.
+    struct CountingAllocator;
+
+    thread_local! {
+        static COUNTING: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+        static ALLOCATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    unsafe impl std::alloc::GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            if COUNTING.with(|c| c.get()) {
+                ALLOCATIONS.with(|a| a.set(a.get() + 1));
+            }
+            std::alloc::System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            std::alloc::System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// Allocations made by `f` on this thread.
+    fn allocations(f: impl FnOnce()) -> usize {
+        ALLOCATIONS.with(|a| a.set(0));
+        COUNTING.with(|c| c.set(true));
+        f();
+        COUNTING.with(|c| c.set(false));
+        ALLOCATIONS.with(|a| a.get())
+    }
+
+    #[test]
+    fn callback_path_does_not_allocate_once_warm() {
+        let state = AudioCaptureState::default();
+        if let Ok(mut settings) = state.settings.lock() {
+            settings.echo_cancellation = true;
+        }
+        // 10 ms of 48 kHz stereo per callback, quiet then speech-level
+        let quiet = vec![0.0f32; 960];
+        let loud: Vec<f32> = (0..960).map(|i| 0.3 * (i as f32 * 0.1).sin()).collect();
+        let mut mic = ConversionScratch::default();
+        let mut loopback = ConversionScratch::default();
+        let mut callback = |data: &[f32]| {
+            process_frame(&state, "loopback", loopback.convert_f32(data, 2, 48_000, TARGET_SAMPLE_RATE, 1.0));
+            process_frame(&state, "mic", mic.convert_f32(data, 2, 48_000, TARGET_SAMPLE_RATE, 1.0));
+        };
+        // Let the scratch buffers and the echo canceller reach their size
+        for _ in 0..50 {
+            callback(&quiet);
+        }
+        let steady = allocations(|| {
+            for i in 0..200 {
+                callback(if i < 100 { &quiet } else { &loud });
+            }
+        });
+        assert_eq!(steady, 0, "{} allocations over 200 callbacks", steady);
+    }
 }
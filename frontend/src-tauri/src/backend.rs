@@ -0,0 +1,181 @@
+use crate::audio::{AudioCaptureState, AudioChunk};
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::Emitter;
+use tokio::sync::Notify;
+use tokio_tungstenite::tungstenite::Message;
+
+// ── Reconnect backoff ──
+const RECONNECT_MIN: Duration = Duration::from_millis(500);
+const RECONNECT_MAX: Duration = Duration::from_secs(30);
+
+/// Chunks waiting for the socket; past this the oldest is dropped
+const LINK_QUEUE_MAX: usize = 64;
+
+/// Chunks handed from the audio callbacks to the link's task, which
+/// serializes them. Bounded: a slow or unreachable backend drops the oldest
+/// chunk rather than growing without limit.
+#[derive(Default)]
+struct LinkQueue {
+    chunks: Mutex<VecDeque<AudioChunk>>,
+    ready: Notify,
+}
+
+impl LinkQueue {
+    fn push(&self, chunk: AudioChunk) {
+        let Ok(mut chunks) = self.chunks.lock() else { return };
+        if chunks.len() >= LINK_QUEUE_MAX {
+            chunks.pop_front();
+            log::warn!("Backend link queue full; dropped the oldest chunk");
+        }
+        chunks.push_back(chunk);
+        drop(chunks);
+        self.ready.notify_one();
+    }
+
+    fn pop(&self) -> Option<AudioChunk> {
+        self.chunks.lock().ok()?.pop_front()
+    }
+
+    /// Puts back a chunk whose send failed, to go first on the next
+    /// connection. Being the oldest, it is the one dropped if the queue
+    /// filled up meanwhile.
+    fn requeue(&self, chunk: AudioChunk) {
+        let Ok(mut chunks) = self.chunks.lock() else { return };
+        if chunks.len() >= LINK_QUEUE_MAX {
+            log::warn!("Backend link queue full; dropped the oldest chunk");
+            return;
+        }
+        chunks.push_front(chunk);
+    }
+}
+
+/// A live backend connection: the queue chunks are pushed into and the task
+/// that owns the socket. Dropping the link stops the task.
+pub struct BackendLink {
+    queue: Arc<LinkQueue>,
+    task: tauri::async_runtime::JoinHandle<()>,
+}
+
+impl Drop for BackendLink {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Payload of the `"backend-status"` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct BackendStatus {
+    pub url: String,
+    /// "connecting", "connected" or "disconnected"
+    pub state: &'static str,
+    pub error: Option<String>,
+}
+
+fn emit_status(app: &tauri::AppHandle, url: &str, state: &'static str, error: Option<String>) {
+    let status = BackendStatus { url: url.to_string(), state, error };
+    if let Err(e) = app.emit("backend-status", status) {
+        log::warn!("Failed to emit backend-status: {}", e);
+    }
+}
+
+/// Opens (or replaces) the WebSocket link to `url`. Every chunk queued after
+/// this is also sent to the backend as JSON; with `relay_to_frontend` off the
+/// chunks go only to the backend and `poll_audio_chunks` stays empty.
+pub fn connect(state: &AudioCaptureState, app: tauri::AppHandle, url: String, relay_to_frontend: bool) -> Result<(), String> {
+    if !(url.starts_with("ws://") || url.starts_with("wss://")) {
+        return Err(format!("Backend URL must start with ws:// or wss://, got '{}'", url));
+    }
+
+    let queue = Arc::new(LinkQueue::default());
+    let task = tauri::async_runtime::spawn(run_link(app, url, queue.clone()));
+    if let Ok(mut link) = state.backend.lock() {
+        *link = Some(BackendLink { queue, task });
+    }
+    state.relay_to_frontend.store(relay_to_frontend, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Closes the backend link, if any, and resumes relaying to the frontend.
+pub fn disconnect(state: &AudioCaptureState) {
+    if let Ok(mut link) = state.backend.lock() {
+        link.take();
+    }
+    state.relay_to_frontend.store(true, Ordering::SeqCst);
+}
+
+/// Whether a backend link is open, so `emit_chunk` only copies chunks
+/// that will be forwarded.
+pub(crate) fn is_linked(state: &AudioCaptureState) -> bool {
+    state.backend.lock().is_ok_and(|link| link.is_some())
+}
+
+/// Hands an emitted chunk to the backend link. Called from the audio
+/// callbacks once the chunk queue is released, so it only enqueues; the
+/// link's task serializes and sends. When both sources emit at once, a
+/// chunk can reach the backend one place out of `seq` order.
+pub(crate) fn forward(state: &AudioCaptureState, chunk: AudioChunk) {
+    let Ok(link) = state.backend.lock() else { return };
+    if let Some(link) = link.as_ref() {
+        link.queue.push(chunk);
+    }
+}
+
+/// Connects, sends chunks until the socket fails or the backend closes it,
+/// then reconnects with exponential backoff. The socket is read alongside,
+/// so pings are answered and a close is noticed while no chunks flow. Ends
+/// when the link is dropped.
+async fn run_link(app: tauri::AppHandle, url: String, queue: Arc<LinkQueue>) {
+    let mut backoff = RECONNECT_MIN;
+    loop {
+        emit_status(&app, &url, "connecting", None);
+        let error = match tokio_tungstenite::connect_async(url.as_str()).await {
+            Ok((mut ws, _)) => {
+                log::info!("Backend connected: {}", url);
+                emit_status(&app, &url, "connected", None);
+                backoff = RECONNECT_MIN;
+                'connected: loop {
+                    while let Some(chunk) = queue.pop() {
+                        let json = match serde_json::to_string(&chunk) {
+                            Ok(json) => json,
+                            Err(e) => {
+                                log::warn!("Failed to serialize chunk {} for backend: {}", chunk.seq, e);
+                                continue;
+                            }
+                        };
+                        if let Err(e) = ws.send(Message::Text(json)).await {
+                            queue.requeue(chunk);
+                            break 'connected e.to_string();
+                        }
+                    }
+                    tokio::select! {
+                        _ = queue.ready.notified() => {}
+                        incoming = ws.next() => {
+                            match incoming {
+                                // tungstenite queues the pong; flushing sends it
+                                Some(Ok(Message::Ping(_))) => {
+                                    if let Err(e) = ws.flush().await {
+                                        break 'connected e.to_string();
+                                    }
+                                }
+                                Some(Ok(Message::Close(_))) | None => break 'connected "closed by the backend".to_string(),
+                                Some(Ok(_)) => {}
+                                Some(Err(e)) => break 'connected e.to_string(),
+                            }
+                        }
+                    }
+                }
+            }
+            Err(e) => e.to_string(),
+        };
+
+        log::warn!("Backend link to {} down: {}", url, error);
+        emit_status(&app, &url, "disconnected", Some(error));
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(RECONNECT_MAX);
+    }
+}
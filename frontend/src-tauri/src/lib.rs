@@ -1,7 +1,10 @@
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tauri::Manager;
+use std::time::Duration;
+use tauri::{Emitter, Manager};
 
 mod audio;
+mod backend;
 
 /// Holds the cpal Stream handles. They must stay alive for capture to continue.
 /// Wrapped in Option so we can take/drop them on stop.
@@ -11,8 +14,8 @@ struct StreamHandles {
 }
 
 // cpal::Stream contains a raw pointer (*mut ()) that prevents auto-impl of
-// Send/Sync. We wrap it in a Mutex and only access it from serialized Tauri
-// commands, so this is safe.
+// Send/Sync. We wrap it in a Mutex and only access it on the main thread,
+// from sync Tauri commands or `run_on_main_thread`, so this is safe.
 // SAFETY: StreamHandles is only accessed behind a Mutex, never moved across
 // threads without synchronization.
 unsafe impl Send for StreamHandles {}
@@ -20,24 +23,376 @@ unsafe impl Sync for StreamHandles {}
 
 struct AudioStreams(std::sync::Mutex<Option<StreamHandles>>);
 
+/// Overlay geometry (logical pixels). The window docks to the right edge of
+/// the primary monitor with a small gap.
+const OVERLAY_WIDTH: f64 = 380.0;
+const OVERLAY_EDGE_GAP: f64 = 10.0;
+const PILL_WIDTH: f64 = 220.0;
+const PILL_HEIGHT: f64 = 56.0;
+/// Strip height when no monitor can be queried (headless, odd setups);
+/// placed at the gap from the top-left, which is on screen everywhere
+const FALLBACK_OVERLAY_HEIGHT: f64 = 720.0;
+
+/// Window flags the OS doesn't let us read back.
+struct OverlayState {
+    clickthrough: AtomicBool,
+    compact: AtomicBool,
+    level: std::sync::Mutex<&'static str>,
+}
+
+/// Applies a stacking level to the overlay.
+///
+/// - `"normal"`: ordinary window
+/// - `"floating"`: above normal windows (plain always-on-top)
+/// - `"screen-saver"`: also above full-screen presentations. On macOS this
+///   joins all Spaces so the overlay follows into full-screen apps; on
+///   Windows topmost already covers full-screen (non-exclusive) apps. Linux
+///   window managers don't let clients stack above full-screen windows, so
+///   it is rejected there.
+fn apply_window_level(window: &tauri::Window, level: &str) -> Result<&'static str, String> {
+    let (level, on_top, all_workspaces) = match level {
+        "normal" => ("normal", false, false),
+        "floating" => ("floating", true, false),
+        "screen-saver" | "always" => {
+            if cfg!(target_os = "linux") {
+                return Err("The screen-saver window level is not supported on Linux".into());
+            }
+            ("screen-saver", true, true)
+        }
+        other => {
+            return Err(format!(
+                "Unknown window level '{}' (expected \"normal\", \"floating\" or \"screen-saver\")",
+                other
+            ))
+        }
+    };
+    window.set_always_on_top(on_top).map_err(|e| e.to_string())?;
+    window
+        .set_visible_on_all_workspaces(all_workspaces)
+        .map_err(|e| e.to_string())?;
+    Ok(level)
+}
+
+/// The monitor to dock on: the primary one, else the one the window is on,
+/// else the first available. Each failure is logged so a misplaced window
+/// can be explained.
+fn dock_monitor(window: &tauri::WebviewWindow) -> Option<tauri::Monitor> {
+    match window.primary_monitor() {
+        Ok(Some(monitor)) => return Some(monitor),
+        Ok(None) => log::warn!("No primary monitor reported; trying the current one"),
+        Err(e) => log::warn!("Primary monitor query failed: {}; trying the current one", e),
+    }
+    match window.current_monitor() {
+        Ok(Some(monitor)) => return Some(monitor),
+        Ok(None) => log::warn!("Window isn't on any monitor; trying the first available"),
+        Err(e) => log::warn!("Current monitor query failed: {}; trying the first available", e),
+    }
+    match window.available_monitors() {
+        Ok(monitors) => monitors.into_iter().next(),
+        Err(e) => {
+            log::warn!("Monitor enumeration failed: {}", e);
+            None
+        }
+    }
+}
+
+/// Docks the overlay at the right edge of the primary monitor, either as the
+/// full-height strip or as the compact pill in the top-right corner. With
+/// no monitor to measure, it falls back to a fixed size at the top-left.
+fn dock_overlay(window: &tauri::WebviewWindow, compact: bool) -> Result<(), String> {
+    let Some(monitor) = dock_monitor(window) else {
+        log::warn!("No monitor found; using the default overlay size and position");
+        let (width, height) = if compact {
+            (PILL_WIDTH, PILL_HEIGHT)
+        } else {
+            (OVERLAY_WIDTH, FALLBACK_OVERLAY_HEIGHT)
+        };
+        window
+            .set_size(tauri::Size::Logical(tauri::LogicalSize::new(width, height)))
+            .map_err(|e| e.to_string())?;
+        window
+            .set_position(tauri::Position::Logical(tauri::LogicalPosition::new(
+                OVERLAY_EDGE_GAP,
+                OVERLAY_EDGE_GAP,
+            )))
+            .map_err(|e| e.to_string())?;
+        return Ok(());
+    };
+    let origin = monitor.position();
+    let screen_size = monitor.size();
+    let scale = monitor.scale_factor();
+    let screen_width = screen_size.width as f64 / scale;
+    let screen_height = screen_size.height as f64 / scale;
+
+    let (width, height, y) = if compact {
+        (PILL_WIDTH, PILL_HEIGHT, OVERLAY_EDGE_GAP)
+    } else {
+        (OVERLAY_WIDTH, screen_height, 0.0)
+    };
+    // Fallback monitors needn't sit at the desktop origin
+    let x = origin.x as f64 / scale + screen_width - width - OVERLAY_EDGE_GAP;
+    let y = origin.y as f64 / scale + y;
+
+    window
+        .set_size(tauri::Size::Logical(tauri::LogicalSize::new(width, height)))
+        .map_err(|e| e.to_string())?;
+    window
+        .set_position(tauri::Position::Logical(tauri::LogicalPosition::new(x, y)))
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Moves the overlay, at its current size, into a corner of the monitor it
+/// is on, `margin_x`/`margin_y` logical pixels in from the edges. The result
+/// is clamped so the window stays on that monitor.
+fn pin_to_corner(window: &tauri::WebviewWindow, corner: &str, margin_x: f64, margin_y: f64) -> Result<(), String> {
+    let (right, bottom) = match corner {
+        "top-left" => (false, false),
+        "top-right" => (true, false),
+        "bottom-left" => (false, true),
+        "bottom-right" => (true, true),
+        other => {
+            return Err(format!(
+                "Unknown corner '{}' (expected \"top-left\", \"top-right\", \"bottom-left\" or \"bottom-right\")",
+                other
+            ))
+        }
+    };
+    if !(margin_x.is_finite() && margin_y.is_finite() && margin_x >= 0.0 && margin_y >= 0.0) {
+        return Err("Margins must be non-negative".into());
+    }
+
+    let monitor = match window.current_monitor().map_err(|e| e.to_string())? {
+        Some(monitor) => monitor,
+        None => window
+            .primary_monitor()
+            .map_err(|e| e.to_string())?
+            .ok_or("No monitor found")?,
+    };
+    let scale = monitor.scale_factor();
+    let origin_x = monitor.position().x as f64 / scale;
+    let origin_y = monitor.position().y as f64 / scale;
+    let screen_width = monitor.size().width as f64 / scale;
+    let screen_height = monitor.size().height as f64 / scale;
+    let size = window.outer_size().map_err(|e| e.to_string())?;
+    let width = size.width as f64 / scale;
+    let height = size.height as f64 / scale;
+
+    let max_x = (screen_width - width).max(0.0);
+    let max_y = (screen_height - height).max(0.0);
+    let x = if right { max_x - margin_x } else { margin_x }.clamp(0.0, max_x);
+    let y = if bottom { max_y - margin_y } else { margin_y }.clamp(0.0, max_y);
+
+    window
+        .set_position(tauri::Position::Logical(tauri::LogicalPosition::new(
+            origin_x + x,
+            origin_y + y,
+        )))
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Whether the overlay can be seen, for `capture_when_visible_only`: hidden
+/// or minimized counts as not visible. Focus isn't used: the overlay is
+/// click-through and normally unfocused while it's in use.
+fn overlay_visible(visible: tauri::Result<bool>, minimized: tauri::Result<bool>) -> bool {
+    visible.unwrap_or(true) && !minimized.unwrap_or(false)
+}
+
+/// How often the monitor layout is checked for dock/undock changes.
+const MONITOR_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Payload of the `"monitor-changed"` event.
+#[derive(Clone, serde::Serialize)]
+struct MonitorChanged {
+    monitors: usize,
+    /// Whether the overlay had to be moved back onto a visible monitor
+    repositioned: bool,
+}
+
+/// Position and size of every connected monitor, used to detect hotplug.
+fn monitor_layout(window: &tauri::WebviewWindow) -> Vec<(i32, i32, u32, u32)> {
+    window
+        .available_monitors()
+        .unwrap_or_default()
+        .iter()
+        .map(|m| (m.position().x, m.position().y, m.size().width, m.size().height))
+        .collect()
+}
+
+/// Watches for monitor hotplug (there is no native event for it) and, if the
+/// overlay's position is no longer on any monitor, docks it back onto the
+/// primary one. Emits `"monitor-changed"` whenever the layout changes.
+fn watch_monitors(window: tauri::WebviewWindow) {
+    std::thread::spawn(move || {
+        let mut last_layout = monitor_layout(&window);
+        loop {
+            std::thread::sleep(MONITOR_POLL_INTERVAL);
+            // Hiding the window raises no event either, so it's polled here too
+            audio::set_window_visible(
+                &window.state::<Arc<audio::AudioCaptureState>>(),
+                overlay_visible(window.is_visible(), window.is_minimized()),
+            );
+            let layout = monitor_layout(&window);
+            if layout == last_layout || layout.is_empty() {
+                continue;
+            }
+            last_layout = layout;
+
+            let on_screen = window.outer_position().is_ok_and(|pos| {
+                last_layout.iter().any(|&(x, y, w, h)| {
+                    pos.x >= x && pos.x < x + w as i32 && pos.y >= y && pos.y < y + h as i32
+                })
+            });
+
+            let mut repositioned = false;
+            if !on_screen {
+                let compact = window.state::<OverlayState>().compact.load(Ordering::Relaxed);
+                match dock_overlay(&window, compact) {
+                    Ok(()) => repositioned = true,
+                    Err(e) => log::warn!("Failed to reposition overlay after monitor change: {}", e),
+                }
+            }
+
+            log::info!(
+                "Monitor layout changed ({} monitors), repositioned: {}",
+                last_layout.len(),
+                repositioned
+            );
+            let _ = window.emit(
+                "monitor-changed",
+                MonitorChanged {
+                    monitors: last_layout.len(),
+                    repositioned,
+                },
+            );
+        }
+    });
+}
+
+/// How often the idle stop and its re-arm are checked.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Drives `set_idle_stop`: releases the devices once capture has been idle
+/// for the configured time, and reopens them when a re-arm comes due. This
+/// thread only keeps time; the streams are stopped and dropped, or built,
+/// on the main thread like in the commands.
+fn watch_idle(app: tauri::AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(IDLE_POLL_INTERVAL);
+        let main = app.clone();
+        let _ = app.run_on_main_thread(move || {
+            // Under the handles lock, so a start or stop can't land between
+            // the idle check and the drop
+            let streams = main.state::<AudioStreams>();
+            let Ok(mut lock) = streams.0.lock() else { return };
+            if audio::stop_if_idle(&main.state::<Arc<audio::AudioCaptureState>>()) {
+                *lock = None;
+            }
+        });
+
+        if audio::take_due_rearm(&app.state::<Arc<audio::AudioCaptureState>>()) {
+            match tauri::async_runtime::block_on(start_capture_off_main(app.clone())) {
+                Ok(()) => log::info!("Audio capture re-armed after idle stop"),
+                Err(e) => log::warn!("Re-arming audio capture failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(target_os = "macos")]
+const MIC_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// macOS leaves an open stream on the old device when the default input
+/// changes (e.g. AirPods connect). Polls the default input's name and, while
+/// capturing on the previous default, moves the mic over the same way
+/// `switch_mic_device` does, on the main thread. A mic the user picked
+/// stays put. Emits `"mic-device-changed"`.
+#[cfg(target_os = "macos")]
+fn watch_default_input(app: tauri::AppHandle) {
+    std::thread::spawn(move || {
+        let mut last_default = audio::default_input_name();
+        loop {
+            std::thread::sleep(MIC_POLL_INTERVAL);
+            let current = audio::default_input_name();
+            if current.is_none() || current == last_default {
+                continue;
+            }
+            let previous_default = std::mem::replace(&mut last_default, current.clone());
+            let Some(name) = current else { continue };
+
+            let main = app.clone();
+            let _ = app.run_on_main_thread(move || {
+                let state = main.state::<Arc<audio::AudioCaptureState>>();
+                if !state.running.load(Ordering::SeqCst) {
+                    return;
+                }
+                let mic = state.last_devices.lock().ok().and_then(|d| d.as_ref().map(|d| d.mic.clone()));
+                if mic != previous_default {
+                    log::info!("Default input changed to {}; keeping the selected mic", name);
+                    return;
+                }
+                match switch_mic_stream(&state, &main.state::<AudioStreams>(), &name) {
+                    Ok(change) => {
+                        log::info!("Default input changed: {} -> {}", change.previous, change.device);
+                        let _ = main.emit("mic-device-changed", change);
+                    }
+                    Err(e) => log::warn!("Failed to follow default input change: {}", e),
+                }
+            });
+        }
+    });
+}
+
 // ── Existing window commands ──
 
 #[tauri::command]
-fn set_clickthrough(window: tauri::Window, ignore: bool) {
+fn set_clickthrough(window: tauri::Window, overlay: tauri::State<'_, OverlayState>, ignore: bool) {
+    overlay.clickthrough.store(ignore, Ordering::Relaxed);
     let _ = window.set_ignore_cursor_events(ignore);
     if !ignore {
         let _ = window.set_focus();
     }
 }
 
+/// Stops capture, saves anything still buffered, and releases the devices.
+/// Runs before every exit path so quitting mid-utterance loses nothing.
+fn shutdown(app: &tauri::AppHandle) {
+    let state = app.state::<Arc<audio::AudioCaptureState>>();
+    audio::shutdown(&state);
+
+    let streams = app.state::<AudioStreams>();
+    if let Ok(mut lock) = streams.0.lock() {
+        *lock = None;
+    };
+}
+
 #[tauri::command]
 fn close_app(app: tauri::AppHandle) {
+    shutdown(&app);
     app.exit(0);
 }
 
 #[tauri::command]
-fn set_always_on_top(window: tauri::Window, enabled: bool) {
+fn set_always_on_top(window: tauri::Window, overlay: tauri::State<'_, OverlayState>, enabled: bool) {
     let _ = window.set_always_on_top(enabled);
+    if let Ok(mut level) = overlay.level.lock() {
+        *level = if enabled { "floating" } else { "normal" };
+    }
+}
+
+#[tauri::command]
+fn set_window_level(window: tauri::Window, overlay: tauri::State<'_, OverlayState>, level: String) -> Result<(), String> {
+    let applied = apply_window_level(&window, &level)?;
+    if let Ok(mut current) = overlay.level.lock() {
+        *current = applied;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn get_window_level(overlay: tauri::State<'_, OverlayState>) -> String {
+    overlay.level.lock().map(|l| l.to_string()).unwrap_or_default()
 }
 
 #[tauri::command]
@@ -45,25 +400,115 @@ fn resize_window(window: tauri::Window, width: f64, height: f64) {
     let _ = window.set_size(tauri::Size::Logical(tauri::LogicalSize::new(width, height)));
 }
 
+/// Switches between the compact pill and the full-height strip. Click-through
+/// is re-applied afterwards since some window managers reset it on resize.
+#[tauri::command]
+fn set_compact(window: tauri::WebviewWindow, overlay: tauri::State<'_, OverlayState>, compact: bool) -> Result<(), String> {
+    dock_overlay(&window, compact)?;
+    overlay.compact.store(compact, Ordering::Relaxed);
+    let _ = window.set_ignore_cursor_events(overlay.clickthrough.load(Ordering::Relaxed));
+    Ok(())
+}
+
+#[tauri::command]
+fn pin_corner(window: tauri::WebviewWindow, corner: String, margin_x: f64, margin_y: f64) -> Result<(), String> {
+    pin_to_corner(&window, &corner, margin_x, margin_y)
+}
+
 // ── Audio capture commands ──
 
 #[tauri::command]
-fn start_audio_capture(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, streams: tauri::State<'_, AudioStreams>) -> Result<String, String> {
-    // Check if already running
-    if state.running.load(std::sync::atomic::Ordering::Relaxed) {
-        return Err("Audio capture is already running".into());
-    }
+fn set_capture_when_visible_only(
+    window: tauri::Window,
+    state: tauri::State<'_, Arc<audio::AudioCaptureState>>,
+    enabled: bool,
+) {
+    audio::set_capture_when_visible_only(&state, enabled);
+    audio::set_window_visible(&state, overlay_visible(window.is_visible(), window.is_minimized()));
+}
+
+#[tauri::command]
+fn get_capture_status(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> audio::CaptureStatus {
+    audio::get_capture_status(&state)
+}
+
+#[tauri::command]
+async fn start_audio_capture(app: tauri::AppHandle) -> Result<String, String> {
+    start_capture_off_main(app).await?;
+    Ok("Audio capture started".into())
+}
 
-    let (mic_stream, loopback_stream) = audio::start_capture(state.inner().clone())?;
+/// Shared by `start_audio_capture`, the launch-time autostart and the idle
+/// re-arm. Device resolution, which can block on loopback probing, runs on
+/// the blocking pool; the streams are then built and stored on the main
+/// thread. `audio::prepare_capture` refuses with "Already running" if any
+/// session holds capture.
+async fn start_capture_off_main(app: tauri::AppHandle) -> Result<(), String> {
+    open_off_main(app, audio::prepare_capture, |prepared| {
+        prepared.open().map(|(mic, loopback)| (mic, loopback, ()))
+    })
+    .await
+}
+
+/// Both capture streams plus whatever else opening them reports.
+type Opened<T> = Result<(cpal::Stream, cpal::Stream, T), String>;
 
+/// Runs `prepare` on the blocking pool, then `open` and storing the streams
+/// on the main thread, handing back what `open` reports besides them.
+async fn open_off_main<P, T>(
+    app: tauri::AppHandle,
+    prepare: fn(Arc<audio::AudioCaptureState>) -> Result<P, String>,
+    open: fn(P) -> Opened<T>,
+) -> Result<T, String>
+where
+    P: Send + 'static,
+    T: Send + 'static,
+{
+    let state = app.state::<Arc<audio::AudioCaptureState>>().inner().clone();
+    let prepared = tauri::async_runtime::spawn_blocking(move || prepare(state))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let main = app.clone();
+    app.run_on_main_thread(move || {
+        let _ = tx.send(open(prepared).map(|(mic, loopback, report)| {
+            store_streams(&main.state::<AudioStreams>(), (mic, loopback));
+            report
+        }));
+    })
+    .map_err(|e| e.to_string())?;
+    tauri::async_runtime::spawn_blocking(move || rx.recv())
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|_| "Capture start was dropped".to_string())?
+}
+
+fn store_streams(streams: &AudioStreams, (mic_stream, loopback_stream): (cpal::Stream, cpal::Stream)) {
     if let Ok(mut lock) = streams.0.lock() {
         *lock = Some(StreamHandles {
             _mic: mic_stream,
             _loopback: loopback_stream,
         });
     }
+}
 
-    Ok("Audio capture started".into())
+/// Kiosk installs capture from launch without a frontend call. Enabled by
+/// `MAESTRO_AUTOSTART_CAPTURE=1` in the environment at launch, or at build
+/// time to bake it in. Off by default.
+fn autostart_enabled() -> bool {
+    std::env::var("MAESTRO_AUTOSTART_CAPTURE")
+        .ok()
+        .as_deref()
+        .or(option_env!("MAESTRO_AUTOSTART_CAPTURE"))
+        .is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
+
+/// Device resolution can probe, so like `start_audio_capture` it runs on
+/// the blocking pool and only opens the streams on the main thread.
+#[tauri::command]
+async fn restart_audio_capture(app: tauri::AppHandle) -> Result<audio::RestartReport, String> {
+    open_off_main(app, audio::prepare_restart, audio::PreparedRestart::open).await
 }
 
 #[tauri::command]
@@ -83,11 +528,582 @@ fn poll_audio_chunks(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) ->
     audio::drain_chunks(&state)
 }
 
+#[tauri::command]
+fn poll_audio_chunks_limited(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, max: usize) -> Result<Vec<audio::AudioChunk>, String> {
+    audio::drain_chunks_limited(&state, max)
+}
+
 #[tauri::command]
 fn get_talk_ratio(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> (f32, f32) {
     audio::get_talk_ratio(&state)
 }
 
+#[tauri::command]
+fn set_capture_mode(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, mode: String) -> Result<(), String> {
+    audio::set_capture_mode(&state, &mode)
+}
+
+#[tauri::command]
+fn set_mix_gains(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, mic_gain: f32, loopback_gain: f32) -> Result<(), String> {
+    audio::set_mix_gains(&state, mic_gain, loopback_gain)
+}
+
+#[tauri::command]
+fn annotate_chunk(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, seq: u64, meta: serde_json::Value) -> Result<(), String> {
+    audio::annotate_chunk(&state, seq, meta)
+}
+
+#[tauri::command]
+fn get_chunk_meta(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, seq: u64) -> Option<serde_json::Value> {
+    audio::get_chunk_meta(&state, seq)
+}
+
+#[tauri::command]
+fn set_adaptive_vad(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, enabled: bool, margin: f32) -> Result<(), String> {
+    audio::set_adaptive_vad(&state, enabled, margin)
+}
+
+#[tauri::command]
+fn get_speaking_state(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> audio::SpeakingState {
+    audio::get_speaking_state(&state)
+}
+
+#[tauri::command]
+fn set_loopback_rejection(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, threshold_scale: f32, min_speech_frames: usize) -> Result<(), String> {
+    audio::set_loopback_rejection(&state, threshold_scale, min_speech_frames)
+}
+
+#[tauri::command]
+fn export_talk_history_csv(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, path: String) -> Result<usize, String> {
+    audio::export_talk_history_csv(&state, &path)
+}
+
+#[tauri::command]
+fn set_limiter(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, enabled: bool) {
+    audio::set_limiter(&state, enabled);
+}
+
+#[tauri::command]
+fn set_endpoint_silence(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, ms: u32, short_ms: Option<u32>) -> Result<(), String> {
+    audio::set_endpoint_silence(&state, ms, short_ms)
+}
+
+/// Runs on the blocking pool: the probe holds a (non-Send) stream open for
+/// the whole window.
+#[tauri::command]
+async fn test_loopback(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, duration_ms: u64) -> Result<audio::LoopbackProbe, String> {
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || audio::probe_loopback(&state, duration_ms))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+fn set_recording_dir(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, dir: Option<String>) -> Result<(), String> {
+    audio::set_recording_dir(&state, dir)
+}
+
+#[tauri::command]
+fn list_loopback_candidates() -> Vec<audio::LoopbackCandidate> {
+    audio::list_loopback_candidates()
+}
+
+#[tauri::command]
+fn set_loopback_device(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, name: Option<String>) -> Result<(), String> {
+    audio::set_loopback_device(&state, name)
+}
+
+#[tauri::command]
+fn set_vad_warmup(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, ms: u32) {
+    audio::set_vad_warmup(&state, ms);
+}
+
+#[tauri::command]
+fn set_vad_cooldown(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, ms: u32) {
+    audio::set_vad_cooldown(&state, ms);
+}
+
+#[tauri::command]
+fn set_energy_events(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, enabled: bool, interval_ms: u32) -> Result<(), String> {
+    audio::set_energy_events(&state, enabled, interval_ms)
+}
+
+/// Opens its own stream for the clip, so it runs on the blocking pool. Fails
+/// while another session is active.
+#[tauri::command]
+async fn record_clip(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, source: String, duration_secs: f32) -> Result<audio::AudioChunk, String> {
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || audio::record_clip(&state, &source, duration_secs))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Blocks for the window, so it runs on the blocking pool.
+#[tauri::command]
+async fn diagnostic_raw_capture(
+    state: tauri::State<'_, Arc<audio::AudioCaptureState>>,
+    source: String,
+    duration_ms: u64,
+) -> Result<audio::RawCaptureReport, String> {
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || audio::diagnostic_raw_capture(state, &source, duration_ms))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+fn connect_backend(app: tauri::AppHandle, state: tauri::State<'_, Arc<audio::AudioCaptureState>>, url: String, relay_to_frontend: bool) -> Result<(), String> {
+    backend::connect(&state, app, url, relay_to_frontend)
+}
+
+#[tauri::command]
+fn disconnect_backend(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) {
+    backend::disconnect(&state);
+}
+
+#[tauri::command]
+fn set_speech_bandpass(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, enabled: bool, filter_output: bool) {
+    audio::set_speech_bandpass(&state, enabled, filter_output);
+}
+
+#[tauri::command]
+fn set_suspend_on_silence(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, ms: u32) {
+    audio::set_suspend_on_silence(&state, ms);
+}
+
+#[tauri::command]
+fn set_dither(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, enabled: bool) {
+    audio::set_dither(&state, enabled);
+}
+
+#[tauri::command]
+fn get_dither(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> bool {
+    audio::get_dither(&state)
+}
+
+#[tauri::command]
+fn set_source_target_rate(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, source: String, hz: u32) -> Result<(), String> {
+    audio::set_source_target_rate(&state, &source, hz)
+}
+
+#[tauri::command]
+fn validate_capture_config(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> Result<audio::ConfigValidation, String> {
+    audio::validate_capture_config(state.inner().clone())
+}
+
+#[tauri::command]
+fn set_sample_type(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, sample_type: String) -> Result<(), String> {
+    audio::set_sample_type(&state, &sample_type)
+}
+
+#[tauri::command]
+fn set_output_ceiling_dbfs(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, value: f32) -> Result<(), String> {
+    audio::set_output_ceiling_dbfs(&state, value)
+}
+
+#[tauri::command]
+fn set_buffer_size(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, frames: u32) -> Result<(), String> {
+    audio::set_buffer_size(&state, frames)
+}
+
+#[tauri::command]
+fn get_session_id(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> String {
+    audio::get_session_id(&state)
+}
+
+#[tauri::command]
+fn compute_spectrogram(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, seq: u64, n_fft: usize, hop: usize) -> Result<audio::Spectrogram, String> {
+    audio::compute_spectrogram(&state, seq, n_fft, hop)
+}
+
+#[tauri::command]
+fn set_barge_in_suppression(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, enabled: bool, db_raise: f32) -> Result<(), String> {
+    audio::set_barge_in_suppression(&state, enabled, db_raise)
+}
+
+#[tauri::command]
+fn reset_capture_state(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) {
+    audio::reset_capture_state(&state);
+}
+
+#[tauri::command]
+fn set_energy_trace(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, enabled: bool) {
+    audio::set_energy_trace(&state, enabled);
+}
+
+#[tauri::command]
+fn set_min_chunk_secs(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, secs: f32) -> Result<(), String> {
+    audio::set_min_chunk_secs(&state, secs)
+}
+
+#[tauri::command]
+fn describe_loopback_strategy(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> audio::LoopbackStrategy {
+    audio::describe_loopback_strategy(&state)
+}
+
+#[tauri::command]
+fn set_backpressure_policy(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, policy: String) -> Result<(), String> {
+    audio::set_backpressure_policy(&state, &policy)
+}
+
+#[tauri::command]
+fn get_buffer_stats(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> audio::BufferStats {
+    audio::get_buffer_stats(&state)
+}
+
+#[tauri::command]
+fn measure_latency(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> Result<audio::LatencyReport, String> {
+    audio::measure_latency(&state)
+}
+
+#[tauri::command]
+fn set_phase_autocorrect(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, enabled: bool) {
+    audio::set_phase_autocorrect(&state, enabled);
+}
+
+#[tauri::command]
+fn get_stream_health(
+    state: tauri::State<'_, Arc<audio::AudioCaptureState>>,
+    streams: tauri::State<'_, AudioStreams>,
+) -> audio::StreamHealth {
+    let held = streams.0.lock().map(|s| s.is_some()).unwrap_or(false);
+    audio::stream_health(&state, held)
+}
+
+#[tauri::command]
+fn set_max_chunk_b64_bytes(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, bytes: usize) -> Result<(), String> {
+    audio::set_max_chunk_b64_bytes(&state, bytes)
+}
+
+#[tauri::command]
+fn swap_sources(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> Result<audio::DeviceSelection, String> {
+    audio::swap_sources(&state)
+}
+
+/// Feeds a WAV/MP3 file through the VAD as `source_label`, in place of a
+/// device. Stop it with `stop_audio_capture`.
+#[tauri::command]
+fn start_file_source(
+    state: tauri::State<'_, Arc<audio::AudioCaptureState>>,
+    streams: tauri::State<'_, AudioStreams>,
+    path: String,
+    source_label: String,
+    speed: Option<f32>,
+) -> Result<audio::FileSourceInfo, String> {
+    if streams.0.lock().is_ok_and(|s| s.is_some()) {
+        return Err("Stop the device streams before starting a file source".into());
+    }
+    audio::start_file_source(state.inner().clone(), &path, &source_label, speed)
+}
+
+#[tauri::command]
+fn set_wasapi_exclusive(enabled: bool) -> Result<(), String> {
+    audio::set_wasapi_exclusive(enabled)
+}
+
+#[tauri::command]
+fn set_vad_release_decrement(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, frames: usize) -> Result<(), String> {
+    audio::set_vad_release_decrement(&state, frames)
+}
+
+#[tauri::command]
+fn get_balance_score(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> audio::BalanceScore {
+    audio::get_balance_score(&state)
+}
+
+#[tauri::command]
+fn set_echo_cancellation(
+    state: tauri::State<'_, Arc<audio::AudioCaptureState>>,
+    enabled: bool,
+    tail_ms: Option<u32>,
+) -> Result<(), String> {
+    audio::set_echo_cancellation(&state, enabled, tail_ms)
+}
+
+#[tauri::command]
+fn set_include_tail_silence(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, enabled: bool) {
+    audio::set_include_tail_silence(&state, enabled);
+}
+
+#[tauri::command]
+fn get_xrun_stats(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> audio::XrunStats {
+    audio::get_xrun_stats(&state)
+}
+
+#[tauri::command]
+fn export_settings(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> audio::SettingsProfile {
+    audio::export_settings(&state)
+}
+
+#[tauri::command]
+fn import_settings(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, json: String) -> Result<audio::ImportReport, String> {
+    audio::import_settings(&state, &json)
+}
+
+#[tauri::command]
+fn get_channel_stats(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> audio::ChannelReport {
+    audio::get_channel_stats(&state)
+}
+
+#[tauri::command]
+fn set_realtime_priority(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, enabled: bool) {
+    audio::set_realtime_priority(&state, enabled);
+}
+
+#[tauri::command]
+fn anyone_speaking(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> bool {
+    audio::anyone_speaking(&state)
+}
+
+#[tauri::command]
+fn set_speaking_hold(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, ms: u32) -> Result<(), String> {
+    audio::set_speaking_hold(&state, ms)
+}
+
+#[tauri::command]
+fn set_chunk_container(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, container: String) -> Result<(), String> {
+    audio::set_chunk_container(&state, &container)
+}
+
+#[tauri::command]
+fn set_source_counts_in_ratio(
+    state: tauri::State<'_, Arc<audio::AudioCaptureState>>,
+    source: String,
+    counts: bool,
+) -> Result<(), String> {
+    audio::set_source_counts_in_ratio(&state, &source, counts)
+}
+
+#[tauri::command]
+fn set_preferred_input_config(
+    state: tauri::State<'_, Arc<audio::AudioCaptureState>>,
+    sample_rate: u32,
+    channels: u16,
+) -> Result<(), String> {
+    audio::set_preferred_input_config(&state, sample_rate, channels)
+}
+
+#[tauri::command]
+fn set_wake_trigger(
+    state: tauri::State<'_, Arc<audio::AudioCaptureState>>,
+    enabled: bool,
+    speech_ms: u32,
+) -> Result<(), String> {
+    audio::set_wake_trigger(&state, enabled, speech_ms)
+}
+
+#[tauri::command]
+fn export_source_session_wav(
+    state: tauri::State<'_, Arc<audio::AudioCaptureState>>,
+    source: String,
+    path: String,
+    comfort_noise_dbfs: Option<f32>,
+) -> Result<audio::SourceExport, String> {
+    audio::export_source_session_wav(&state, &source, &path, comfort_noise_dbfs)
+}
+
+#[tauri::command]
+fn set_downmix_compensation(
+    state: tauri::State<'_, Arc<audio::AudioCaptureState>>,
+    enabled: bool,
+    gain: Option<f32>,
+) -> Result<(), String> {
+    audio::set_downmix_compensation(&state, enabled, gain)
+}
+
+/// What `get_build_info` reports, for bug reports.
+#[derive(Clone, serde::Serialize)]
+struct BuildInfo {
+    version: &'static str,
+    /// Short commit hash; empty if built outside a git checkout
+    git_hash: &'static str,
+    target: &'static str,
+    debug: bool,
+    /// Enabled optional cargo features of this crate
+    features: Vec<&'static str>,
+}
+
+#[tauri::command]
+fn get_build_info() -> BuildInfo {
+    let features = env!("MAESTRO_FEATURES");
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_hash: env!("MAESTRO_GIT_HASH"),
+        target: env!("MAESTRO_TARGET"),
+        debug: cfg!(debug_assertions),
+        features: features.split(',').filter(|f| !f.is_empty()).collect(),
+    }
+}
+
+#[tauri::command]
+fn set_idle_stop(
+    state: tauri::State<'_, Arc<audio::AudioCaptureState>>,
+    minutes: u32,
+    rearm_minutes: u32,
+) -> Result<(), String> {
+    audio::set_idle_stop(&state, minutes, rearm_minutes)
+}
+
+#[tauri::command]
+fn set_vad_log(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, enabled: bool, clear: bool) {
+    audio::set_vad_log(&state, enabled, clear);
+}
+
+#[tauri::command]
+fn get_vad_log(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> audio::VadLog {
+    audio::get_vad_log(&state)
+}
+
+#[tauri::command]
+fn set_ptt_mode(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, enabled: bool) {
+    audio::set_ptt_mode(&state, enabled);
+}
+
+#[tauri::command]
+fn set_ptt(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, active: bool) -> Result<(), String> {
+    audio::set_ptt(&state, active)
+}
+
+#[tauri::command]
+fn resample_chunk(
+    state: tauri::State<'_, Arc<audio::AudioCaptureState>>,
+    seq: u64,
+    target_rate: u32,
+) -> Result<audio::AudioChunk, String> {
+    audio::resample_chunk(&state, seq, target_rate)
+}
+
+#[tauri::command]
+fn set_meter_floor(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, dbfs: f32) -> Result<(), String> {
+    audio::set_meter_floor(&state, dbfs)
+}
+
+#[tauri::command]
+fn set_vad_analysis_filter(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, kind: String) -> Result<(), String> {
+    audio::set_vad_analysis_filter(&state, &kind)
+}
+
+#[tauri::command]
+fn diagnostics_snapshot(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> audio::DiagnosticsSnapshot {
+    audio::diagnostics_snapshot(&state)
+}
+
+#[tauri::command]
+fn set_peak_normalize(
+    state: tauri::State<'_, Arc<audio::AudioCaptureState>>,
+    enabled: bool,
+    target_dbfs: Option<f32>,
+    boost: bool,
+) -> Result<(), String> {
+    audio::set_peak_normalize(&state, enabled, target_dbfs, boost)
+}
+
+#[tauri::command]
+fn get_talk_ratio_buckets(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, bucket_secs: u32) -> Result<Vec<audio::TalkBucket>, String> {
+    audio::get_talk_ratio_buckets(&state, bucket_secs)
+}
+
+#[tauri::command]
+fn check_mic_permission(prompt: bool) -> audio::MicPermission {
+    audio::check_mic_permission(prompt)
+}
+
+#[tauri::command]
+fn set_continuous_record(
+    state: tauri::State<'_, Arc<audio::AudioCaptureState>>,
+    enabled: bool,
+    window_secs: Option<u32>,
+) -> Result<(), String> {
+    audio::set_continuous_record(&state, enabled, window_secs)
+}
+
+#[tauri::command]
+fn get_latency_breakdown(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> audio::LatencyBreakdown {
+    audio::get_latency_breakdown(&state)
+}
+
+#[tauri::command]
+fn set_loopback_fallback(
+    state: tauri::State<'_, Arc<audio::AudioCaptureState>>,
+    enabled: bool,
+    probe_ms: Option<u32>,
+) -> Result<(), String> {
+    audio::set_loopback_fallback(&state, enabled, probe_ms)
+}
+
+#[tauri::command]
+fn set_source_speaker(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, source: String, name: String) -> Result<(), String> {
+    audio::set_source_speaker(&state, &source, &name)
+}
+
+#[tauri::command]
+fn get_recent_chunks_by_speaker(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, speaker: String) -> Vec<audio::AudioChunk> {
+    audio::get_recent_chunks_by_speaker(&state, &speaker)
+}
+
+/// Moves the mic to another input mid-session without a stop/start gap: the
+/// new stream starts before the old one is dropped, and the accumulator is
+/// handed over between two callbacks. Sync, so it runs on the main thread
+/// like every other command touching the `!Send` streams. Emits
+/// `"mic-device-changed"`.
+#[tauri::command]
+fn switch_mic_device(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, Arc<audio::AudioCaptureState>>,
+    streams: tauri::State<'_, AudioStreams>,
+    name: String,
+) -> Result<audio::MicDeviceChanged, String> {
+    let change = switch_mic_stream(state.inner(), &streams, &name)?;
+    log::info!("Mic switched: {} -> {}", change.previous, change.device);
+    let _ = app.emit("mic-device-changed", change.clone());
+    Ok(change)
+}
+
+/// The handover behind `switch_mic_device` and the macOS default-input
+/// watcher. If the new stream won't play, the old one keeps capturing.
+/// Main thread only.
+fn switch_mic_stream(state: &Arc<audio::AudioCaptureState>, streams: &AudioStreams, name: &str) -> Result<audio::MicDeviceChanged, String> {
+    use cpal::traits::StreamTrait;
+
+    let mut lock = streams.0.lock().map_err(|_| "Stream handles are unavailable".to_string())?;
+    let handles = lock.as_mut().ok_or("Audio capture is not running")?;
+
+    let stream = audio::prepare_mic_switch(state.clone(), name)?;
+    if let Err(e) = stream.play() {
+        audio::abort_mic_switch(state);
+        return Err(format!("New mic stream failed to play: {}", e));
+    }
+    let change = audio::commit_mic_switch(state, name);
+    // The old stream went silent at the handover; dropping it closes the device
+    handles._mic = stream;
+    Ok(change)
+}
+
+#[tauri::command]
+fn set_max_utterance_secs(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, secs: u32) -> Result<(), String> {
+    audio::set_max_utterance_secs(&state, secs)
+}
+
+#[tauri::command]
+fn set_turn_end_markers(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, enabled: bool) {
+    audio::set_turn_end_markers(&state, enabled);
+}
+
+#[tauri::command]
+fn mark_turn_end(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, source: String) -> Result<(), String> {
+    audio::mark_turn_end(&state, &source)
+}
+
+#[tauri::command]
+fn set_flush_order(state: tauri::State<'_, Arc<audio::AudioCaptureState>>, order: String) -> Result<(), String> {
+    audio::set_flush_order(&state, &order)
+}
+
+#[tauri::command]
+fn get_avg_utterance_length(state: tauri::State<'_, Arc<audio::AudioCaptureState>>) -> audio::UtteranceLengths {
+    audio::get_avg_utterance_length(&state)
+}
+
 #[tauri::command]
 fn list_audio_devices() -> serde_json::Value {
     serde_json::json!({
@@ -106,37 +1122,159 @@ pub fn run() {
         .plugin(tauri_plugin_process::init())
         .manage(capture_state)
         .manage(AudioStreams(std::sync::Mutex::new(None)))
+        .manage(OverlayState {
+            clickthrough: AtomicBool::new(true),
+            compact: AtomicBool::new(false),
+            level: std::sync::Mutex::new("floating"),
+        })
         .setup(|app| {
+            audio::attach_app(&app.state::<Arc<audio::AudioCaptureState>>(), app.handle().clone());
+
             let window = app.get_webview_window("main").unwrap();
 
             // Position at right edge of primary monitor
-            if let Ok(Some(monitor)) = window.primary_monitor() {
-                let screen_size = monitor.size();
-                let scale = monitor.scale_factor();
-                let x = (screen_size.width as f64 / scale) - 390.0;
-                let _ = window.set_position(tauri::Position::Logical(
-                    tauri::LogicalPosition::new(x, 0.0),
-                ));
-                let _ = window.set_size(tauri::Size::Logical(
-                    tauri::LogicalSize::new(380.0, screen_size.height as f64 / scale),
-                ));
+            if let Err(e) = dock_overlay(&window, false) {
+                log::warn!("Failed to position the overlay at startup: {}", e);
             }
 
             // Start in click-through mode
             let _ = window.set_ignore_cursor_events(true);
 
+            watch_monitors(window);
+
+            if autostart_enabled() {
+                let handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    match start_capture_off_main(handle.clone()).await {
+                        Ok(()) => log::info!("Audio capture auto-started"),
+                        Err(e) => {
+                            log::error!("Audio capture autostart failed: {}", e);
+                            let _ = handle.emit("autostart-failed", e);
+                        }
+                    }
+                });
+            }
+
+            watch_idle(app.handle().clone());
+
+            #[cfg(target_os = "macos")]
+            watch_default_input(app.handle().clone());
+
             Ok(())
         })
+        .on_window_event(|window, event| match event {
+            tauri::WindowEvent::CloseRequested { .. } => shutdown(window.app_handle()),
+            tauri::WindowEvent::Focused(_) | tauri::WindowEvent::Resized(_) => audio::set_window_visible(
+                &window.state::<Arc<audio::AudioCaptureState>>(),
+                overlay_visible(window.is_visible(), window.is_minimized()),
+            ),
+            _ => {}
+        })
         .invoke_handler(tauri::generate_handler![
             set_clickthrough,
             close_app,
             set_always_on_top,
+            set_window_level,
+            get_window_level,
             resize_window,
+            set_compact,
             start_audio_capture,
+            restart_audio_capture,
             stop_audio_capture,
             poll_audio_chunks,
             get_talk_ratio,
             list_audio_devices,
+            set_capture_mode,
+            set_mix_gains,
+            annotate_chunk,
+            get_chunk_meta,
+            set_adaptive_vad,
+            get_speaking_state,
+            set_loopback_rejection,
+            export_talk_history_csv,
+            set_limiter,
+            set_endpoint_silence,
+            test_loopback,
+            set_recording_dir,
+            list_loopback_candidates,
+            set_loopback_device,
+            set_vad_warmup,
+            set_energy_events,
+            record_clip,
+            set_vad_cooldown,
+            connect_backend,
+            disconnect_backend,
+            set_speech_bandpass,
+            poll_audio_chunks_limited,
+            set_suspend_on_silence,
+            set_dither,
+            get_dither,
+            set_source_target_rate,
+            validate_capture_config,
+            set_sample_type,
+            set_output_ceiling_dbfs,
+            set_buffer_size,
+            get_session_id,
+            compute_spectrogram,
+            set_barge_in_suppression,
+            reset_capture_state,
+            set_energy_trace,
+            set_min_chunk_secs,
+            describe_loopback_strategy,
+            set_backpressure_policy,
+            get_buffer_stats,
+            measure_latency,
+            set_phase_autocorrect,
+            get_stream_health,
+            set_max_chunk_b64_bytes,
+            swap_sources,
+            start_file_source,
+            set_wasapi_exclusive,
+            set_vad_release_decrement,
+            get_balance_score,
+            set_echo_cancellation,
+            set_include_tail_silence,
+            get_xrun_stats,
+            export_settings,
+            import_settings,
+            get_channel_stats,
+            set_realtime_priority,
+            anyone_speaking,
+            set_speaking_hold,
+            set_chunk_container,
+            set_source_counts_in_ratio,
+            set_preferred_input_config,
+            set_wake_trigger,
+            export_source_session_wav,
+            set_downmix_compensation,
+            get_build_info,
+            set_idle_stop,
+            set_vad_log,
+            get_vad_log,
+            set_ptt_mode,
+            set_ptt,
+            resample_chunk,
+            set_meter_floor,
+            pin_corner,
+            set_capture_when_visible_only,
+            get_capture_status,
+            set_vad_analysis_filter,
+            diagnostics_snapshot,
+            set_peak_normalize,
+            get_talk_ratio_buckets,
+            check_mic_permission,
+            set_continuous_record,
+            get_latency_breakdown,
+            set_loopback_fallback,
+            set_source_speaker,
+            get_recent_chunks_by_speaker,
+            switch_mic_device,
+            set_max_utterance_secs,
+            set_turn_end_markers,
+            mark_turn_end,
+            diagnostic_raw_capture,
+            set_flush_order,
+            get_avg_utterance_length,
         ])
         .run(tauri::generate_context!())
         .expect("error while running MAESTRO");
@@ -2,9 +2,49 @@ import { invoke } from "@tauri-apps/api/core";
 
 export interface AudioChunk {
   audio_b64: string;
-  source: "mic" | "loopback";
+  source: "mic" | "loopback" | "mixed";
   duration_secs: number;
   sample_count: number;
+  /** PCM rate: the source's target rate (16000 unless changed). */
+  sample_rate: number;
+  /** PCM encoding of audio_b64: 16-bit int (default) or 32-bit float. */
+  sample_type: "i16" | "f32";
+  bits_per_sample: 16 | 32;
+  /** "wav": audio_b64 is a complete WAV file, playable via a data: URL. */
+  container: "raw" | "wav";
+  /** Monotonic nanoseconds stamped when the chunk was queued in Rust. */
+  emit_nanos: number;
+  /** Session-wide emission order. */
+  seq: number;
+  /** Wall-clock ms since the epoch at which the chunk's audio began (estimated). */
+  onset_ms: number;
+  /** UUID of the capture session, regenerated on every start. */
+  session_id: string;
+  /** Downstream annotations attached via annotateChunk. */
+  meta: unknown | null;
+  /** "mono" for a single source, "mixed" for mic + loopback summed. */
+  layout: "mono" | "mixed";
+  /** Capture device name ("<mic> + <loopback>" for mixed chunks). */
+  device_name: string;
+  /** Gap since this source's previous chunk ended (or capture started). */
+  preceding_silence_secs: number;
+  /** RMS per 50 ms across the chunk; empty unless setEnergyTrace(true). */
+  energy_trace: number[];
+  /** Shared by all parts of an utterance split for exceeding the size limit. */
+  utterance_id: string;
+  /** Index of this part (0-based) and the part count (1 unless split). */
+  part: number;
+  parts: number;
+  /** Gain applied by peak normalization, in dB (0 when off). */
+  normalize_gain_db: number;
+  /** Capture mode that produced the chunk (a chunk flushed right after a switch keeps the old one). */
+  mode: "per-source" | "mixed-mono";
+  /** False only for a continuous-recording window with no speech in it. */
+  contains_speech: boolean;
+  /** setSourceSpeaker label, else the source name; null for mixed chunks. */
+  speaker: string | null;
+  /** "turn-end" on zero-length marker chunks (no audio); null for audio. */
+  marker: "turn-end" | null;
 }
 
 export interface AudioDevices {
@@ -12,6 +52,373 @@ export interface AudioDevices {
   output: string[];
 }
 
+export interface RestartReport {
+  devices: { mic: string; loopback: string };
+  /** One note per remembered device that was missing and replaced by a default. */
+  substitutions: string[];
+}
+
+export interface LoopbackProbe {
+  device_name: string;
+  peak: number;
+  rms: number;
+  /** 0 means the device delivered no audio callbacks at all. */
+  samples: number;
+  signal_detected: boolean;
+}
+
+export interface LoopbackCandidate {
+  name: string;
+  kind: "monitor" | "output";
+  /** What auto-detection would pick. */
+  auto_selected: boolean;
+}
+
+/** Payload of the "monitor-changed" event. */
+export interface MonitorChanged {
+  monitors: number;
+  repositioned: boolean;
+}
+
+/** Payload of the "energy-frame" event. */
+export interface EnergyFrame {
+  source: "mic" | "loopback";
+  /** Clamped to the meter floor (default -90 dBFS) */
+  rms_dbfs: number;
+  /** The level was at or below the meter floor */
+  silence: boolean;
+}
+
+/** Payload of the "sample-rate-changed" event. */
+export interface SampleRateChange {
+  source: "mic" | "loopback";
+  previous_hz: number;
+  detected_hz: number;
+}
+
+export interface StreamInfo {
+  device: string;
+  sample_rate: number;
+  channels: number;
+}
+
+/**
+ * Payload of the "capture-started" event ("capture-stopped" has none),
+ * sent only once both the mic and loopback streams are running.
+ * With MAESTRO_AUTOSTART_CAPTURE=1 capture starts at launch; a failure is
+ * reported as an "autostart-failed" event carrying the error string.
+ */
+export interface CaptureStarted {
+  mic: StreamInfo;
+  loopback: StreamInfo;
+}
+
+export interface SourceValidation {
+  ok: boolean;
+  device: string | null;
+  sample_rate: number | null;
+  channels: number | null;
+  error: string | null;
+}
+
+export interface ConfigValidation {
+  mic: SourceValidation;
+  loopback: SourceValidation;
+}
+
+/** Payload of the "mic-device-changed" event (macOS default-input follow, or switchMicDevice). */
+export interface MicDeviceChanged {
+  previous: string;
+  device: string;
+}
+
+export interface Spectrogram {
+  seq: number;
+  sample_rate: number;
+  n_fft: number;
+  hop: number;
+  /** One row per hop: n_fft / 2 + 1 linear magnitudes, DC to Nyquist. */
+  frames: number[][];
+}
+
+export interface LoopbackStrategy {
+  platform: string;
+  host: string;
+  method:
+    | "user-selected"
+    | "linux-monitor-source"
+    | "linux-default-output-fallback"
+    | "wasapi-default-output"
+    | "macos-default-output"
+    /** loopback fallback on: the last start's probe choice, or no probe yet */
+    | "probed-default-output"
+    | "probed-monitor-source"
+    | "probe-pending";
+  device: string | null;
+}
+
+export type BackpressurePolicy = "buffer" | "drop-oldest" | "coalesce";
+
+export interface BufferStats {
+  queued: number;
+  /** Queue length at which the policy acts. */
+  capacity: number;
+  policy: BackpressurePolicy;
+  dropped: number;
+  coalesced: number;
+}
+
+export interface LatencyReport {
+  frame_ms: number;
+  buffer_ms: number | null;
+  vad_endpoint_ms: number;
+  processing_ms: number;
+  total_ms: number;
+}
+
+/** Payload of the one-time "phase-inversion-detected" event. */
+export interface PhaseInversion {
+  source: "mic" | "loopback";
+  correlation: number;
+  downmix: "first-channel";
+}
+
+export interface SourceHealth {
+  held: boolean;
+  device: string | null;
+  /** null if the stream never called back */
+  last_callback_ms_ago: number | null;
+  /** Held, but no callback for 2s: the stream is dead */
+  stale: boolean;
+}
+
+export interface StreamHealth {
+  running: boolean;
+  mic: SourceHealth;
+  loopback: SourceHealth;
+}
+
+export interface FileSourceInfo {
+  path: string;
+  source: "mic" | "loopback";
+  sample_rate: number;
+  channels: number;
+  /** null if the file doesn't state its length. */
+  duration_secs: number | null;
+}
+
+/** Payload of the "file-source-finished" event. */
+export interface FileSourceFinished {
+  path: string;
+  source: "mic" | "loopback";
+  fed_secs: number;
+  /** Stopped via stopAudioCapture before the end of the file. */
+  stopped: boolean;
+  error: string | null;
+}
+
+export interface BalanceScore {
+  /** 0–100: 50 balanced, >50 mic-heavy, <50 loopback-heavy. */
+  score: number;
+  mic_speech_secs: number;
+  loopback_speech_secs: number;
+}
+
+export interface TalkBucket {
+  /** ms since the UNIX epoch, a multiple of the bucket length. */
+  bucket_start: number;
+  mic_secs: number;
+  loopback_secs: number;
+}
+
+export interface SourceXruns {
+  xruns: number;
+  /** Estimated from capture timestamp gaps, in device frames. */
+  dropped_frames: number;
+}
+
+export interface XrunStats {
+  mic: SourceXruns;
+  loopback: SourceXruns;
+}
+
+/** Payload of the "xrun-spike" event. */
+export interface XrunSpike {
+  source: "mic" | "loopback";
+  xruns: number;
+  window_secs: number;
+}
+
+/**
+ * Full audio configuration from exportSettings. `settings` mirrors the Rust
+ * AudioSettings struct; keep it opaque and pass it back to importSettings.
+ */
+export interface SettingsProfile {
+  settings: Record<string, unknown>;
+  capture_mode: "per-source" | "mixed-mono";
+  backpressure: "buffer" | "drop-oldest" | "coalesce";
+  mic_gain: number;
+  loopback_gain: number;
+  loopback_device: string | null;
+}
+
+export interface ImportReport {
+  /** Device-level settings that apply on the next start. */
+  restart_required: string[];
+  /** Settings this platform doesn't support. */
+  ignored: string[];
+}
+
+export interface ChannelStats {
+  channels: number;
+  /** Channels that actually differ; below `channels` means duplicated mono. null until measured. */
+  effective_channels: number | null;
+}
+
+export interface ChannelReport {
+  mic: ChannelStats;
+  loopback: ChannelStats;
+}
+
+/** Payload of the "wake" event. */
+export interface Wake {
+  speech_ms: number;
+  /** Silence before the utterance; null for the first one of a session */
+  silence_ms: number | null;
+}
+
+/** Result of exportSourceSessionWav. */
+export interface SourceExport {
+  chunks: number;
+  /** Length of the written file, silence included */
+  duration_secs: number;
+}
+
+/** Result of getBuildInfo. */
+export interface BuildInfo {
+  version: string;
+  /** Short commit hash; empty if built outside a git checkout */
+  git_hash: string;
+  target: string;
+  debug: boolean;
+  features: string[];
+}
+
+/** Payload of the "idle-stop" event. */
+export interface IdleStop {
+  idle_secs: number;
+  /** When capture reopens; null if no re-arm is set */
+  rearm_in_secs: number | null;
+}
+
+/** One VAD transition from getVadLog. */
+export interface VadEvent {
+  kind: "speech_start" | "flush" | "discard_short" | "size_split";
+  /** Wall clock, Unix ms */
+  ts_ms: number;
+  /** Position in the source's audio */
+  audio_ms: number;
+  rms: number;
+  threshold: number;
+  speech_frames: number;
+  buffered_secs: number;
+}
+
+/** Result of getVadLog. */
+export interface VadLog {
+  enabled: boolean;
+  mic: VadEvent[];
+  loopback: VadEvent[];
+  mixed: VadEvent[];
+}
+
+/** Result of getCaptureStatus. */
+export interface CaptureStatus {
+  running: boolean;
+  /** Running, but dropping audio because the overlay is hidden */
+  privacy_paused: boolean;
+  capture_when_visible_only: boolean;
+  session_id: string;
+  /** "device capture", "file source" or "clip recording"; null when idle */
+  active_session: string | null;
+}
+
+/** Payload of the "privacy-paused" event. */
+export interface PrivacyPause {
+  paused: boolean;
+}
+
+/** OS microphone access; "not_determined" is macOS-only, "unsupported" means nothing to query (Linux). */
+export type MicPermission = "granted" | "denied" | "not_determined" | "unsupported";
+
+/** One source's live latency by stage, in ms (see getLatencyBreakdown). */
+export interface StageLatency {
+  /** Latest device callback buffer; null before the first callback. */
+  buffer_ms: number | null;
+  /** Always 0: the linear-interpolation resampler adds no group delay. */
+  resampler_ms: number;
+  /** Endpoint silence; the window length in continuous recording, 0 for push-to-talk. */
+  vad_endpoint_ms: number;
+  /** Endpoint after a rising-energy tail, when set shorter; null otherwise. */
+  vad_short_endpoint_ms: number | null;
+  /** Post-flush window needing sustained speech; gates, not in total_ms. */
+  vad_cooldown_ms: number;
+  /** Latest chunk's encode time; null before the first chunk. */
+  encode_ms: number | null;
+  /** Buffer + full endpoint + encode. */
+  total_ms: number;
+}
+
+/** Payload of the "loopback-strategy" event. */
+export interface LoopbackStrategyChosen {
+  method: "default-output" | "monitor-source";
+  device: string;
+  /** False when every strategy was silent and the first that opened was kept. */
+  signal_detected: boolean;
+  /** Strategies tried before it, with the reason each was skipped. */
+  rejected: string[];
+}
+
+/** Payload of the "raw-audio" event: 100 ms of pre-VAD audio from diagnosticRawCapture. */
+export interface RawAudio {
+  source: "mic" | "loopback";
+  index: number;
+  sample_rate: number;
+  /** base64 little-endian i16 mono PCM. */
+  audio_b64: string;
+  peak: number;
+  rms_dbfs: number;
+}
+
+export interface RawCaptureReport {
+  /** "session": tapped the running stream; "device": opened its own. */
+  path: "session" | "device";
+  events: number;
+  samples: number;
+  sample_rate: number;
+  peak: number;
+  signal_detected: boolean;
+}
+
+export interface UtteranceAverage {
+  /** Utterances counted (a split utterance counts once). */
+  count: number;
+  /** 0 until the first utterance. */
+  avg_secs: number;
+}
+
+export interface UtteranceLengths {
+  mic: UtteranceAverage;
+  loopback: UtteranceAverage;
+}
+
+/** Payload of the "backend-status" event. */
+export interface BackendStatus {
+  url: string;
+  state: "connecting" | "connected" | "disconnected";
+  error: string | null;
+}
+
 export const tauriAPI = {
   // ── Window commands ──
   setClickthrough: (enabled: boolean) =>
@@ -20,21 +427,400 @@ export const tauriAPI = {
   setAlwaysOnTop: (enabled: boolean) =>
     invoke("set_always_on_top", { enabled }),
 
+  /**
+   * "normal" | "floating" (always-on-top) | "screen-saver" (also above
+   * full-screen apps; macOS and Windows only).
+   */
+  setWindowLevel: (level: "normal" | "floating" | "screen-saver") =>
+    invoke("set_window_level", { level }),
+
+  getWindowLevel: () => invoke<string>("get_window_level"),
+
   resizeWindow: (w: number, h: number) =>
     invoke("resize_window", { width: w, height: h }),
 
+  /** Shrinks the overlay to a top-right pill, or restores the full strip. */
+  setCompact: (compact: boolean) => invoke("set_compact", { compact }),
+
+  /** Moves the overlay, at its current size, into a corner of its monitor. */
+  pinCorner: (
+    corner: "top-left" | "top-right" | "bottom-left" | "bottom-right",
+    marginX: number = 10,
+    marginY: number = 10,
+  ) => invoke("pin_corner", { corner, marginX, marginY }),
+
   closeApp: () => invoke("close_app"),
 
   // ── Audio capture commands ──
   startAudioCapture: () => invoke<string>("start_audio_capture"),
 
+  /** Restarts capture on the devices used by the previous session. */
+  restartAudioCapture: () => invoke<RestartReport>("restart_audio_capture"),
+
   stopAudioCapture: () => invoke<string>("stop_audio_capture"),
 
   /** Drains all pending VAD-sliced audio chunks from the Rust side. */
   pollAudioChunks: () => invoke<AudioChunk[]>("poll_audio_chunks"),
 
+  /** Drains at most `max` of the oldest chunks; the rest wait for the next poll. */
+  pollAudioChunksLimited: (max: number) =>
+    invoke<AudioChunk[]>("poll_audio_chunks_limited", { max }),
+
   /** Returns [mic_secs, loopback_secs] for talk-to-listen ratio. */
   getTalkRatio: () => invoke<[number, number]>("get_talk_ratio"),
 
   listAudioDevices: () => invoke<AudioDevices>("list_audio_devices"),
+
+  /** "per-source" (default) or "mixed-mono" (one VAD over mic + loopback). */
+  setCaptureMode: (mode: "per-source" | "mixed-mono") =>
+    invoke("set_capture_mode", { mode }),
+
+  /** Attaches arbitrary metadata (language, confidence, speaker...) to an emitted chunk. */
+  annotateChunk: (seq: number, meta: unknown) =>
+    invoke("annotate_chunk", { seq, meta }),
+
+  getChunkMeta: (seq: number) => invoke<unknown | null>("get_chunk_meta", { seq }),
+
+  setMixGains: (micGain: number, loopbackGain: number) =>
+    invoke("set_mix_gains", { micGain, loopbackGain }),
+
+  /** Tracks the ambient noise floor; threshold = floor × margin. */
+  setAdaptiveVad: (enabled: boolean, margin: number) =>
+    invoke("set_adaptive_vad", { enabled, margin }),
+
+  /** Live per-source speaking flags (no silence-timeout lag). */
+  getSpeakingState: () =>
+    invoke<{ mic: boolean; loopback: boolean }>("get_speaking_state"),
+
+  /** Stricter VAD for system audio so UI sounds and music don't become chunks. */
+  setLoopbackRejection: (thresholdScale: number, minSpeechFrames: number) =>
+    invoke("set_loopback_rejection", { thresholdScale, minSpeechFrames }),
+
+  /** Writes the per-utterance talk log to CSV; resolves to the row count. */
+  exportTalkHistoryCsv: (path: string) =>
+    invoke<number>("export_talk_history_csv", { path }),
+
+  /** Soft-knee limiting before i16 conversion (off = bit-exact hard clamp). */
+  setLimiter: (enabled: boolean) => invoke("set_limiter", { enabled }),
+
+  /**
+   * Trailing silence that ends an utterance. `shortMs` applies after a
+   * rising-energy (question-like) tail.
+   */
+  setEndpointSilence: (ms: number, shortMs?: number) =>
+    invoke("set_endpoint_silence", { ms, shortMs: shortMs ?? null }),
+
+  /** Opens the resolved loopback device for a window and reports its levels. */
+  testLoopback: (durationMs: number) =>
+    invoke<LoopbackProbe>("test_loopback", { durationMs }),
+
+  /** Directory that unpolled chunks are saved to on quit (null disables). */
+  setRecordingDir: (dir: string | null) =>
+    invoke("set_recording_dir", { dir }),
+
+  listLoopbackCandidates: () =>
+    invoke<LoopbackCandidate[]>("list_loopback_candidates"),
+
+  /** Pins the loopback device for the next start (null = auto-detect). */
+  setLoopbackDevice: (name: string | null) =>
+    invoke("set_loopback_device", { name }),
+
+  /** Audio ignored by the VAD after each start (default 200 ms). */
+  setVadWarmup: (ms: number) => invoke("set_vad_warmup", { ms }),
+
+  /** Post-flush window where new speech must be sustained (default 150 ms). */
+  setVadCooldown: (ms: number) => invoke("set_vad_cooldown", { ms }),
+
+  /** Opt-in "energy-frame" level events, at most one per source per interval. */
+  setEnergyEvents: (enabled: boolean, intervalMs = 50) =>
+    invoke("set_energy_events", { enabled, intervalMs }),
+
+  /** Records exactly `durationSecs` of a source, bypassing the VAD. */
+  recordClip: (source: "mic" | "loopback", durationSecs: number) =>
+    invoke<AudioChunk>("record_clip", { source, durationSecs }),
+
+  /**
+   * Streams chunks from Rust straight to a WebSocket backend, reconnecting
+   * with backoff. Watch "backend-status" for the link state.
+   */
+  connectBackend: (url: string, relayToFrontend = true) =>
+    invoke("connect_backend", { url, relayToFrontend }),
+
+  disconnectBackend: () => invoke("disconnect_backend"),
+
+  /** 300–3400 Hz band-pass in front of the VAD; `filterOutput` also filters the emitted audio. */
+  setSpeechBandpass: (enabled: boolean, filterOutput = false) =>
+    invoke("set_speech_bandpass", { enabled, filterOutput }),
+
+  /** Skip resampling/VAD on a source after `ms` of silence (0 = off). */
+  setSuspendOnSilence: (ms: number) => invoke("set_suspend_on_silence", { ms }),
+
+  /** TPDF dither on the i16 conversion (off by default for bit-exact output). */
+  setDither: (enabled: boolean) => invoke("set_dither", { enabled }),
+
+  getDither: () => invoke<boolean>("get_dither"),
+
+  /** Resample target for one source; applies at the next start. */
+  setSourceTargetRate: (source: "mic" | "loopback", hz: number) =>
+    invoke("set_source_target_rate", { source, hz }),
+
+  /** Dry-run: builds (without starting) each source's stream and reports per-source success. */
+  validateCaptureConfig: () => invoke<ConfigValidation>("validate_capture_config"),
+
+  /** PCM encoding for emitted chunks; "f32" keeps full range at twice the size. */
+  setSampleType: (sampleType: "i16" | "f32") =>
+    invoke("set_sample_type", { sampleType }),
+
+  /** Scales down any chunk whose peak exceeds `value` dBFS (0 = off). */
+  setOutputCeilingDbfs: (value: number) =>
+    invoke("set_output_ceiling_dbfs", { value }),
+
+  /** Fixed device buffer in frames for the next start (0 = driver default). */
+  setBufferSize: (frames: number) => invoke("set_buffer_size", { frames }),
+
+  /** UUID stamped on chunks from the current capture session ("" before the first start). */
+  getSessionId: () => invoke<string>("get_session_id"),
+
+  /** STFT magnitudes for one of the last 32 chunks, computed in Rust. */
+  computeSpectrogram: (seq: number, nFft = 512, hop = 256) =>
+    invoke<Spectrogram>("compute_spectrogram", { seq, nFft, hop }),
+
+  /** Raises the mic threshold by `dbRaise` dB while loopback is speaking. */
+  setBargeInSuppression: (enabled: boolean, dbRaise = 12) =>
+    invoke("set_barge_in_suppression", { enabled, dbRaise }),
+
+  /** Clears VAD state, talk totals and queued chunks while the streams keep running. */
+  resetCaptureState: () => invoke("reset_capture_state"),
+
+  /** Attach a 50 ms RMS trace to every chunk (off by default). */
+  setEnergyTrace: (enabled: boolean) => invoke("set_energy_trace", { enabled }),
+
+  /** Drop chunks shorter than `secs` (0 = off). */
+  setMinChunkSecs: (secs: number) => invoke("set_min_chunk_secs", { secs }),
+
+  /** Which loopback resolution path this platform takes, and the device it finds. */
+  describeLoopbackStrategy: () => invoke<LoopbackStrategy>("describe_loopback_strategy"),
+
+  /** What to do when the chunk queue is full (default "buffer" = unbounded). */
+  setBackpressurePolicy: (policy: BackpressurePolicy) =>
+    invoke("set_backpressure_policy", { policy }),
+
+  getBufferStats: () => invoke<BufferStats>("get_buffer_stats"),
+
+  /** Simulated end-of-speech to chunk latency with the current settings. */
+  measureLatency: () => invoke<LatencyReport>("measure_latency"),
+
+  /** Auto-switch phase-inverted stereo to a first-channel downmix (default on). */
+  setPhaseAutocorrect: (enabled: boolean) =>
+    invoke("set_phase_autocorrect", { enabled }),
+
+  /** Per-source stream handle presence and last-callback liveness. */
+  getStreamHealth: () => invoke<StreamHealth>("get_stream_health"),
+
+  /** Largest audio_b64 per chunk (default 16 MiB); longer utterances are split. */
+  setMaxChunkB64Bytes: (bytes: number) =>
+    invoke("set_max_chunk_b64_bytes", { bytes }),
+
+  /**
+   * Swap the mic/loopback labels (and talk-ratio totals) of the running
+   * streams when auto-detection got them backwards. Returns the device now
+   * behind each label.
+   */
+  swapSources: () =>
+    invoke<{ mic: string; loopback: string }>("swap_sources"),
+
+  /**
+   * Play a WAV/MP3 file through the VAD as `sourceLabel` instead of a device.
+   * `speed` multiplies real time (default 1); 0 runs as fast as it decodes.
+   */
+  startFileSource: (path: string, sourceLabel: "mic" | "loopback", speed?: number) =>
+    invoke<FileSourceInfo>("start_file_source", { path, sourceLabel, speed }),
+
+  /**
+   * WASAPI exclusive mode. The audio backend only opens shared-mode
+   * streams, so enabling it rejects; disabling always succeeds.
+   */
+  setWasapiExclusive: (enabled: boolean) =>
+    invoke("set_wasapi_exclusive", { enabled }),
+
+  /**
+   * Silence frames each speech frame cancels during a pause, so noise blips
+   * can't hold an utterance open forever (0, the default, resets fully).
+   */
+  setVadReleaseDecrement: (frames: number) =>
+    invoke("set_vad_release_decrement", { frames }),
+
+  /** Talk ratio as a smoothed 0–100 balance score (50 = balanced). */
+  getBalanceScore: () => invoke<BalanceScore>("get_balance_score"),
+
+  /**
+   * Cancel the far end (loopback) from the mic before the VAD, for
+   * speakerphone setups. `tailMs` is the echo path length (default 128).
+   */
+  setEchoCancellation: (enabled: boolean, tailMs?: number) =>
+    invoke("set_echo_cancellation", { enabled, tailMs: tailMs ?? null }),
+
+  /** Keep the endpoint silence at the end of chunks (default true) or cut at the last speech frame. */
+  setIncludeTailSilence: (enabled: boolean) =>
+    invoke("set_include_tail_silence", { enabled }),
+
+  /** Per-source overrun counts since capture started. */
+  getXrunStats: () => invoke<XrunStats>("get_xrun_stats"),
+
+  /** Snapshot of every audio setting, to save or share as a profile. */
+  exportSettings: () => invoke<SettingsProfile>("export_settings"),
+
+  /** Applies an exported profile atomically; nothing changes if any value is invalid. */
+  importSettings: (profile: SettingsProfile) =>
+    invoke<ImportReport>("import_settings", { json: JSON.stringify(profile) }),
+
+  /** Reported vs. distinct channels per source, to spot fake stereo devices. */
+  getChannelStats: () => invoke<ChannelReport>("get_channel_stats"),
+
+  /**
+   * Raise the capture callback threads to max priority from the next start.
+   * Linux needs CAP_SYS_NICE or rtprio limits; denials only log a warning.
+   */
+  setRealtimePriority: (enabled: boolean) =>
+    invoke("set_realtime_priority", { enabled }),
+
+  /** True while either side is speaking, held briefly after to avoid flicker. */
+  anyoneSpeaking: () => invoke<boolean>("anyone_speaking"),
+
+  /** Trailing hold for anyoneSpeaking (default 300 ms, 0 disables). */
+  setSpeakingHold: (ms: number) => invoke("set_speaking_hold", { ms }),
+
+  /**
+   * "wav" prefixes each chunk with a WAV header so it plays directly:
+   * `new Audio("data:audio/wav;base64," + chunk.audio_b64)`. Default "raw".
+   */
+  setChunkContainer: (container: "raw" | "wav") =>
+    invoke("set_chunk_container", { container }),
+
+  /** Exclude a source (e.g. background music on loopback) from the talk ratio; its chunks still emit. */
+  setSourceCountsInRatio: (source: "mic" | "loopback", counts: boolean) =>
+    invoke("set_source_counts_in_ratio", { source, counts }),
+
+  /** Capture the mic at a specific rate/channel count from the next start (0, 0 clears). Rejected if the device doesn't advertise it. */
+  setPreferredInputConfig: (sampleRate: number, channels: number) =>
+    invoke("set_preferred_input_config", { sampleRate, channels }),
+
+  /** Enable the "wake" event: fires when the mic speaks for `speechMs` after a stretch of silence. */
+  setWakeTrigger: (enabled: boolean, speechMs: number) =>
+    invoke("set_wake_trigger", { enabled, speechMs }),
+
+  /** Write a source's buffered chunks from its latest session to one WAV, with silence (or comfort noise at `comfortNoiseDbfs`, -90..-30) for the gaps. */
+  exportSourceSessionWav: (source: "mic" | "loopback" | "mixed", path: string, comfortNoiseDbfs?: number) =>
+    invoke<SourceExport>("export_source_session_wav", {
+      source,
+      path,
+      comfortNoiseDbfs: comfortNoiseDbfs ?? null,
+    }),
+
+  /** Boost the averaged downmix so a one-active-channel device isn't attenuated. Gain defaults to sqrt(channels). */
+  setDownmixCompensation: (enabled: boolean, gain?: number) =>
+    invoke("set_downmix_compensation", { enabled, gain: gain ?? null }),
+
+  /** Version, commit, target and enabled features of the running build, for bug reports. */
+  getBuildInfo: () => invoke<BuildInfo>("get_build_info"),
+
+  /** Stop capture after `minutes` without speech (0 disables), optionally reopening `rearmMinutes` later (0 stays stopped). */
+  setIdleStop: (minutes: number, rearmMinutes: number = 0) =>
+    invoke("set_idle_stop", { minutes, rearmMinutes }),
+
+  /** Record VAD transitions for getVadLog (debugging aid). `clear` empties what was logged. */
+  setVadLog: (enabled: boolean, clear: boolean = false) =>
+    invoke("set_vad_log", { enabled, clear }),
+
+  /** Recent VAD transitions per source, for tracing why an utterance was cut where it was. */
+  getVadLog: () => invoke<VadLog>("get_vad_log"),
+
+  /** Replace the mic VAD with push-to-talk (loopback keeps its VAD). */
+  setPttMode: (enabled: boolean) => invoke("set_ptt_mode", { enabled }),
+
+  /** Press (true) or release (false) push-to-talk; the release ends the utterance. */
+  setPtt: (active: boolean) => invoke("set_ptt", { active }),
+
+  /** A recently emitted chunk (by seq) resampled to `targetRate`, e.g. 48 kHz for playback. */
+  resampleChunk: (seq: number, targetRate: number) =>
+    invoke<AudioChunk>("resample_chunk", { seq, targetRate }),
+
+  /** Level (dBFS, -120..0) below which meters report silence; default -90. */
+  setMeterFloor: (dbfs: number) => invoke("set_meter_floor", { dbfs }),
+
+  /** Pause capture (devices stay open) while the overlay is hidden or minimized. */
+  setCaptureWhenVisibleOnly: (enabled: boolean) =>
+    invoke("set_capture_when_visible_only", { enabled }),
+
+  getCaptureStatus: () => invoke<CaptureStatus>("get_capture_status"),
+
+  /** Filter the VAD decides on; emitted audio stays full-band. */
+  setVadAnalysisFilter: (kind: "none" | "highpass" | "bandpass") =>
+    invoke("set_vad_analysis_filter", { kind }),
+
+  /** Host, devices, resolved selections and settings in one object, for bug reports. Never rejects on device errors. */
+  diagnosticsSnapshot: () => invoke<Record<string, unknown>>("diagnostics_snapshot"),
+
+  /** Peak-normalize each utterance to `targetDbfs` (default -1); `boost` also raises quiet ones. */
+  setPeakNormalize: (enabled: boolean, targetDbfs?: number, boost = false) =>
+    invoke("set_peak_normalize", { enabled, targetDbfs: targetDbfs ?? null, boost }),
+
+  /** Speech per source in fixed buckets (empty ones are zeros), for a participation timeline. */
+  getTalkRatioBuckets: (bucketSecs: number) =>
+    invoke<TalkBucket[]>("get_talk_ratio_buckets", { bucketSecs }),
+
+  /** OS mic permission; on macOS `prompt` asks the user if they haven't been asked (check again afterwards). */
+  checkMicPermission: (prompt = false) =>
+    invoke<MicPermission>("check_mic_permission", { prompt }),
+
+  /** Keep all audio in fixed windows (default 10 s) marked with contains_speech, instead of speech-only utterances. */
+  setContinuousRecord: (enabled: boolean, windowSecs?: number) =>
+    invoke("set_continuous_record", { enabled, windowSecs: windowSecs ?? null }),
+
+  /** Live per-stage latency (buffer, resampler, VAD endpoint, encode) for each source. */
+  getLatencyBreakdown: () =>
+    invoke<{ mic: StageLatency; loopback: StageLatency }>("get_latency_breakdown"),
+
+  /** Probe loopback strategies in order on start and keep the first with signal (emits "loopback-strategy"). */
+  setLoopbackFallback: (enabled: boolean, probeMs?: number) =>
+    invoke("set_loopback_fallback", { enabled, probeMs: probeMs ?? null }),
+
+  /** Label a source's chunks with a speaker name ("" clears it back to the source name). */
+  setSourceSpeaker: (source: "mic" | "loopback", name: string) =>
+    invoke("set_source_speaker", { source, name }),
+
+  /** Recently emitted chunks attributed to `speaker`, oldest first. */
+  getRecentChunksBySpeaker: (speaker: string) =>
+    invoke<AudioChunk[]>("get_recent_chunks_by_speaker", { speaker }),
+
+  /** Move the mic to another input mid-session without stopping; the old device's utterance is flushed first. */
+  switchMicDevice: (name: string) =>
+    invoke<MicDeviceChanged>("switch_mic_device", { name }),
+
+  /** Hard cap (default 60 s) past which buffered audio is flushed whatever the VAD says. */
+  setMaxUtteranceSecs: (secs: number) =>
+    invoke("set_max_utterance_secs", { secs }),
+
+  /** Follow every utterance with a zero-length chunk whose marker is "turn-end". */
+  setTurnEndMarkers: (enabled: boolean) =>
+    invoke("set_turn_end_markers", { enabled }),
+
+  /** Flush the source's utterance in progress and emit a "turn-end" marker now. */
+  markTurnEnd: (source: "mic" | "loopback" | "mixed") =>
+    invoke("mark_turn_end", { source }),
+
+  /** Stream a source's audio, bypassing the VAD, as "raw-audio" events for `durationMs` (max 30 s). */
+  diagnosticRawCapture: (source: "mic" | "loopback", durationMs: number) =>
+    invoke<RawCaptureReport>("diagnostic_raw_capture", { source, durationMs }),
+
+  /**
+   * Order of utterances that end in the same flush (stop, mode change):
+   * "onset" (earliest speech first, default) or "source" (mic, loopback, mixed).
+   */
+  setFlushOrder: (order: "onset" | "source") =>
+    invoke("set_flush_order", { order }),
+
+  /** Average utterance length and count per source, over the talk-ratio utterances; reset with it. */
+  getAvgUtteranceLength: () =>
+    invoke<UtteranceLengths>("get_avg_utterance_length"),
 };